@@ -0,0 +1,81 @@
+use std::{
+  cell::RefCell,
+  collections::VecDeque,
+  io::{self, Read, Write},
+  rc::Rc,
+};
+
+use serde_json::json;
+
+use crate::transport::Transport;
+
+pub(crate) type Shared<W> = Rc<RefCell<Transport<W>>>;
+
+/// Guest stdout, forwarded to the client as `output` events.
+pub(crate) struct ConsoleOut<W> {
+  pub io: Shared<W>,
+}
+
+impl<W: Write> Write for ConsoleOut<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self
+      .io
+      .borrow_mut()
+      .output("stdout", &String::from_utf8_lossy(buf))?;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Guest stdin, fed by lines typed into the debug console.
+pub(crate) struct ConsoleIn<W> {
+  pub io: Shared<W>,
+  pub input: Rc<RefCell<VecDeque<u8>>>,
+}
+
+impl<W: Write> ConsoleIn<W> {
+  /// Waits for the client to send a line through the debug console. Other
+  /// requests are handled once the guest stops; a disconnect ends input.
+  fn wait_for_line(&mut self) -> io::Result<()> {
+    let mut io = self.io.borrow_mut();
+    io.output("console", "Waiting for input from the debug console\n")?;
+
+    while let Some(msg) = io.recv()? {
+      let command = msg["command"].as_str().unwrap_or_default();
+      if command == "evaluate" && msg["arguments"]["context"] == "repl" {
+        let line = msg["arguments"]["expression"].as_str().unwrap_or_default();
+        self
+          .input
+          .borrow_mut()
+          .extend(line.bytes().chain(Some(b'\n')));
+        io.respond(&msg, json!({ "result": "", "variablesReference": 0 }))?;
+        return Ok(());
+      }
+
+      let stop = matches!(command, "disconnect" | "terminate");
+      io.defer(msg);
+      if stop {
+        break;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<W: Write> Read for ConsoleIn<W> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.input.borrow().is_empty() {
+      self.wait_for_line()?;
+    }
+
+    let mut input = self.input.borrow_mut();
+    let n = buf.len().min(input.len());
+    for (dst, src) in buf.iter_mut().zip(input.drain(..n)) {
+      *dst = src;
+    }
+    Ok(n)
+  }
+}
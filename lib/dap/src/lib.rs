@@ -0,0 +1,24 @@
+//! A Debug Adapter Protocol server for the MIPS emulator, so editors such
+//! as VS Code can launch and debug `.asm` sources and ELF executables.
+//!
+//! Guest output appears in the debug console, and lines typed into the
+//! console are fed to the guest's stdin. ELF executables have no source,
+//! so they are shown and stepped as a disassembly of their text.
+
+mod console;
+mod session;
+mod transport;
+
+use std::io::{self, Read, Write};
+
+pub use session::Session;
+pub use transport::Transport;
+
+/// Serves one client over `reader` and `writer` until it disconnects.
+pub fn serve<R, W>(reader: R, writer: W) -> io::Result<()>
+where
+  R: 'static + Read + Send,
+  W: 'static + Write,
+{
+  Session::new(reader, writer).run().map(drop)
+}
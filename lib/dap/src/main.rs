@@ -0,0 +1,11 @@
+use std::{io, process::ExitCode};
+
+fn main() -> ExitCode {
+  match mips_dap::serve(io::stdin(), io::stdout()) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("mips-dap: {}", e);
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,588 @@
+use std::{
+  cell::RefCell,
+  collections::VecDeque,
+  fs,
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+  rc::Rc,
+};
+
+use mips::{
+  asm::{assemble, Program},
+  emulator::{
+    arch::Register, cpu::Cpu, debug::StopReason, disasm::disassemble,
+    interrupt::Result as EmuResult, sys::Sys, virt::MemRegion,
+  },
+};
+use serde_json::{json, Value};
+
+use crate::{
+  console::{ConsoleIn, ConsoleOut, Shared},
+  transport::Transport,
+};
+
+/// The only thread the machine has.
+const THREAD_ID: i64 = 1;
+
+/// `variablesReference` of the Registers scope.
+const REGISTERS: i64 = 1;
+
+/// `sourceReference` of the disassembly shown for programs without source.
+const LISTING: i64 = 1;
+
+/// Special registers shown after the general-purpose ones.
+const SPECIAL: [&str; 3] = ["pc", "hi", "lo"];
+
+/// Instructions run between checks for a pause or disconnect while
+/// continuing.
+const POLL_INTERVAL: u64 = 4096;
+
+#[derive(Debug, Clone, Copy)]
+enum Resume {
+  Continue,
+  /// Over calls, until the source line changes
+  Next,
+  /// Into calls, until the source line changes
+  StepIn,
+  StepOut,
+}
+
+/// One debugging session: a client, and the program it launched.
+pub struct Session<W> {
+  io: Shared<W>,
+  /// Lines typed into the debug console for the guest to read
+  input: Rc<RefCell<VecDeque<u8>>>,
+  sys: Option<Sys>,
+  /// Line information, when the program was assembled from source
+  program: Option<Program>,
+  /// The launched file
+  source: Option<PathBuf>,
+  /// Code shown as disassembly, one instruction per line, when there is
+  /// no source
+  listing: Option<MemRegion>,
+  /// Addresses of the breakpoints set on source lines
+  line_breaks: Vec<u32>,
+  stop_on_entry: bool,
+  exited: bool,
+  done: bool,
+  /// Execution to start once the current request has been answered
+  pending: Option<Resume>,
+}
+
+type Reply = Result<Value, String>;
+
+impl<W> Session<W>
+where
+  W: 'static + Write,
+{
+  pub fn new<R>(reader: R, writer: W) -> Self
+  where
+    R: 'static + Read + Send,
+  {
+    Session {
+      io: Rc::new(RefCell::new(Transport::new(reader, writer))),
+      input: Rc::default(),
+      sys: None,
+      program: None,
+      source: None,
+      listing: None,
+      line_breaks: Vec::new(),
+      stop_on_entry: false,
+      exited: false,
+      done: false,
+      pending: None,
+    }
+  }
+
+  /// Serves requests until the client disconnects, returning the writer.
+  pub fn run(mut self) -> io::Result<W> {
+    while !self.done {
+      let Some(msg) = self.io.borrow_mut().next_request()? else {
+        break;
+      };
+      if msg["type"] == "request" {
+        self.handle(&msg)?;
+      }
+    }
+
+    /* The machine holds the other handles to the transport */
+    drop(self.sys.take());
+    let io = Rc::try_unwrap(self.io)
+      .map_err(|_| io::Error::other("transport still in use after the session ended"))?;
+    Ok(io.into_inner().into_writer())
+  }
+
+  fn handle(&mut self, request: &Value) -> io::Result<()> {
+    let args = &request["arguments"];
+    let reply = match request["command"].as_str().unwrap_or_default() {
+      "initialize" => Ok(json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsEvaluateForHovers": true,
+        "supportsSetVariable": true,
+        "supportsReadMemoryRequest": true,
+        "supportsTerminateRequest": true,
+      })),
+      "launch" => self.launch(args),
+      "setBreakpoints" => self.set_breakpoints(args),
+      "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+      "configurationDone" => {
+        self.pending = (!self.stop_on_entry).then_some(Resume::Continue);
+        Ok(json!({}))
+      }
+      "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+      "stackTrace" => self.stack_trace(),
+      "scopes" => Ok(json!({
+        "scopes": [{
+          "name": "Registers",
+          "presentationHint": "registers",
+          "variablesReference": REGISTERS,
+          "expensive": false,
+        }],
+      })),
+      "source" => self.source(args),
+      "variables" => self.variables(args),
+      "setVariable" => self.set_variable(args),
+      "evaluate" => self.evaluate(args),
+      "readMemory" => self.read_memory(args),
+      "continue" => self.resume(Resume::Continue, json!({ "allThreadsContinued": true })),
+      "next" => self.resume(Resume::Next, json!({})),
+      "stepIn" => self.resume(Resume::StepIn, json!({})),
+      "stepOut" => self.resume(Resume::StepOut, json!({})),
+      /* A pause while continuing is answered by the transport, so the
+       * program is already stopped here */
+      "pause" => Ok(json!({})),
+      "disconnect" | "terminate" => {
+        self.done = true;
+        Ok(json!({}))
+      }
+      command => Err(format!("Unsupported request: {}", command)),
+    };
+
+    let mut io = self.io.borrow_mut();
+    match reply {
+      Ok(body) => io.respond(request, body)?,
+      Err(message) => io.fail(request, &message)?,
+    }
+
+    match request["command"].as_str() {
+      Some("initialize") => {}
+      Some("launch") if self.sys.is_some() => io.event("initialized", json!({}))?,
+      Some("configurationDone") if self.stop_on_entry => io.event(
+        "stopped",
+        json!({ "reason": "entry", "threadId": THREAD_ID }),
+      )?,
+      Some("terminate") => io.event("terminated", json!({}))?,
+      _ => {}
+    }
+    drop(io);
+
+    match self.pending.take() {
+      Some(how) => self.execute(how),
+      None => Ok(()),
+    }
+  }
+
+  fn sys(&mut self) -> Result<&mut Sys, String> {
+    self
+      .sys
+      .as_mut()
+      .ok_or_else(|| "No program is running".to_string())
+  }
+
+  fn launch(&mut self, args: &Value) -> Reply {
+    let path = args["program"]
+      .as_str()
+      .ok_or("Missing \"program\" in launch configuration")?;
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut cpu = Cpu::new();
+    if bytes.starts_with(b"\x7fELF") {
+      cpu
+        .load_elf(&bytes)
+        .map_err(|e| format!("{}: {}", path, e))?;
+      self.listing = cpu.bus.mapping(cpu.pc).map(|m| m.region);
+    } else {
+      let src = String::from_utf8_lossy(&bytes);
+      let program = assemble(&src).map_err(|e| format!("{}: {}", path, e))?;
+      cpu.load_program(&program).map_err(|e| e.to_string())?;
+      self.program = Some(program);
+    }
+    self.source = Some(PathBuf::from(path));
+
+    let stdout = ConsoleOut {
+      io: self.io.clone(),
+    };
+    let stdin = ConsoleIn {
+      io: self.io.clone(),
+      input: self.input.clone(),
+    };
+    self.sys = Some(Sys::new(cpu, stdout, stdin));
+    self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+    Ok(json!({}))
+  }
+
+  fn set_breakpoints(&mut self, args: &Value) -> Reply {
+    let path = args["source"]["path"].as_str().map(Path::new);
+    let lines: Vec<usize> = args["breakpoints"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter_map(|bp| bp["line"].as_u64())
+      .map(|line| line as usize)
+      .collect();
+
+    let sys = self.sys.as_mut().ok_or("No program is running")?;
+    for addr in self.line_breaks.drain(..) {
+      sys.clear_breakpoint(addr);
+    }
+
+    let program = self
+      .program
+      .as_ref()
+      .filter(|_| path.is_some() && same_file(path, self.source.as_deref()));
+    let listing = self
+      .listing
+      .filter(|_| args["source"]["sourceReference"] == LISTING);
+    let resolve = |line| match listing {
+      Some(listing) => listing_addr(listing, line).map(|addr| (line, addr)),
+      None => program.and_then(|p| p.resolve_line(line)),
+    };
+
+    let breakpoints = lines
+      .iter()
+      .map(|&line| match resolve(line) {
+        Some((line, addr)) => {
+          sys.set_breakpoint(addr);
+          self.line_breaks.push(addr);
+          json!({ "verified": true, "line": line })
+        }
+        None => json!({
+          "verified": false,
+          "line": line,
+          "message": "No code at or after this line",
+        }),
+      })
+      .collect::<Vec<_>>();
+
+    Ok(json!({ "breakpoints": breakpoints }))
+  }
+
+  fn stack_trace(&mut self) -> Reply {
+    let pc = self.sys()?.cpu().pc;
+
+    let mut frame = json!({
+      "id": 0,
+      "name": self.symbol_at(pc),
+      "line": 0,
+      "column": 0,
+      "instructionPointerReference": format!("0x{:08x}", pc),
+    });
+    let line = self.program.as_ref().and_then(|p| p.line_at(pc));
+    let name = self
+      .source
+      .as_ref()
+      .and_then(|s| s.file_name())
+      .map(|n| n.to_string_lossy().into_owned());
+    if let (Some(line), Some(source)) = (line, &self.source) {
+      frame["line"] = json!(line);
+      frame["column"] = json!(1);
+      frame["source"] = json!({ "name": name, "path": source });
+    } else if let Some(listing) = self.listing.filter(|l| l.contains(pc)) {
+      frame["line"] = json!((pc - listing.base) / 4 + 1);
+      frame["column"] = json!(1);
+      frame["source"] = json!({
+        "name": format!("{} (disassembly)", name.unwrap_or_default()),
+        "sourceReference": LISTING,
+        "presentationHint": "deemphasize",
+      });
+    }
+
+    Ok(json!({ "stackFrames": [frame], "totalFrames": 1 }))
+  }
+
+  /// The nearest label at or before `addr`, with the offset from it.
+  fn symbol_at(&self, addr: u32) -> String {
+    let symbol = self
+      .program
+      .as_ref()
+      .into_iter()
+      .flat_map(|p| &p.symbols)
+      .filter(|&(_, &a)| a <= addr)
+      .max_by_key(|&(_, &a)| a);
+
+    match symbol {
+      Some((name, &a)) if a == addr => name.clone(),
+      Some((name, &a)) => format!("{}+{}", name, addr - a),
+      None => format!("0x{:08x}", addr),
+    }
+  }
+
+  /// The disassembly listing, the only source the client cannot read
+  /// from disk.
+  fn source(&mut self, args: &Value) -> Reply {
+    let reference = &args["sourceReference"];
+    let listing = match self.listing {
+      Some(listing) if *reference == LISTING => listing,
+      _ => return Err(format!("Unknown source reference: {}", reference)),
+    };
+
+    let cpu = self.sys()?.cpu();
+    let content: String = (listing.base..listing.end())
+      .step_by(4)
+      .map(|addr| match cpu.bus.load(addr, 32) {
+        Ok(inst) => format!(
+          "{:08x}:  {:08x}  {}\n",
+          addr,
+          inst,
+          disassemble(inst, addr, cpu.isa)
+        ),
+        Err(_) => format!("{:08x}:  ????????\n", addr),
+      })
+      .collect();
+
+    Ok(json!({ "content": content }))
+  }
+
+  fn variables(&mut self, args: &Value) -> Reply {
+    if args["variablesReference"] != REGISTERS {
+      return Ok(json!({ "variables": [] }));
+    }
+
+    let cpu = self.sys()?.cpu();
+    let variable = |name: String, value: u32| {
+      json!({
+        "name": name,
+        "value": format!("0x{:08x}", value),
+        "memoryReference": format!("0x{:08x}", value),
+        "variablesReference": 0,
+      })
+    };
+
+    let mut variables: Vec<Value> = Register::NAMES
+      .iter()
+      .zip(cpu.regs)
+      .map(|(name, value)| variable(format!("${}", name), value))
+      .collect();
+    variables.extend(
+      SPECIAL
+        .iter()
+        .zip([cpu.pc, cpu.hi, cpu.lo])
+        .map(|(name, value)| variable(name.to_string(), value)),
+    );
+
+    Ok(json!({ "variables": variables }))
+  }
+
+  fn set_variable(&mut self, args: &Value) -> Reply {
+    let name = args["name"].as_str().unwrap_or_default();
+    let text = args["value"].as_str().unwrap_or_default();
+    let value = parse_value(text).ok_or_else(|| format!("Invalid value: {}", text))?;
+
+    let cpu = self.sys()?.cpu_mut();
+    match name {
+      "pc" => cpu.pc = value,
+      "hi" => cpu.hi = value,
+      "lo" => cpu.lo = value,
+      _ => match Register::from_name(name) {
+        Some(Register::ZERO) => return Err("$zero is read-only".to_string()),
+        Some(r) => cpu.regs[r] = value,
+        None => return Err(format!("Unknown register: {}", name)),
+      },
+    }
+
+    Ok(json!({ "value": format!("0x{:08x}", value) }))
+  }
+
+  /// Registers can be inspected by name. Anything else typed into the
+  /// debug console becomes a line of input for the guest.
+  fn evaluate(&mut self, args: &Value) -> Reply {
+    let expr = args["expression"].as_str().unwrap_or_default().trim();
+    let repl = args["context"] == "repl";
+
+    let cpu = self.sys()?.cpu();
+    let value = match expr {
+      "pc" | "$pc" => Some(cpu.pc),
+      "hi" | "$hi" => Some(cpu.hi),
+      "lo" | "$lo" => Some(cpu.lo),
+      _ if repl && !expr.starts_with('$') => None,
+      _ => Register::from_name(expr).map(|r| cpu.regs[r]),
+    };
+
+    match value {
+      Some(value) => Ok(json!({
+        "result": format!("0x{:08x} ({})", value, value as i32),
+        "variablesReference": 0,
+      })),
+      None if repl => {
+        let line = args["expression"].as_str().unwrap_or_default();
+        self
+          .input
+          .borrow_mut()
+          .extend(line.bytes().chain(Some(b'\n')));
+        Ok(json!({ "result": "", "variablesReference": 0 }))
+      }
+      None => Err(format!("Not a register: {}", expr)),
+    }
+  }
+
+  fn read_memory(&mut self, args: &Value) -> Reply {
+    let reference = args["memoryReference"].as_str().unwrap_or_default();
+    let base = parse_value(reference).ok_or_else(|| format!("Invalid address: {}", reference))?;
+    let addr = base.wrapping_add(args["offset"].as_i64().unwrap_or(0) as u32);
+    let count = args["count"].as_u64().unwrap_or(0) as u32;
+
+    let bus = &self.sys()?.cpu().bus;
+    let data: Vec<u8> = (0..count)
+      .map_while(|i| bus.load(addr.wrapping_add(i), 8).ok().map(|b| b as u8))
+      .collect();
+
+    Ok(json!({
+      "address": format!("0x{:08x}", addr),
+      "data": base64(&data),
+      "unreadableBytes": count - data.len() as u32,
+    }))
+  }
+
+  fn resume(&mut self, how: Resume, body: Value) -> Reply {
+    self.sys()?;
+    self.pending = Some(how);
+    Ok(body)
+  }
+
+  /// Runs the machine as requested, then tells the client why it stopped.
+  fn execute(&mut self, how: Resume) -> io::Result<()> {
+    if self.exited {
+      return self.io.borrow_mut().event("terminated", json!({}));
+    }
+
+    let Some(sys) = self.sys.as_mut() else {
+      return Ok(());
+    };
+    let program = self.program.as_ref();
+    let result = match how {
+      Resume::Continue => continue_polling(sys, &self.io)?,
+      Resume::Next => step_line(sys, program, Sys::step_over),
+      Resume::StepIn => step_line(sys, program, Sys::step),
+      Resume::StepOut => sys.step_out(),
+    };
+
+    let mut io = self.io.borrow_mut();
+    let (reason, text) = match result {
+      Ok(StopReason::Exited(code)) => {
+        self.exited = true;
+        io.event("exited", json!({ "exitCode": code }))?;
+        return io.event("terminated", json!({}));
+      }
+      Ok(StopReason::Step | StopReason::Reached(_)) => ("step", None),
+      Ok(StopReason::Breakpoint(_)) => ("breakpoint", None),
+      Ok(StopReason::Watchpoint(hit)) => ("data breakpoint", Some(hit.to_string())),
+      Ok(StopReason::LimitExceeded(limit)) => ("exception", Some(limit.to_string())),
+      Ok(StopReason::Interrupted) => ("pause", None),
+      Err(e) => {
+        io.output("stderr", &format!("{}\n", e))?;
+        ("exception", Some(e.to_string()))
+      }
+    };
+
+    let mut body = json!({
+      "reason": reason,
+      "threadId": THREAD_ID,
+      "allThreadsStopped": true,
+    });
+    if let Some(text) = text {
+      body["text"] = json!(text);
+    }
+    io.event("stopped", body)
+  }
+}
+
+/// Continues until the program stops or the client pauses, disconnects or
+/// hangs up, checking every [`POLL_INTERVAL`] instructions.
+fn continue_polling<W: Write>(sys: &mut Sys, io: &Shared<W>) -> io::Result<EmuResult<StopReason>> {
+  loop {
+    match sys.cont_for(POLL_INTERVAL) {
+      Ok(StopReason::Step) if io.borrow_mut().interrupted()? => {
+        sys.stop_clock();
+        return Ok(Ok(StopReason::Interrupted));
+      }
+      Ok(StopReason::Step) => {}
+      res => return Ok(res),
+    }
+  }
+}
+
+/// Steps until execution reaches a different source line. Without line
+/// information, a single step.
+fn step_line<F>(sys: &mut Sys, program: Option<&Program>, step: F) -> EmuResult<StopReason>
+where
+  F: Fn(&mut Sys) -> EmuResult<StopReason>,
+{
+  let Some(program) = program else {
+    return step(sys);
+  };
+
+  let start = program.line_at(sys.cpu().pc);
+  loop {
+    let reason = step(sys)?;
+    let line = program.line_at(sys.cpu().pc);
+    if reason != StopReason::Step || line.is_none() || line != start {
+      return Ok(reason);
+    }
+  }
+}
+
+/// Address of the instruction on `line` of the disassembly listing.
+fn listing_addr(listing: MemRegion, line: usize) -> Option<u32> {
+  let offset = u32::try_from(line.checked_sub(1)?).ok()?.checked_mul(4)?;
+  let addr = listing.base.checked_add(offset)?;
+  listing.contains(addr).then_some(addr)
+}
+
+fn same_file(a: Option<&Path>, b: Option<&Path>) -> bool {
+  match (a, b) {
+    (Some(a), Some(b)) => match (a.canonicalize(), b.canonicalize()) {
+      (Ok(a), Ok(b)) => a == b,
+      _ => a == b,
+    },
+    _ => false,
+  }
+}
+
+/// Parses a decimal or `0x` hexadecimal value, allowing negative numbers.
+fn parse_value(text: &str) -> Option<u32> {
+  let text = text.trim();
+  let (negative, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text),
+  };
+
+  let value = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+    None => digits.parse::<u32>().ok()?,
+  };
+  Some(if negative {
+    value.wrapping_neg()
+  } else {
+    value
+  })
+}
+
+fn base64(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+  let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+    for i in 0..4 {
+      match i <= chunk.len() {
+        true => out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char),
+        false => out.push('='),
+      }
+    }
+  }
+  out
+}
@@ -0,0 +1,190 @@
+use std::{
+  collections::VecDeque,
+  io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+  sync::mpsc::{self, Receiver, TryRecvError},
+  thread,
+};
+
+use serde_json::{json, Value};
+
+/// Frames DAP messages as a `Content-Length` header followed by JSON, and
+/// numbers everything sent with the protocol's `seq`.
+///
+/// Messages are read on a thread of their own, so the client can be
+/// polled while the guest runs.
+pub struct Transport<W> {
+  incoming: Receiver<io::Result<Value>>,
+  /// The client has closed its end
+  closed: bool,
+  writer: W,
+  seq: i64,
+  /// Requests that arrived while the guest was waiting for input or
+  /// running
+  deferred: VecDeque<Value>,
+}
+
+impl<W: Write> Transport<W> {
+  pub fn new<R>(reader: R, writer: W) -> Self
+  where
+    R: 'static + Read + Send,
+  {
+    let (sent, incoming) = mpsc::channel();
+    thread::spawn(move || {
+      let mut reader = BufReader::new(reader);
+      loop {
+        let msg = match read_message(&mut reader) {
+          Ok(Some(msg)) => Ok(msg),
+          Ok(None) => break,
+          Err(e) => Err(e),
+        };
+        let failed = msg.is_err();
+        if sent.send(msg).is_err() || failed {
+          break;
+        }
+      }
+    });
+
+    Transport {
+      incoming,
+      closed: false,
+      writer,
+      seq: 1,
+      deferred: VecDeque::new(),
+    }
+  }
+
+  pub fn into_writer(self) -> W {
+    self.writer
+  }
+
+  /// Receives the next message, or `None` once the client disconnects.
+  pub fn recv(&mut self) -> io::Result<Option<Value>> {
+    match self.incoming.recv() {
+      Ok(msg) => msg.map(Some),
+      Err(_) => {
+        self.closed = true;
+        Ok(None)
+      }
+    }
+  }
+
+  /// Checks, without waiting, whether the client wants the running guest
+  /// to stop: it sent `pause`, `disconnect` or `terminate`, or hung up.
+  /// A pause is answered here; every other request waits in the deferred
+  /// queue until the guest stops.
+  pub fn interrupted(&mut self) -> io::Result<bool> {
+    let mut interrupted = self.closed;
+    loop {
+      let msg = match self.incoming.try_recv() {
+        Ok(msg) => msg?,
+        Err(TryRecvError::Empty) => break,
+        Err(TryRecvError::Disconnected) => {
+          self.closed = true;
+          interrupted = true;
+          break;
+        }
+      };
+
+      match msg["command"].as_str() {
+        Some("pause") => {
+          self.respond(&msg, json!({}))?;
+          interrupted = true;
+        }
+        Some("disconnect" | "terminate") => {
+          self.defer(msg);
+          interrupted = true;
+        }
+        _ => self.defer(msg),
+      }
+    }
+    Ok(interrupted)
+  }
+
+  /// The next message to handle, taking deferred requests first.
+  pub fn next_request(&mut self) -> io::Result<Option<Value>> {
+    match self.deferred.pop_front() {
+      Some(msg) => Ok(Some(msg)),
+      None => self.recv(),
+    }
+  }
+
+  pub fn defer(&mut self, msg: Value) {
+    self.deferred.push_back(msg);
+  }
+
+  fn send(&mut self, mut msg: Value) -> io::Result<()> {
+    msg["seq"] = json!(self.seq);
+    self.seq += 1;
+
+    let body = msg.to_string();
+    write!(
+      self.writer,
+      "Content-Length: {}\r\n\r\n{}",
+      body.len(),
+      body
+    )?;
+    self.writer.flush()
+  }
+
+  pub fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+    self.send(json!({
+      "type": "response",
+      "request_seq": request["seq"],
+      "command": request["command"],
+      "success": true,
+      "body": body,
+    }))
+  }
+
+  pub fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+    self.send(json!({
+      "type": "response",
+      "request_seq": request["seq"],
+      "command": request["command"],
+      "success": false,
+      "message": message,
+    }))
+  }
+
+  pub fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+    self.send(json!({
+      "type": "event",
+      "event": event,
+      "body": body,
+    }))
+  }
+
+  pub fn output(&mut self, category: &str, text: &str) -> io::Result<()> {
+    self.event("output", json!({ "category": category, "output": text }))
+  }
+}
+
+/// Reads one message, or `None` at the end of the stream.
+fn read_message<R: Read>(reader: &mut BufReader<R>) -> io::Result<Option<Value>> {
+  let mut length = None;
+  let mut header = String::new();
+  loop {
+    header.clear();
+    if reader.read_line(&mut header)? == 0 {
+      return Ok(None);
+    }
+
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      if name.trim().eq_ignore_ascii_case("Content-Length") {
+        length = value.trim().parse::<usize>().ok();
+      }
+    }
+  }
+
+  let length =
+    length.ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "missing Content-Length"))?;
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body)?;
+  serde_json::from_slice(&body)
+    .map(Some)
+    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,411 @@
+use std::{fs, io::Cursor, path::PathBuf};
+
+use mips_dap::Session;
+use serde_json::{json, Value};
+
+const PROGRAM: &str = "\
+        .data
+msg:    .asciiz \"hello\\n\"
+
+        .text
+main:   li    $t0, 1
+        jal   double
+        addiu $t0, $t0, 1
+
+        la    $a0, msg
+        li    $v0, 4
+        syscall
+        li    $a0, 3
+        li    $v0, 17
+        syscall
+
+double: addu  $t0, $t0, $t0
+        jr    $ra
+";
+
+/// Writes `src` to a file unique to the calling test.
+fn source(name: &str, src: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("mips-dap-{}-{}.asm", std::process::id(), name));
+  fs::write(&path, src).unwrap();
+  path
+}
+
+/// Runs a scripted session, returning every message the adapter sent.
+fn session(requests: Vec<Value>) -> Vec<Value> {
+  let mut input = Vec::new();
+  for (seq, mut request) in requests.into_iter().enumerate() {
+    request["seq"] = json!(seq + 1);
+    request["type"] = json!("request");
+    let body = request.to_string();
+    input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+  }
+
+  let output = Session::new(Cursor::new(input), Vec::new()).run().unwrap();
+  let mut output = output.as_slice();
+  let mut messages = Vec::new();
+  while let Some(start) = output.windows(4).position(|w| w == b"\r\n\r\n") {
+    let header = std::str::from_utf8(&output[..start]).unwrap();
+    let length: usize = header["Content-Length: ".len()..].parse().unwrap();
+    let body = &output[start + 4..start + 4 + length];
+    messages.push(serde_json::from_slice(body).unwrap());
+    output = &output[start + 4 + length..];
+  }
+  messages
+}
+
+fn request(command: &str, arguments: Value) -> Value {
+  json!({ "command": command, "arguments": arguments })
+}
+
+fn launch(path: &PathBuf, stop_on_entry: bool) -> Vec<Value> {
+  vec![
+    request("initialize", json!({ "adapterID": "mips" })),
+    request(
+      "launch",
+      json!({ "program": path, "stopOnEntry": stop_on_entry }),
+    ),
+  ]
+}
+
+fn response<'a>(messages: &'a [Value], command: &str) -> Vec<&'a Value> {
+  messages
+    .iter()
+    .filter(|m| m["type"] == "response" && m["command"] == command)
+    .collect()
+}
+
+fn events<'a>(messages: &'a [Value], event: &str) -> Vec<&'a Value> {
+  messages
+    .iter()
+    .filter(|m| m["type"] == "event" && m["event"] == event)
+    .collect()
+}
+
+fn stdout(messages: &[Value]) -> String {
+  events(messages, "output")
+    .iter()
+    .filter(|e| e["body"]["category"] == "stdout")
+    .map(|e| e["body"]["output"].as_str().unwrap())
+    .collect()
+}
+
+fn register(variables: &Value, name: &str) -> String {
+  variables["body"]["variables"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .find(|v| v["name"] == name)
+    .map(|v| v["value"].as_str().unwrap().to_string())
+    .unwrap()
+}
+
+#[test]
+fn stops_at_line_breakpoints() {
+  let path = source("breakpoints", PROGRAM);
+  let mut script = launch(&path, false);
+  script.extend([
+    request(
+      "setBreakpoints",
+      json!({ "source": { "path": path }, "breakpoints": [{ "line": 7 }, { "line": 8 }] }),
+    ),
+    request("configurationDone", json!({})),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("scopes", json!({ "frameId": 0 })),
+    request("variables", json!({ "variablesReference": 1 })),
+    request("continue", json!({ "threadId": 1 })),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  assert!(response(&messages, "launch")[0]["success"]
+    .as_bool()
+    .unwrap());
+  assert_eq!(events(&messages, "initialized").len(), 1);
+
+  /* Line 8 is blank, so its breakpoint moves to line 9 */
+  let breakpoints = &response(&messages, "setBreakpoints")[0]["body"]["breakpoints"];
+  assert_eq!(breakpoints[0], json!({ "verified": true, "line": 7 }));
+  assert_eq!(breakpoints[1], json!({ "verified": true, "line": 9 }));
+
+  let stopped = events(&messages, "stopped");
+  assert_eq!(stopped[0]["body"]["reason"], "breakpoint");
+
+  let frame = &response(&messages, "stackTrace")[0]["body"]["stackFrames"][0];
+  assert_eq!(frame["line"], 7);
+  assert_eq!(frame["name"], "main+8");
+  assert_eq!(frame["source"]["path"], json!(path));
+
+  let scopes = &response(&messages, "scopes")[0]["body"]["scopes"];
+  assert_eq!(scopes[0]["name"], "Registers");
+
+  let variables = response(&messages, "variables")[0];
+  assert_eq!(register(variables, "$t0"), "0x00000002");
+  assert_eq!(register(variables, "$zero"), "0x00000000");
+  assert_eq!(register(variables, "pc"), "0x80000008");
+
+  /* Continuing stops at the second breakpoint */
+  assert_eq!(stopped.len(), 2);
+  assert_eq!(stopped[1]["body"]["reason"], "breakpoint");
+}
+
+#[test]
+fn forwards_output_and_exit_code() {
+  let path = source("output", PROGRAM);
+  let mut script = launch(&path, false);
+  script.extend([
+    request("configurationDone", json!({})),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  assert_eq!(stdout(&messages), "hello\n");
+  assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 3);
+  assert_eq!(events(&messages, "terminated").len(), 1);
+}
+
+#[test]
+fn steps_by_source_line() {
+  let path = source("stepping", PROGRAM);
+  let mut script = launch(&path, true);
+  script.extend([
+    request("configurationDone", json!({})),
+    request("next", json!({ "threadId": 1 })),
+    /* Over the call to `double` */
+    request("next", json!({ "threadId": 1 })),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("variables", json!({ "variablesReference": 1 })),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  let stopped = events(&messages, "stopped");
+  assert_eq!(stopped[0]["body"]["reason"], "entry");
+  assert_eq!(stopped[1]["body"]["reason"], "step");
+  assert_eq!(stopped[2]["body"]["reason"], "step");
+
+  let frame = &response(&messages, "stackTrace")[0]["body"]["stackFrames"][0];
+  assert_eq!(frame["line"], 7);
+  let variables = response(&messages, "variables")[0];
+  assert_eq!(register(variables, "$t0"), "0x00000002");
+
+  let mut script = launch(&path, true);
+  script.extend([
+    request("configurationDone", json!({})),
+    request("next", json!({ "threadId": 1 })),
+    request("stepIn", json!({ "threadId": 1 })),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("stepOut", json!({ "threadId": 1 })),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  let frames = response(&messages, "stackTrace");
+  assert_eq!(frames[0]["body"]["stackFrames"][0]["name"], "double");
+  assert_eq!(frames[0]["body"]["stackFrames"][0]["line"], 16);
+  assert_eq!(frames[1]["body"]["stackFrames"][0]["line"], 7);
+}
+
+#[test]
+fn routes_stdin_through_the_debug_console() {
+  let path = source(
+    "stdin",
+    "
+        .data
+buf:    .space 16
+        .text
+        li    $v0, 14
+        li    $a0, 0
+        la    $a1, buf
+        li    $a2, 16
+        syscall
+        move  $a2, $v0
+        li    $v0, 15
+        li    $a0, 1
+        syscall
+",
+  );
+  let mut script = launch(&path, false);
+  script.extend([
+    request("configurationDone", json!({})),
+    request(
+      "evaluate",
+      json!({ "expression": "echo me", "context": "repl" }),
+    ),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  assert_eq!(stdout(&messages), "echo me\n");
+  assert!(response(&messages, "evaluate")[0]["success"]
+    .as_bool()
+    .unwrap());
+  assert_eq!(events(&messages, "terminated").len(), 1);
+}
+
+#[test]
+fn reads_memory_and_sets_registers() {
+  let path = source("memory", PROGRAM);
+  let mut script = launch(&path, true);
+  script.extend([
+    request("configurationDone", json!({})),
+    request(
+      "readMemory",
+      json!({ "memoryReference": "0x81000000", "offset": 1, "count": 4 }),
+    ),
+    request(
+      "setVariable",
+      json!({ "variablesReference": 1, "name": "$t1", "value": "-1" }),
+    ),
+    request(
+      "evaluate",
+      json!({ "expression": "$t1", "context": "watch" }),
+    ),
+    request(
+      "setVariable",
+      json!({ "variablesReference": 1, "name": "$zero", "value": "1" }),
+    ),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  let memory = &response(&messages, "readMemory")[0]["body"];
+  assert_eq!(memory["address"], "0x81000001");
+  /* "ello" */
+  assert_eq!(memory["data"], "ZWxsbw==");
+  assert_eq!(memory["unreadableBytes"], 0);
+
+  let set = response(&messages, "setVariable");
+  assert_eq!(set[0]["body"]["value"], "0xffffffff");
+  assert!(!set[1]["success"].as_bool().unwrap());
+
+  let result = &response(&messages, "evaluate")[0]["body"]["result"];
+  assert_eq!(result, "0xffffffff (-1)");
+}
+
+#[test]
+fn reports_assembler_errors_on_launch() {
+  let path = source("error", "main: li $t0, 1\n  bogus $t0\n");
+  let mut script = launch(&path, false);
+  script.push(request("disconnect", json!({})));
+  let messages = session(script);
+
+  let launch = response(&messages, "launch")[0];
+  assert!(!launch["success"].as_bool().unwrap());
+  let message = launch["message"].as_str().unwrap();
+  assert!(
+    message.ends_with(": line 2: Unknown instruction: bogus"),
+    "{}",
+    message
+  );
+  assert!(events(&messages, "initialized").is_empty());
+}
+
+#[test]
+fn pauses_a_running_program() {
+  let path = source("pause", "main: b main\n");
+  let mut script = launch(&path, false);
+  script.extend([
+    request("configurationDone", json!({})),
+    request("pause", json!({ "threadId": 1 })),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  assert!(response(&messages, "pause")[0]["success"]
+    .as_bool()
+    .unwrap());
+  let stopped = events(&messages, "stopped");
+  assert_eq!(stopped[0]["body"]["reason"], "pause");
+  let frame = &response(&messages, "stackTrace")[0]["body"]["stackFrames"][0];
+  assert_eq!(frame["name"], "main");
+}
+
+#[test]
+fn disconnect_stops_a_running_program() {
+  let path = source("spin", "main: b main\n");
+  let mut script = launch(&path, false);
+  script.extend([
+    request("configurationDone", json!({})),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  assert_eq!(response(&messages, "disconnect").len(), 1);
+  assert!(events(&messages, "exited").is_empty());
+}
+
+/// A little-endian ELF executable with `text` loaded and entered at the
+/// base of DRAM.
+fn elf(name: &str, text: &[u32]) -> PathBuf {
+  const BASE: u32 = 0x8000_0000;
+  let text: Vec<u8> = text.iter().flat_map(|w| w.to_le_bytes()).collect();
+  let size = text.len() as u32;
+
+  let mut out = b"\x7fELF\x01\x01\x01".to_vec();
+  out.resize(16, 0);
+  for half in [2u16, 8] {
+    out.extend_from_slice(&half.to_le_bytes());
+  }
+  for word in [1, BASE, 52, 0, 0] {
+    out.extend_from_slice(&word.to_le_bytes());
+  }
+  for half in [52u16, 32, 1, 40, 0, 0] {
+    out.extend_from_slice(&half.to_le_bytes());
+  }
+  for word in [1, 84, BASE, BASE, size, size, 0x5, 4] {
+    out.extend_from_slice(&word.to_le_bytes());
+  }
+  out.extend_from_slice(&text);
+
+  let path = std::env::temp_dir().join(format!("mips-dap-{}-{}.elf", std::process::id(), name));
+  fs::write(&path, out).unwrap();
+  path
+}
+
+#[test]
+fn shows_disassembly_for_elf_programs() {
+  let path = elf(
+    "disasm",
+    &[
+      0x2408_0001, // addiu $t0, $zero, 1
+      0x2508_0001, // addiu $t0, $t0, 1
+      0x2508_0001, // addiu $t0, $t0, 1
+    ],
+  );
+  let mut script = launch(&path, true);
+  script.extend([
+    request(
+      "setBreakpoints",
+      json!({ "source": { "sourceReference": 1 }, "breakpoints": [{ "line": 3 }, { "line": 9 }] }),
+    ),
+    request("configurationDone", json!({})),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("source", json!({ "sourceReference": 1 })),
+    request("continue", json!({ "threadId": 1 })),
+    request("stackTrace", json!({ "threadId": 1 })),
+    request("disconnect", json!({})),
+  ]);
+  let messages = session(script);
+
+  let breakpoints = &response(&messages, "setBreakpoints")[0]["body"]["breakpoints"];
+  assert_eq!(breakpoints[0], json!({ "verified": true, "line": 3 }));
+  assert_eq!(breakpoints[1]["verified"], false);
+
+  let frames = response(&messages, "stackTrace");
+  let frame = &frames[0]["body"]["stackFrames"][0];
+  assert_eq!(frame["line"], 1);
+  assert_eq!(frame["source"]["sourceReference"], 1);
+  assert_eq!(frames[1]["body"]["stackFrames"][0]["line"], 3);
+
+  let content = response(&messages, "source")[0]["body"]["content"]
+    .as_str()
+    .unwrap();
+  let lines: Vec<_> = content.lines().collect();
+  assert_eq!(lines.len(), 3);
+  assert_eq!(lines[1], "80000004:  25080001  addiu $t0, $t0, 1");
+
+  let stopped = events(&messages, "stopped");
+  assert_eq!(stopped[1]["body"]["reason"], "breakpoint");
+}
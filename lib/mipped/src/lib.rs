@@ -0,0 +1,7 @@
+//! The `mipped` command-line tools: a runner, a debugger REPL and a
+//! full-screen terminal debugger over the emulator.
+
+pub mod load;
+pub mod repl;
+pub mod run;
+pub mod tui;
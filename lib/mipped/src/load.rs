@@ -0,0 +1,92 @@
+use std::{fs, io, path::Path};
+
+use mips::{
+  asm::{assemble, AsmError, Program},
+  emulator::{cpu::Cpu, elf::ElfError, interrupt::Interrupt},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+  #[error("{0}")]
+  IO(#[from] io::Error),
+
+  #[error("{0}")]
+  ELF(#[from] ElfError),
+
+  #[error("{0}")]
+  ASM(#[from] AsmError),
+
+  #[error("Cannot load program: {0}")]
+  LOAD(#[from] Interrupt),
+}
+
+/// A program loaded into a fresh machine.
+pub struct Image {
+  pub cpu: Cpu,
+  /// Labels and line information, for programs assembled from source
+  pub program: Option<Program>,
+  pub source: Option<String>,
+}
+
+/// How to interpret the bytes of a program file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Format {
+  /// ELF by magic number, raw for `.bin` files, otherwise source
+  #[default]
+  Auto,
+  /// MIPS assembly source
+  Asm,
+  /// ELF executable
+  Elf,
+  /// Machine code loaded at the start of DRAM and run from there
+  Raw,
+}
+
+impl Format {
+  pub(crate) fn detect(path: &Path, bytes: &[u8]) -> Format {
+    if bytes.starts_with(b"\x7fELF") {
+      return Format::Elf;
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("bin") => Format::Raw,
+      _ => Format::Asm,
+    }
+  }
+}
+
+/// Loads an ELF executable, or assembles anything else as MIPS source.
+pub fn load(path: &Path) -> Result<Image, LoadError> {
+  load_with(path, Cpu::new(), Format::Auto)
+}
+
+/// Loads the program at `path` into `cpu`, which may already be configured
+/// with a memory layout and ISA.
+pub fn load_with(path: &Path, mut cpu: Cpu, format: Format) -> Result<Image, LoadError> {
+  let bytes = fs::read(path)?;
+  let format = match format {
+    Format::Auto => Format::detect(path, &bytes),
+    format => format,
+  };
+
+  match format {
+    Format::Elf => cpu.load_elf(&bytes)?,
+    Format::Raw => cpu.load(bytes)?,
+    _ => {
+      let source = String::from_utf8_lossy(&bytes).into_owned();
+      let program = assemble(&source)?;
+      cpu.load_program(&program)?;
+      return Ok(Image {
+        cpu,
+        program: Some(program),
+        source: Some(source),
+      });
+    }
+  }
+
+  Ok(Image {
+    cpu,
+    program: None,
+    source: None,
+  })
+}
@@ -0,0 +1,105 @@
+use std::{
+  io::{self, Write},
+  path::PathBuf,
+  process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use mipped::{
+  repl::{Flow, Repl},
+  run::{self, RunArgs},
+  tui,
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+#[derive(Parser)]
+#[command(version, about = "MIPS emulator and debugger")]
+struct Cli {
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Run a program to completion, exiting with its exit code
+  Run(RunArgs),
+  /// Debug a program interactively
+  Debug {
+    /// An `.asm` source file or ELF executable
+    program: PathBuf,
+  },
+  /// Debug a program in a full-screen terminal interface
+  Tui {
+    /// An `.asm` source file or ELF executable
+    program: PathBuf,
+  },
+}
+
+const HISTORY: &str = ".mipped_history";
+
+fn debug(program: PathBuf) -> Result<(), String> {
+  let streams = Box::new(|| {
+    let stdout: Box<dyn Write> = Box::new(io::stdout());
+    let stdin: Box<dyn io::Read> = Box::new(io::stdin());
+    (stdout, stdin)
+  });
+  let mut repl =
+    Repl::new(&program, streams).map_err(|e| format!("{}: {}", program.display(), e))?;
+
+  let mut editor = DefaultEditor::new().map_err(|e| e.to_string())?;
+  let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY));
+  if let Some(history) = &history {
+    let _ = editor.load_history(history);
+  }
+
+  let mut out = io::stdout();
+  repl.execute("disas", &mut out).map_err(|e| e.to_string())?;
+  loop {
+    let line = match editor.readline("(mipped) ") {
+      Ok(line) => line,
+      Err(ReadlineError::Interrupted) => continue,
+      Err(ReadlineError::Eof) => break,
+      Err(e) => return Err(e.to_string()),
+    };
+    if !line.trim().is_empty() {
+      let _ = editor.add_history_entry(line.as_str());
+    }
+    match repl.execute(&line, &mut out).map_err(|e| e.to_string())? {
+      Flow::Continue => {}
+      Flow::Quit => break,
+    }
+  }
+
+  if let Some(history) = &history {
+    let _ = editor.save_history(history);
+  }
+  Ok(())
+}
+
+fn run(args: RunArgs) -> Result<ExitCode, String> {
+  let code = run::run(&args, io::stdout(), io::stdin()).map_err(|e| e.to_string())?;
+  if args.report_exit {
+    eprintln!("Process exited with code {}", code);
+  }
+  match args.ignore_exit_code {
+    true => Ok(ExitCode::SUCCESS),
+    false => Ok(ExitCode::from(code as u8)),
+  }
+}
+
+fn main() -> ExitCode {
+  let cli = Cli::parse();
+  let result = match cli.command {
+    Command::Run(args) => run(args),
+    Command::Debug { program } => debug(program).map(|()| ExitCode::SUCCESS),
+    Command::Tui { program } => tui::run(&program).map(|()| ExitCode::SUCCESS),
+  };
+
+  match result {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("mipped: {}", e);
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,416 @@
+use std::{
+  fmt::Write as _,
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+};
+
+use mips::{
+  asm::Program,
+  emulator::{
+    arch::Register, debug::StopReason, disasm::disassemble, interrupt::Result as EmuResult,
+    sys::Sys,
+  },
+};
+
+use crate::load::{load, LoadError};
+
+/// Creates the guest's stdout and stdin each time the program is loaded.
+pub type Streams = Box<dyn Fn() -> (Box<dyn Write>, Box<dyn Read>)>;
+
+/// Instructions shown by `disas` without a count.
+const DISAS_LINES: u32 = 10;
+
+const HELP: &str = "\
+step [n]            execute n instructions (default 1)
+continue            run until a breakpoint or the end of the program
+break [label|addr]  set a breakpoint, or list them without an argument
+delete <label|addr> remove a breakpoint
+print <$reg|label>  show a register or the address of a label
+x/<n><w|h|b> <addr> examine n words, halfwords or bytes of memory
+disas [addr] [n]    disassemble n instructions (default: around pc)
+regs                show all registers
+set $reg = value    change a register
+reload              reload the program from disk, keeping breakpoints
+quit                leave the debugger
+";
+
+pub enum Flow {
+  Continue,
+  Quit,
+}
+
+type Reply = Result<String, String>;
+
+/// A command-line debugger session over one program.
+pub struct Repl {
+  path: PathBuf,
+  sys: Sys,
+  program: Option<Program>,
+  source: Option<String>,
+  streams: Streams,
+  exited: bool,
+  /// Repeated when an empty line is entered
+  last: String,
+}
+
+impl Repl {
+  pub fn new(path: &Path, streams: Streams) -> Result<Repl, LoadError> {
+    let image = load(path)?;
+    let (stdout, stdin) = streams();
+    Ok(Repl {
+      path: path.to_path_buf(),
+      sys: Sys::new(image.cpu, stdout, stdin),
+      program: image.program,
+      source: image.source,
+      streams,
+      exited: false,
+      last: String::new(),
+    })
+  }
+
+  pub fn sys(&self) -> &Sys {
+    &self.sys
+  }
+
+  /// Runs one command line, writing its output to `out`.
+  pub fn execute(&mut self, line: &str, out: &mut dyn Write) -> io::Result<Flow> {
+    let line = match line.trim() {
+      "" => self.last.clone(),
+      line => line.to_string(),
+    };
+    self.last = line.clone();
+
+    let (command, args) = line.split_once(' ').unwrap_or((&line, ""));
+    let args = args.trim();
+    let reply = match command {
+      "" => Ok(String::new()),
+      "s" | "step" => self.step(args),
+      "c" | "continue" => self.resume(Sys::cont),
+      "b" | "break" => self.set_break(args),
+      "d" | "delete" => self.delete(args),
+      "p" | "print" => self.print(args),
+      "disas" => self.disas(args),
+      "regs" => Ok(self.regs()),
+      "set" => self.set(args),
+      "reload" => self.reload(),
+      "h" | "help" => Ok(HELP.to_string()),
+      "q" | "quit" => return Ok(Flow::Quit),
+      _ if command.starts_with("x/") || command == "x" => self.examine(command, args),
+      _ => Err(format!("Unknown command `{}`; try `help`", command)),
+    };
+
+    match reply {
+      Ok(text) => write!(out, "{}", text)?,
+      Err(message) => writeln!(out, "error: {}", message)?,
+    }
+    out.flush()?;
+    Ok(Flow::Continue)
+  }
+
+  /// Parses a label, `$reg`, or decimal or `0x` hexadecimal number.
+  fn value(&self, text: &str) -> Result<u32, String> {
+    if let Some(&addr) = self.program.as_ref().and_then(|p| p.symbols.get(text)) {
+      return Ok(addr);
+    }
+    if text.starts_with('$') {
+      return self.register(text);
+    }
+    parse_number(text).ok_or_else(|| format!("No label or number `{}`", text))
+  }
+
+  fn register(&self, name: &str) -> Result<u32, String> {
+    let cpu = self.sys.cpu();
+    match name.strip_prefix('$').unwrap_or(name) {
+      "pc" => Ok(cpu.pc),
+      "hi" => Ok(cpu.hi),
+      "lo" => Ok(cpu.lo),
+      _ => Register::from_name(name)
+        .map(|r| cpu.regs[r])
+        .ok_or_else(|| format!("No register `{}`", name)),
+    }
+  }
+
+  /// `addr <label+offset>`, or just the address without labels.
+  fn describe(&self, addr: u32) -> String {
+    let symbol = self
+      .program
+      .as_ref()
+      .into_iter()
+      .flat_map(|p| &p.symbols)
+      .filter(|&(_, &a)| a <= addr)
+      .max_by_key(|&(_, &a)| a);
+
+    match symbol {
+      Some((name, &a)) if a == addr => format!("0x{:08x} <{}>", addr, name),
+      Some((name, &a)) => format!("0x{:08x} <{}+{}>", addr, name, addr - a),
+      None => format!("0x{:08x}", addr),
+    }
+  }
+
+  fn instruction(&self, addr: u32) -> String {
+    let cpu = self.sys.cpu();
+    match cpu.bus.load(addr, 32) {
+      Ok(inst) => disassemble(inst, addr, cpu.isa),
+      Err(_) => "<unreadable>".to_string(),
+    }
+  }
+
+  /// The next instruction, with its source line when there is one.
+  fn current(&self) -> String {
+    let pc = self.sys.cpu().pc;
+    let mut text = format!("=> {}: {}\n", self.describe(pc), self.instruction(pc));
+
+    let line = self.program.as_ref().and_then(|p| p.line_at(pc));
+    let source = line.and_then(|line| self.source.as_deref()?.lines().nth(line - 1));
+    if let (Some(line), Some(source)) = (line, source) {
+      writeln!(text, "{:>5}  {}", line, source.trim()).unwrap();
+    }
+    text
+  }
+
+  fn report(&mut self, result: EmuResult<StopReason>) -> String {
+    match result {
+      Ok(StopReason::Exited(code)) => {
+        self.exited = true;
+        format!("Program exited with code {}\n", code)
+      }
+      Ok(StopReason::Step | StopReason::Reached(_)) => self.current(),
+      Ok(StopReason::Breakpoint(addr)) => {
+        format!("Breakpoint at {}\n{}", self.describe(addr), self.current())
+      }
+      Ok(StopReason::Watchpoint(hit)) => format!("{}\n{}", hit, self.current()),
+      Ok(StopReason::LimitExceeded(limit)) => format!("{}\n{}", limit, self.current()),
+      Ok(StopReason::Interrupted) => format!("Interrupted\n{}", self.current()),
+      Err(e) => format!("Exception: {}\n{}", e, self.current()),
+    }
+  }
+
+  fn resume<F>(&mut self, run: F) -> Reply
+  where
+    F: FnOnce(&mut Sys) -> EmuResult<StopReason>,
+  {
+    if self.exited {
+      return Err("The program is not running; use `reload` to start again".to_string());
+    }
+    let result = run(&mut self.sys);
+    Ok(self.report(result))
+  }
+
+  fn step(&mut self, args: &str) -> Reply {
+    let count = match args {
+      "" => 1,
+      n => n
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid count `{}`", n))?,
+    };
+
+    self.resume(|sys| {
+      for _ in 1..count {
+        match sys.step()? {
+          StopReason::Step => {}
+          reason => return Ok(reason),
+        }
+      }
+      sys.step()
+    })
+  }
+
+  fn set_break(&mut self, args: &str) -> Reply {
+    if args.is_empty() {
+      let breakpoints: Vec<u32> = self.sys.breakpoints().collect();
+      if breakpoints.is_empty() {
+        return Ok("No breakpoints\n".to_string());
+      }
+      return Ok(
+        breakpoints
+          .into_iter()
+          .map(|addr| format!("Breakpoint at {}\n", self.describe(addr)))
+          .collect(),
+      );
+    }
+
+    let addr = self.value(args)?;
+    match self.sys.set_breakpoint(addr) {
+      true => Ok(format!("Breakpoint at {}\n", self.describe(addr))),
+      false => Err(format!("Breakpoint already set at {}", self.describe(addr))),
+    }
+  }
+
+  fn delete(&mut self, args: &str) -> Reply {
+    let addr = self.value(args)?;
+    match self.sys.clear_breakpoint(addr) {
+      true => Ok(format!("Deleted breakpoint at {}\n", self.describe(addr))),
+      false => Err(format!("No breakpoint at {}", self.describe(addr))),
+    }
+  }
+
+  fn print(&self, args: &str) -> Reply {
+    if args.is_empty() {
+      return Err("Usage: print <$reg|label>".to_string());
+    }
+    let value = self.value(args)?;
+    Ok(format!("{} = 0x{:08x} ({})\n", args, value, value as i32))
+  }
+
+  fn examine(&self, command: &str, args: &str) -> Reply {
+    let format = command.strip_prefix("x").unwrap_or_default();
+    let format = format.strip_prefix('/').unwrap_or(format);
+    let (count, unit) = match format.find(|c: char| !c.is_ascii_digit()) {
+      Some(i) => (&format[..i], &format[i..]),
+      None => (format, ""),
+    };
+    let count = match count {
+      "" => 1,
+      n => n
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid count `{}`", n))?,
+    };
+    let (bits, per_line) = match unit {
+      "" | "w" => (32, 4),
+      "h" => (16, 8),
+      "b" => (8, 16),
+      _ => return Err(format!("Unknown unit `{}`; use w, h or b", unit)),
+    };
+
+    if args.is_empty() {
+      return Err("Usage: x/<n><w|h|b> <addr>".to_string());
+    }
+    let start = self.value(args)?;
+    let bytes = bits / 8;
+
+    let mut text = String::new();
+    for i in 0..count {
+      let addr = start.wrapping_add(i * bytes);
+      if i % per_line == 0 {
+        if i > 0 {
+          text.push('\n');
+        }
+        write!(text, "0x{:08x}:", addr).unwrap();
+      }
+      match self.sys.cpu().bus.load(addr, bits) {
+        Ok(value) => write!(text, " 0x{:0width$x}", value, width = bytes as usize * 2).unwrap(),
+        Err(_) => {
+          writeln!(text).unwrap();
+          return Err(format!("{}Cannot access memory at 0x{:08x}", text, addr));
+        }
+      }
+    }
+    text.push('\n');
+    Ok(text)
+  }
+
+  fn disas(&self, args: &str) -> Reply {
+    let mut args = args.split_whitespace();
+    let pc = self.sys.cpu().pc;
+    let start = match args.next() {
+      Some(loc) => self.value(loc)? & !3,
+      /* A few instructions of context before the PC */
+      None => {
+        let text_base = self.program.as_ref().map_or(pc, |p| p.text_base);
+        pc.saturating_sub(12).max(text_base.min(pc))
+      }
+    };
+    let count = match args.next() {
+      Some(n) => n
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid count `{}`", n))?,
+      None => DISAS_LINES,
+    };
+
+    let breakpoints: Vec<u32> = self.sys.breakpoints().collect();
+    let mut text = String::new();
+    for i in 0..count {
+      let addr = start.wrapping_add(i * 4);
+      let labels = self.program.iter().flat_map(|p| &p.symbols);
+      for (name, _) in labels.filter(|&(_, &a)| a == addr) {
+        writeln!(text, "{}:", name).unwrap();
+      }
+
+      let marker = match (addr == pc, breakpoints.contains(&addr)) {
+        (true, _) => "=>",
+        (false, true) => " *",
+        (false, false) => "  ",
+      };
+      writeln!(
+        text,
+        "{} 0x{:08x}:  {}",
+        marker,
+        addr,
+        self.instruction(addr)
+      )
+      .unwrap();
+    }
+    Ok(text)
+  }
+
+  fn regs(&self) -> String {
+    let cpu = self.sys.cpu();
+    let mut text = String::new();
+    for (i, name) in Register::NAMES.iter().enumerate() {
+      write!(text, "{:>4} 0x{:08x}", name, cpu.regs[i]).unwrap();
+      text.push_str(if i % 4 == 3 { "\n" } else { "  " });
+    }
+    writeln!(
+      text,
+      "{:>4} 0x{:08x}  {:>4} 0x{:08x}  {:>4} 0x{:08x}",
+      "pc", cpu.pc, "hi", cpu.hi, "lo", cpu.lo
+    )
+    .unwrap();
+    text
+  }
+
+  fn set(&mut self, args: &str) -> Reply {
+    let (name, value) = args.split_once('=').ok_or("Usage: set $reg = value")?;
+    let (name, value) = (name.trim(), self.value(value.trim())?);
+
+    let cpu = self.sys.cpu_mut();
+    match name.strip_prefix('$').unwrap_or(name) {
+      "pc" => cpu.pc = value,
+      "hi" => cpu.hi = value,
+      "lo" => cpu.lo = value,
+      _ => match Register::from_name(name) {
+        Some(Register::ZERO) => return Err("$zero cannot be changed".to_string()),
+        Some(r) => cpu.regs[r] = value,
+        None => return Err(format!("No register `{}`", name)),
+      },
+    }
+    Ok(format!("{} = 0x{:08x} ({})\n", name, value, value as i32))
+  }
+
+  fn reload(&mut self) -> Reply {
+    let image = load(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+    let breakpoints: Vec<u32> = self.sys.breakpoints().collect();
+
+    let (stdout, stdin) = (self.streams)();
+    self.sys = Sys::new(image.cpu, stdout, stdin);
+    for addr in breakpoints {
+      self.sys.set_breakpoint(addr);
+    }
+    self.program = image.program;
+    self.source = image.source;
+    self.exited = false;
+
+    Ok(format!(
+      "Reloaded {}\n{}",
+      self.path.display(),
+      self.current()
+    ))
+  }
+}
+
+/// Parses a decimal or `0x` hexadecimal number, allowing negative values.
+fn parse_number(text: &str) -> Option<u32> {
+  let (negative, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text),
+  };
+  let value = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+    None => digits.parse::<u32>().ok()?,
+  };
+  Some(if negative {
+    value.wrapping_neg()
+  } else {
+    value
+  })
+}
@@ -0,0 +1,167 @@
+use std::{
+  fs::{self, File},
+  io::{self, Read, Write},
+  path::PathBuf,
+  time::Duration,
+};
+
+use clap::{Args, ValueEnum};
+use mips::{
+  emulator::{
+    arch::Isa,
+    interrupt::{Interrupt, SoftwareInterrupt},
+  },
+  LimitExceeded, Limits, Machine, MachineBuilder, MachineError,
+};
+use thiserror::Error;
+
+use crate::load::Format;
+
+#[derive(Debug, Error)]
+pub enum RunError {
+  #[error("{0}")]
+  IO(#[from] io::Error),
+
+  #[error("{0}")]
+  MACHINE(#[from] MachineError),
+
+  #[error("{0} at 0x{1:08x}")]
+  EXCEPTION(Interrupt, u32),
+
+  #[error("{0}")]
+  LIMIT(LimitExceeded),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum IsaLevel {
+  /// MIPS32 Release 1-5
+  #[default]
+  Mips32,
+  /// MIPS32 Release 6
+  Mips32r6,
+}
+
+impl From<IsaLevel> for Isa {
+  fn from(level: IsaLevel) -> Isa {
+    match level {
+      IsaLevel::Mips32 => Isa::Mips32,
+      IsaLevel::Mips32r6 => Isa::Mips32R6,
+    }
+  }
+}
+
+/// Options of `mipped run`.
+#[derive(Debug, Clone, Args)]
+pub struct RunArgs {
+  /// An `.asm` source file, ELF executable or raw image
+  pub program: PathBuf,
+
+  /// Arguments passed to the program in `$a0` (argc) and `$a1` (argv)
+  #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+  pub args: Vec<String>,
+
+  /// How to interpret the program file
+  #[arg(long, value_enum, default_value_t)]
+  pub format: Format,
+
+  /// Size of DRAM in MiB, mapped from 0x80000000
+  #[arg(long, value_name = "MIB", default_value_t = 128)]
+  #[arg(value_parser = clap::value_parser!(u32).range(1..2048))]
+  pub memory: u32,
+
+  /// Maximum depth of the stack in KiB, at the top of DRAM
+  #[arg(long, value_name = "KIB", default_value_t = 8192)]
+  #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+  pub stack: u32,
+
+  /// Allocate all of DRAM up front instead of on first touch
+  #[arg(long)]
+  pub flat_memory: bool,
+
+  /// Execute the instruction after a branch before taking it
+  #[arg(long)]
+  pub delay_slots: bool,
+
+  /// Instruction set to decode
+  #[arg(long, value_enum, default_value_t)]
+  pub isa: IsaLevel,
+
+  /// Stop with an error after this many instructions
+  #[arg(long, value_name = "N")]
+  pub max_instructions: Option<u64>,
+
+  /// Stop with an error after running for this many seconds
+  #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+  pub time_limit: Option<Duration>,
+
+  /// Stop with an error once the program has written this many bytes
+  #[arg(long, value_name = "BYTES")]
+  pub output_limit: Option<u64>,
+
+  /// Read guest input from a file instead of standard input
+  #[arg(long, value_name = "FILE")]
+  pub stdin: Option<PathBuf>,
+
+  /// Exit with status 0 whatever exit code the program returns
+  #[arg(long)]
+  pub ignore_exit_code: bool,
+
+  /// Print the program's exit code to standard error when it finishes
+  #[arg(long)]
+  pub report_exit: bool,
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+  let seconds = arg.parse::<f64>().map_err(|e| e.to_string())?;
+  Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+impl RunArgs {
+  /// A machine builder with the layout, ISA and limits the options ask for.
+  fn builder(&self) -> MachineBuilder {
+    Machine::builder()
+      .memory(self.memory * 1024 * 1024)
+      .flat_memory(self.flat_memory)
+      .stack_size(self.stack.saturating_mul(1024))
+      .isa(self.isa.into())
+      .delay_slots(self.delay_slots)
+      .args(self.args.iter().cloned())
+      .limits(Limits {
+        instructions: self.max_instructions,
+        time: self.time_limit,
+        output: self.output_limit,
+      })
+  }
+}
+
+/// Runs the program `args` describes to completion and returns its exit
+/// code. Guest input comes from `stdin` unless `--stdin` names a file.
+pub fn run<O, I>(args: &RunArgs, stdout: O, stdin: I) -> Result<i32, RunError>
+where
+  O: 'static + Write,
+  I: 'static + Read,
+{
+  let bytes = fs::read(&args.program)?;
+  let format = match args.format {
+    Format::Auto => Format::detect(&args.program, &bytes),
+    format => format,
+  };
+  let builder = match format {
+    Format::Elf => args.builder().elf(bytes),
+    Format::Raw => args.builder().raw(bytes),
+    _ => args
+      .builder()
+      .source(String::from_utf8_lossy(&bytes).into_owned()),
+  };
+  let builder = match &args.stdin {
+    Some(path) => builder.stdin(File::open(path)?),
+    None => builder.stdin(stdin),
+  };
+  let mut machine = builder.stdout(stdout).build()?;
+
+  match machine.run() {
+    Ok(code) => Ok(code),
+    Err(Interrupt::Software(SoftwareInterrupt::LIMIT(limit))) => Err(RunError::LIMIT(limit)),
+    Err(e) => Err(RunError::EXCEPTION(e, machine.sys().cpu().pc)),
+  }
+}
@@ -0,0 +1,403 @@
+//! A full-screen debugger showing the code, registers, memory and console
+//! of a running program side by side.
+
+mod ui;
+
+use std::{
+  cell::RefCell,
+  collections::VecDeque,
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+  rc::Rc,
+  time::Duration,
+};
+
+use mips::{
+  asm::Program,
+  emulator::{arch::Register, debug::StopReason, sys::Sys, virt::MemMap},
+};
+use ratatui::{
+  crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+  DefaultTerminal, Frame,
+};
+
+use crate::load::{load, LoadError};
+
+/// Instructions executed between redraws while the program runs.
+const CHUNK: u64 = 20_000;
+
+/// Bytes shown on each row of the memory pane.
+pub const ROW_BYTES: u32 = 16;
+
+/// Registers tracked for highlighting: the 32 GPRs, then pc, hi and lo.
+const TRACKED: usize = 35;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+  Code,
+  Memory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemView {
+  Data,
+  Stack,
+}
+
+/// How far a resumed program should run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Run {
+  Step,
+  /// Step, running a call to completion as one step
+  Over,
+  Continue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+  Paused,
+  Running(Run),
+  /// The program is about to read stdin and the console has no input
+  Input {
+    line: String,
+    then: Run,
+  },
+  Exited(i32),
+}
+
+pub struct App {
+  path: PathBuf,
+  pub sys: Sys,
+  program: Option<Program>,
+  /// Everything shown in the console pane: output and echoed input
+  console: Rc<RefCell<Vec<u8>>>,
+  input: Rc<RefCell<VecDeque<u8>>>,
+  regs: [u32; TRACKED],
+  changed: [bool; TRACKED],
+  pub mode: Mode,
+  pub focus: Focus,
+  /// Selected address in the code pane
+  pub cursor: u32,
+  pub mem_view: MemView,
+  pub mem_addr: u32,
+  pub status: String,
+  pub quit: bool,
+}
+
+struct ConsoleOut(Rc<RefCell<Vec<u8>>>);
+
+impl Write for ConsoleOut {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+struct ConsoleIn(Rc<RefCell<VecDeque<u8>>>);
+
+impl Read for ConsoleIn {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let mut input = self.0.borrow_mut();
+    let n = buf.len().min(input.len());
+    for (dst, src) in buf.iter_mut().zip(input.drain(..n)) {
+      *dst = src;
+    }
+    Ok(n)
+  }
+}
+
+impl App {
+  pub fn new(path: &Path) -> Result<App, LoadError> {
+    let image = load(path)?;
+    let console = Rc::<RefCell<Vec<u8>>>::default();
+    let input = Rc::<RefCell<VecDeque<u8>>>::default();
+    let sys = Sys::new(
+      image.cpu,
+      ConsoleOut(console.clone()),
+      ConsoleIn(input.clone()),
+    );
+
+    let mut app = App {
+      path: path.to_path_buf(),
+      sys,
+      program: image.program,
+      console,
+      input,
+      regs: [0; TRACKED],
+      changed: [false; TRACKED],
+      mode: Mode::Paused,
+      focus: Focus::Code,
+      cursor: 0,
+      mem_view: MemView::Data,
+      mem_addr: 0,
+      status: String::new(),
+      quit: false,
+    };
+    app.started();
+    Ok(app)
+  }
+
+  /// Loads the program from disk again, keeping breakpoints.
+  pub fn reload(&mut self) -> Result<(), LoadError> {
+    let image = load(&self.path)?;
+    let breakpoints: Vec<u32> = self.sys.breakpoints().collect();
+
+    self.console.borrow_mut().clear();
+    self.input.borrow_mut().clear();
+    self.sys = Sys::new(
+      image.cpu,
+      ConsoleOut(self.console.clone()),
+      ConsoleIn(self.input.clone()),
+    );
+    for addr in breakpoints {
+      self.sys.set_breakpoint(addr);
+    }
+    self.program = image.program;
+    self.started();
+    Ok(())
+  }
+
+  /// Resets the view for a freshly loaded program.
+  fn started(&mut self) {
+    self.mode = Mode::Paused;
+    self.regs = self.snapshot();
+    self.changed = [false; TRACKED];
+    self.cursor = self.sys.cpu().pc;
+    self.show_memory(self.mem_view);
+    self.status = format!("Loaded {}", self.path.display());
+  }
+
+  pub fn program(&self) -> Option<&Program> {
+    self.program.as_ref()
+  }
+
+  pub fn console(&self) -> String {
+    String::from_utf8_lossy(&self.console.borrow()).into_owned()
+  }
+
+  /// Whether register `n` (32 = pc, 33 = hi, 34 = lo) changed at the last
+  /// stop.
+  pub fn changed(&self, n: usize) -> bool {
+    self.changed[n]
+  }
+
+  fn snapshot(&self) -> [u32; TRACKED] {
+    let cpu = self.sys.cpu();
+    let mut regs = [0; TRACKED];
+    regs[..32].copy_from_slice(&cpu.regs);
+    regs[32..].copy_from_slice(&[cpu.pc, cpu.hi, cpu.lo]);
+    regs
+  }
+
+  pub fn show_memory(&mut self, view: MemView) {
+    self.mem_view = view;
+    self.mem_addr = match view {
+      MemView::Data => match &self.program {
+        Some(program) => program.data_base,
+        None => MemMap::HIGHMEM.base,
+      },
+      MemView::Stack => self.sys.cpu().regs[Register::SP] & !(ROW_BYTES - 1),
+    };
+  }
+
+  fn stop(&mut self, status: String) {
+    self.sys.stop_clock();
+    let regs = self.snapshot();
+    for (changed, (old, new)) in self.changed.iter_mut().zip(self.regs.iter().zip(regs)) {
+      *changed = *old != new;
+    }
+    self.regs = regs;
+    self.cursor = self.sys.cpu().pc;
+    self.mode = Mode::Paused;
+    self.status = status;
+  }
+
+  pub fn resume(&mut self, run: Run) {
+    match self.mode {
+      Mode::Exited(_) => self.status = "The program has exited; press r to reload".to_string(),
+      _ => self.mode = Mode::Running(run),
+    }
+  }
+
+  /// Runs the program for up to `budget` instructions, stopping early at
+  /// breakpoints, exceptions, the end of the requested step, or a read
+  /// from an empty console.
+  pub fn advance(&mut self, budget: u64) {
+    let Mode::Running(run) = self.mode else {
+      return;
+    };
+    if self.wait_for_input(run) {
+      return;
+    }
+
+    let res = match run {
+      Run::Step => self.sys.step(),
+      Run::Over => self.sys.step_over(),
+      Run::Continue => {
+        let input = self.input.clone();
+        self
+          .sys
+          .cont_until(budget, |sys| needs_input(sys, &input.borrow()))
+      }
+    };
+
+    match res {
+      /* Paused for a redraw or in front of a read */
+      Ok(StopReason::Step) if run == Run::Continue => {
+        self.wait_for_input(run);
+      }
+      Ok(StopReason::Step | StopReason::Reached(_)) => self.stop(String::new()),
+      Ok(StopReason::Breakpoint(pc)) => self.stop(format!("Breakpoint at 0x{:08x}", pc)),
+      Ok(StopReason::Watchpoint(hit)) => self.stop(hit.to_string()),
+      Ok(StopReason::LimitExceeded(limit)) => self.stop(limit.to_string()),
+      Ok(StopReason::Interrupted) => self.stop("Paused".to_string()),
+      Ok(StopReason::Exited(code)) => {
+        self.stop(format!("Program exited with code {}", code));
+        self.mode = Mode::Exited(code);
+      }
+      Err(e) => self.stop(format!("Exception: {}", e)),
+    }
+  }
+
+  /// Asks for a line of input if the next instruction reads an empty
+  /// console, resuming with `then` once it is entered.
+  fn wait_for_input(&mut self, then: Run) -> bool {
+    if !needs_input(&self.sys, &self.input.borrow()) {
+      return false;
+    }
+
+    self.sys.stop_clock();
+    self.mode = Mode::Input {
+      line: String::new(),
+      then,
+    };
+    self.status = "Waiting for input: type a line and press Enter".to_string();
+    true
+  }
+
+  fn toggle_breakpoint(&mut self) {
+    let addr = self.cursor;
+    if !self.sys.clear_breakpoint(addr) {
+      self.sys.set_breakpoint(addr);
+    }
+  }
+
+  fn scroll(&mut self, rows: i32) {
+    match self.focus {
+      Focus::Code => self.cursor = self.cursor.wrapping_add_signed(rows * 4),
+      Focus::Memory => self.mem_addr = self.mem_addr.wrapping_add_signed(rows * ROW_BYTES as i32),
+    }
+  }
+
+  pub fn handle_key(&mut self, key: KeyEvent) {
+    if key.kind != KeyEventKind::Press {
+      return;
+    }
+    if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+      self.quit = true;
+      return;
+    }
+
+    /* Typing a line of input for the program */
+    if let Mode::Input { line, then } = &mut self.mode {
+      match key.code {
+        KeyCode::Char(c) => line.push(c),
+        KeyCode::Backspace => {
+          line.pop();
+        }
+        KeyCode::Enter => {
+          let text = format!("{}\n", line);
+          let then = *then;
+          self.console.borrow_mut().extend_from_slice(text.as_bytes());
+          self.input.borrow_mut().extend(text.bytes());
+          self.status.clear();
+          self.mode = Mode::Running(then);
+        }
+        KeyCode::Esc => self.stop("Input cancelled".to_string()),
+        _ => {}
+      }
+      return;
+    }
+
+    /* Any key pauses a running program */
+    if let Mode::Running(_) = self.mode {
+      self.stop("Paused".to_string());
+      return;
+    }
+
+    match key.code {
+      KeyCode::Char('q') => self.quit = true,
+      KeyCode::Char('s') => self.resume(Run::Step),
+      KeyCode::Char('n') => self.resume(Run::Over),
+      KeyCode::Char('c') => self.resume(Run::Continue),
+      KeyCode::Char('b') => self.toggle_breakpoint(),
+      KeyCode::Char('r') => {
+        if let Err(e) = self.reload() {
+          self.status = format!("{}: {}", self.path.display(), e);
+        }
+      }
+      KeyCode::Char('m') => self.show_memory(match self.mem_view {
+        MemView::Data => MemView::Stack,
+        MemView::Stack => MemView::Data,
+      }),
+      KeyCode::Char('.') => self.cursor = self.sys.cpu().pc,
+      KeyCode::Tab => {
+        self.focus = match self.focus {
+          Focus::Code => Focus::Memory,
+          Focus::Memory => Focus::Code,
+        }
+      }
+      KeyCode::Up => self.scroll(-1),
+      KeyCode::Down => self.scroll(1),
+      KeyCode::PageUp => self.scroll(-16),
+      KeyCode::PageDown => self.scroll(16),
+      _ => {}
+    }
+  }
+
+  pub fn draw(&self, frame: &mut Frame) {
+    ui::draw(frame, self);
+  }
+}
+
+/// Whether the next instruction is a console read with no input queued.
+fn needs_input(sys: &Sys, input: &VecDeque<u8>) -> bool {
+  let cpu = sys.cpu();
+  let syscall = matches!(cpu.bus.load(cpu.pc, 32), Ok(0x0000_000c));
+  let reads_stdin = match cpu.regs[Register::V0] {
+    0x05 | 0x08 | 0x0C => true,
+    0x0E => cpu.regs[Register::A0] == 0,
+    _ => false,
+  };
+  syscall && reads_stdin && input.is_empty()
+}
+
+/// Runs the debugger on the terminal until the user quits.
+pub fn run(path: &Path) -> Result<(), String> {
+  let mut app = App::new(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+  let mut terminal = ratatui::init();
+  let result = event_loop(&mut terminal, &mut app);
+  ratatui::restore();
+  result.map_err(|e| e.to_string())
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+  while !app.quit {
+    terminal.draw(|frame| app.draw(frame))?;
+
+    /* Keep running between redraws, but stay responsive to keys */
+    let running = matches!(app.mode, Mode::Running(_));
+    if !running || event::poll(Duration::ZERO)? {
+      if let Event::Key(key) = event::read()? {
+        app.handle_key(key);
+      }
+    }
+    app.advance(CHUNK);
+  }
+  Ok(())
+}
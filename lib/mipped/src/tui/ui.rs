@@ -0,0 +1,197 @@
+use mips::emulator::{arch::Register, disasm::disassemble};
+use ratatui::{
+  layout::{Constraint, Layout, Rect},
+  style::{Color, Modifier, Style},
+  text::{Line, Span},
+  widgets::{Block, Paragraph, Wrap},
+  Frame,
+};
+
+use super::{App, Focus, MemView, Mode, ROW_BYTES};
+
+const KEYS: &str =
+  "s step  n next  c continue  b breakpoint  m data/stack  Tab focus  r reload  q quit";
+
+pub(super) fn draw(frame: &mut Frame, app: &App) {
+  let [main, status] =
+    Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+  let [left, right] =
+    Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+  let [code, memory] =
+    Layout::vertical([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(left);
+  let [registers, console] =
+    Layout::vertical([Constraint::Length(20), Constraint::Min(3)]).areas(right);
+
+  draw_code(frame, app, code);
+  draw_memory(frame, app, memory);
+  draw_registers(frame, app, registers);
+  draw_console(frame, app, console);
+
+  let state = match &app.mode {
+    Mode::Paused => "paused",
+    Mode::Running(_) => "running",
+    Mode::Input { .. } => "input",
+    Mode::Exited(_) => "exited",
+  };
+  let text = match app.status.is_empty() {
+    true => format!("[{}] {}", state, KEYS),
+    false => format!("[{}] {}", state, app.status),
+  };
+  frame.render_widget(
+    Paragraph::new(text).style(Style::new().add_modifier(Modifier::REVERSED)),
+    status,
+  );
+}
+
+fn block(title: &str, focused: bool) -> Block<'_> {
+  let block = Block::bordered().title(title);
+  match focused {
+    true => block.border_style(Style::new().fg(Color::Cyan)),
+    false => block,
+  }
+}
+
+fn draw_code(frame: &mut Frame, app: &App, area: Rect) {
+  let rows = area.height.saturating_sub(2) as u32;
+  let cpu = app.sys.cpu();
+  let start = app.cursor.wrapping_sub(rows / 2 * 4);
+
+  let lines: Vec<Line> = (0..rows)
+    .map(|i| {
+      let addr = start.wrapping_add(i * 4);
+      let label = app
+        .program()
+        .and_then(|p| p.symbols.iter().find(|&(_, &a)| a == addr))
+        .map(|(name, _)| format!("{}:", name))
+        .unwrap_or_default();
+      let inst = match cpu.bus.load(addr, 32) {
+        Ok(inst) => disassemble(inst, addr, cpu.isa),
+        Err(_) => String::new(),
+      };
+      let breakpoint = app.sys.breakpoints().any(|a| a == addr);
+
+      let marker = match (breakpoint, addr == cpu.pc) {
+        (true, true) => "●▶",
+        (true, false) => "● ",
+        (false, true) => " ▶",
+        (false, false) => "  ",
+      };
+      let mut style = Style::new();
+      if addr == cpu.pc {
+        style = style.fg(Color::Green).add_modifier(Modifier::BOLD);
+      }
+      if addr == app.cursor && app.focus == Focus::Code {
+        style = style.add_modifier(Modifier::REVERSED);
+      }
+
+      Line::from(vec![
+        Span::styled(marker, Style::new().fg(Color::Red)),
+        Span::styled(format!(" {:08x}  {:<10}{}", addr, label, inst), style),
+      ])
+    })
+    .collect();
+
+  frame.render_widget(
+    Paragraph::new(lines).block(block(" Code ", app.focus == Focus::Code)),
+    area,
+  );
+}
+
+fn draw_memory(frame: &mut Frame, app: &App, area: Rect) {
+  let rows = area.height.saturating_sub(2) as u32;
+  let bus = &app.sys.cpu().bus;
+
+  let lines: Vec<Line> = (0..rows)
+    .map(|row| {
+      let addr = app.mem_addr.wrapping_add(row * ROW_BYTES);
+      let bytes: Vec<Option<u8>> = (0..ROW_BYTES)
+        .map(|i| bus.load(addr.wrapping_add(i), 8).ok().map(|b| b as u8))
+        .collect();
+
+      let hex: Vec<String> = bytes
+        .iter()
+        .map(|b| b.map_or("??".to_string(), |b| format!("{:02x}", b)))
+        .collect();
+      let ascii: String = bytes
+        .iter()
+        .map(|b| match b {
+          Some(b) if b.is_ascii_graphic() || *b == b' ' => *b as char,
+          _ => '.',
+        })
+        .collect();
+      Line::from(format!(
+        "{:08x}  {}  {}  |{}|",
+        addr,
+        hex[..8].join(" "),
+        hex[8..].join(" "),
+        ascii
+      ))
+    })
+    .collect();
+
+  let title = match app.mem_view {
+    MemView::Data => " Memory: data ",
+    MemView::Stack => " Memory: stack ",
+  };
+  frame.render_widget(
+    Paragraph::new(lines).block(block(title, app.focus == Focus::Memory)),
+    area,
+  );
+}
+
+fn draw_registers(frame: &mut Frame, app: &App, area: Rect) {
+  let cpu = app.sys.cpu();
+  let names = Register::NAMES.iter().chain(&["pc", "hi", "lo"]);
+  let special = [cpu.pc, cpu.hi, cpu.lo];
+  let values = cpu.regs.iter().chain(&special);
+
+  let cells: Vec<Span> = names
+    .zip(values)
+    .enumerate()
+    .map(|(n, (name, value))| {
+      let style = match app.changed(n) {
+        true => Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        false => Style::new(),
+      };
+      Span::styled(format!("{:>4} {:08x}", name, value), style)
+    })
+    .collect();
+
+  /* Two columns: the first half of the registers, then the second */
+  let half = cells.len().div_ceil(2);
+  let lines: Vec<Line> = (0..half)
+    .map(|i| {
+      let mut line = vec![cells[i].clone()];
+      if let Some(cell) = cells.get(i + half) {
+        line.push(Span::raw("   "));
+        line.push(cell.clone());
+      }
+      Line::from(line)
+    })
+    .collect();
+
+  frame.render_widget(
+    Paragraph::new(lines).block(block(" Registers ", false)),
+    area,
+  );
+}
+
+fn draw_console(frame: &mut Frame, app: &App, area: Rect) {
+  let mut text = app.console();
+  if let Mode::Input { line, .. } = &app.mode {
+    text.push_str(line);
+    text.push('_');
+  }
+
+  /* Keep the end of the output in view */
+  let rows = area.height.saturating_sub(2) as usize;
+  let lines: Vec<&str> = text.split('\n').collect();
+  let visible = lines[lines.len().saturating_sub(rows)..].join("\n");
+
+  frame.render_widget(
+    Paragraph::new(visible)
+      .wrap(Wrap { trim: false })
+      .block(block(" Console ", matches!(app.mode, Mode::Input { .. }))),
+    area,
+  );
+}
@@ -0,0 +1,146 @@
+use std::{
+  cell::RefCell,
+  fs,
+  io::{empty, Read, Write},
+  path::PathBuf,
+  rc::Rc,
+};
+
+use mipped::repl::{Flow, Repl};
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+const PROGRAM: &str = "\
+        .data
+nums:   .word 7, 8
+
+        .text
+main:   li    $t0, 1
+        jal   double
+        addiu $t0, $t0, 1
+        li    $v0, 1
+        move  $a0, $t0
+        syscall
+        li    $v0, 10
+        syscall
+
+double: addu  $t0, $t0, $t0
+        jr    $ra
+";
+
+fn source(name: &str) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("mipped-{}-{}.asm", std::process::id(), name));
+  fs::write(&path, PROGRAM).unwrap();
+  path
+}
+
+/// Runs `commands` in a fresh session, returning the debugger's output and
+/// the program's.
+fn session(name: &str, commands: &[&str]) -> (String, String) {
+  let guest = Output::default();
+  let stdout = guest.clone();
+  let streams = Box::new(move || {
+    let out: Box<dyn Write> = Box::new(stdout.clone());
+    let input: Box<dyn Read> = Box::new(empty());
+    (out, input)
+  });
+
+  let mut repl = Repl::new(&source(name), streams).unwrap();
+  let mut out = Vec::new();
+  for command in commands {
+    if let Flow::Quit = repl.execute(command, &mut out).unwrap() {
+      break;
+    }
+  }
+
+  let guest = String::from_utf8(guest.0.borrow().clone()).unwrap();
+  (String::from_utf8(out).unwrap(), guest)
+}
+
+#[test]
+fn breaks_on_labels_and_continues() {
+  let (out, guest) = session(
+    "break",
+    &["break double", "continue", "print $t0", "continue"],
+  );
+  assert!(
+    out.contains("Breakpoint at 0x80000020 <double>\n"),
+    "{}",
+    out
+  );
+  assert!(out.contains("=> 0x80000020 <double>: addu $t0, $t0, $t0\n   14  double: addu"));
+  assert!(out.contains("$t0 = 0x00000001 (1)\n"));
+  assert!(out.contains("Program exited with code 0\n"));
+  assert!(guest.starts_with('3'));
+}
+
+#[test]
+fn steps_and_repeats_the_last_command() {
+  let (out, _) = session("step", &["step 2", "", "regs"]);
+  assert!(out.contains("=> 0x80000020 <double>"));
+  assert!(out.contains("=> 0x80000008 <main+8>: addiu $t0, $t0, 1\n"));
+  assert!(out.contains("  t0 0x00000002"));
+  assert!(out.contains("  pc 0x80000008"));
+}
+
+#[test]
+fn examines_memory_and_sets_registers() {
+  let (out, _) = session(
+    "memory",
+    &[
+      "x/2w nums",
+      "x/4b nums",
+      "set $t1 = 0x10",
+      "print $t1",
+      "set $zero = 1",
+    ],
+  );
+  assert!(out.contains("0x81000000: 0x00000007 0x00000008\n"));
+  assert!(out.contains("0x81000000: 0x07 0x00 0x00 0x00\n"));
+  assert!(out.contains("$t1 = 0x00000010 (16)\n"));
+  assert!(out.contains("error: $zero cannot be changed\n"));
+}
+
+#[test]
+fn disassembles_around_the_pc() {
+  let (out, _) = session("disas", &["break double", "disas"]);
+  assert!(out.contains("main:\n=> 0x80000000:  li $t0, 1\n   0x80000004:  jal 0x80000020\n"));
+  assert!(out.contains("double:\n * 0x80000020:  addu $t0, $t0, $t0\n"));
+}
+
+#[test]
+fn reload_restarts_the_program_and_keeps_breakpoints() {
+  let (out, _) = session(
+    "reload",
+    &[
+      "break double",
+      "continue",
+      "continue",
+      "step",
+      "reload",
+      "continue",
+      "quit",
+      "regs",
+    ],
+  );
+  assert!(out.contains("error: The program is not running"));
+  assert!(out.contains("Reloaded "));
+  assert_eq!(
+    out.matches("Breakpoint at 0x80000020 <double>\n=>").count(),
+    2
+  );
+  /* Nothing runs after `quit` */
+  assert!(!out.contains("  pc "));
+}
@@ -0,0 +1,149 @@
+use std::{
+  fs,
+  path::PathBuf,
+  process::{Command, Output},
+};
+
+use mips::asm::assemble;
+
+const EXIT: &str = "
+        .data
+msg:    .asciiz \"hi\\n\"
+
+        .text
+main:   la    $a0, msg
+        li    $v0, 4
+        syscall
+        li    $a0, 3
+        li    $v0, 17
+        syscall
+";
+
+const ECHO: &str = "
+        .data
+buf:    .space 16
+
+        .text
+main:   li    $a0, 0
+        la    $a1, buf
+        li    $a2, 16
+        li    $v0, 14
+        syscall
+        move  $a2, $v0
+        li    $a0, 1
+        li    $v0, 15
+        syscall
+";
+
+fn file(name: &str, contents: &[u8]) -> PathBuf {
+  let path = std::env::temp_dir().join(format!("mipped-run-{}-{}", std::process::id(), name));
+  fs::write(&path, contents).unwrap();
+  path
+}
+
+fn mipped(args: &[&str], program: &PathBuf) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_mipped"))
+    .arg("run")
+    .args(args)
+    .arg(program)
+    .output()
+    .unwrap()
+}
+
+fn text(bytes: &[u8]) -> String {
+  String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[test]
+fn propagates_the_exit_code() {
+  let program = file("exit.asm", EXIT.as_bytes());
+
+  let output = mipped(&[], &program);
+  assert_eq!(output.status.code(), Some(3));
+  assert_eq!(text(&output.stdout), "hi\n");
+
+  let output = mipped(&["--ignore-exit-code", "--report-exit"], &program);
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(text(&output.stdout), "hi\n");
+  assert_eq!(text(&output.stderr), "Process exited with code 3\n");
+}
+
+#[test]
+fn reads_stdin_from_a_file() {
+  let program = file("echo.asm", ECHO.as_bytes());
+  let input = file("echo.txt", b"ping");
+
+  let output = mipped(&["--stdin", input.to_str().unwrap()], &program);
+  assert!(output.status.success());
+  assert_eq!(text(&output.stdout), "ping");
+}
+
+#[test]
+fn runs_raw_images() {
+  let program = assemble("li $a0, 5\nli $v0, 17\nsyscall").unwrap();
+  let bytes: Vec<u8> = program.text.iter().flat_map(|w| w.to_le_bytes()).collect();
+  let image = file("raw.bin", &bytes);
+
+  assert_eq!(mipped(&[], &image).status.code(), Some(5));
+  assert_eq!(mipped(&["--format", "raw"], &image).status.code(), Some(5));
+}
+
+#[test]
+fn stops_at_the_instruction_limit() {
+  let program = file("loop.asm", b"loop: b loop\n");
+
+  let output = mipped(&["--max-instructions", "100"], &program);
+  assert_eq!(output.status.code(), Some(1));
+  assert_eq!(
+    text(&output.stderr),
+    "mipped: Instruction limit of 100 reached at 0x80000000\n"
+  );
+}
+
+#[test]
+fn rejects_layouts_without_room_for_the_heap() {
+  let program = file("small.asm", EXIT.as_bytes());
+
+  let output = mipped(&["--memory", "64"], &program);
+  assert!(!output.status.success());
+  assert!(text(&output.stderr).contains("no room for the heap"));
+
+  let output = mipped(&["--memory", "256", "--stack", "65536"], &program);
+  assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn passes_trailing_arguments_to_the_program() {
+  /* Prints its second argument and exits with argc */
+  let program = file(
+    "args.asm",
+    b"
+        move  $t0, $a0
+        lw    $a0, 4($a1)
+        li    $v0, 4
+        syscall
+        move  $a0, $t0
+        li    $v0, 17
+        syscall
+",
+  );
+
+  let output = Command::new(env!("CARGO_BIN_EXE_mipped"))
+    .args(["run", "--report-exit"])
+    .arg(&program)
+    .args(["first", "--second", "third"])
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(3));
+  assert_eq!(text(&output.stdout), "--second");
+}
+
+#[test]
+fn stops_at_the_output_limit() {
+  let program = file("spam.asm", b"loop: li $a0, 1\nli $v0, 1\nsyscall\nb loop\n");
+
+  let output = mipped(&["--output-limit", "5", "--time-limit", "10"], &program);
+  assert_eq!(output.status.code(), Some(1));
+  assert_eq!(text(&output.stdout), "11111");
+  assert!(text(&output.stderr).contains("Output limit of 5 bytes"));
+}
@@ -0,0 +1,120 @@
+use std::{fs, path::PathBuf};
+
+use mipped::tui::{App, Focus, MemView, Mode};
+use ratatui::{
+  backend::TestBackend,
+  crossterm::event::{KeyCode, KeyEvent},
+  Terminal,
+};
+
+const PROGRAM: &str = "\
+        .data
+prompt: .asciiz \"name? \"
+buf:    .space 16
+
+        .text
+main:   li    $t0, 1
+        la    $a0, prompt
+        li    $v0, 4
+        syscall
+        li    $v0, 14
+        li    $a0, 0
+        la    $a1, buf
+        li    $a2, 16
+        syscall
+        li    $t1, 2
+        li    $v0, 10
+        syscall
+";
+
+fn app(name: &str) -> App {
+  let path: PathBuf =
+    std::env::temp_dir().join(format!("mipped-tui-{}-{}.asm", std::process::id(), name));
+  fs::write(&path, PROGRAM).unwrap();
+  App::new(&path).unwrap()
+}
+
+fn press(app: &mut App, code: KeyCode) {
+  app.handle_key(KeyEvent::from(code));
+  app.advance(1000);
+}
+
+fn screen(app: &App) -> String {
+  let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+  terminal.draw(|frame| app.draw(frame)).unwrap();
+
+  let buffer = terminal.backend().buffer();
+  buffer
+    .content()
+    .chunks(buffer.area.width as usize)
+    .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+#[test]
+fn highlights_registers_changed_by_a_step() {
+  let mut app = app("step");
+  press(&mut app, KeyCode::Char('s'));
+
+  assert_eq!(app.sys.cpu().regs[8], 1);
+  assert!(app.changed(8));
+  assert!(!app.changed(9));
+  /* The PC always moves */
+  assert!(app.changed(32));
+
+  let screen = screen(&app);
+  assert!(screen.contains("  t0 00000001"));
+  assert!(screen.contains(" ▶ 80000004  "));
+  assert!(screen.contains("main:     li $t0, 1"));
+}
+
+#[test]
+fn continues_to_breakpoints_at_the_cursor() {
+  let mut app = app("break");
+  press(&mut app, KeyCode::Down);
+  press(&mut app, KeyCode::Char('b'));
+  assert!(screen(&app).contains("● "));
+
+  press(&mut app, KeyCode::Char('c'));
+  assert_eq!(app.mode, Mode::Paused);
+  assert_eq!(app.sys.cpu().pc, 0x8000_0004);
+  assert_eq!(app.status, "Breakpoint at 0x80000004");
+}
+
+#[test]
+fn waits_for_console_input() {
+  let mut app = app("input");
+  press(&mut app, KeyCode::Char('c'));
+  assert!(matches!(app.mode, Mode::Input { .. }));
+  assert_eq!(app.console(), "name? ");
+
+  for c in "bob".chars() {
+    press(&mut app, KeyCode::Char(c));
+  }
+  assert!(screen(&app).contains("name? bob_"));
+
+  press(&mut app, KeyCode::Enter);
+  assert_eq!(app.mode, Mode::Exited(0));
+  assert_eq!(app.sys.cpu().regs[9], 2);
+  assert_eq!(app.console(), "name? bob\n");
+}
+
+#[test]
+fn shows_the_data_segment_and_stack() {
+  let mut app = app("memory");
+  let screen_text = screen(&app);
+  assert!(screen_text.contains("Memory: data"));
+  assert!(screen_text.contains("81000000  6e 61 6d 65 3f 20 00"));
+  assert!(screen_text.contains("|name? ."));
+
+  press(&mut app, KeyCode::Char('m'));
+  assert_eq!(app.mem_view, MemView::Stack);
+  assert!(screen(&app).contains("Memory: stack"));
+
+  press(&mut app, KeyCode::Tab);
+  assert_eq!(app.focus, Focus::Memory);
+  let addr = app.mem_addr;
+  press(&mut app, KeyCode::PageDown);
+  assert_eq!(app.mem_addr, addr + 256);
+}
@@ -0,0 +1,536 @@
+use crate::emulator::arch::Register;
+
+use super::{
+  parse::{Expr, Operand},
+  AsmErrorKind,
+};
+
+const AT: usize = Register::AT;
+const ZERO: usize = Register::ZERO;
+
+/// How the immediate field of an I-type instruction is derived.
+#[derive(Debug, Clone)]
+pub(crate) enum Imm {
+  /// The value itself, which must fit in 16 bits
+  Expr(Expr),
+  /// Upper half, for pairing with `ori`
+  Hi(Expr),
+  /// Upper half adjusted for a sign-extended lower half
+  HiAdj(Expr),
+  Lo(Expr),
+  /// Word offset from the delay slot
+  Branch(Expr),
+}
+
+/// A machine instruction whose fields may still refer to labels.
+#[derive(Debug, Clone)]
+pub(crate) enum Op {
+  R {
+    funct: u32,
+    rs: usize,
+    rt: usize,
+    rd: usize,
+    shamt: u32,
+  },
+  I {
+    opcode: u32,
+    rs: usize,
+    rt: usize,
+    imm: Imm,
+  },
+  J {
+    opcode: u32,
+    target: Expr,
+  },
+}
+
+fn r(funct: u32, rd: usize, rs: usize, rt: usize) -> Op {
+  Op::R {
+    funct,
+    rs,
+    rt,
+    rd,
+    shamt: 0,
+  }
+}
+
+fn i(opcode: u32, rt: usize, rs: usize, imm: Imm) -> Op {
+  Op::I {
+    opcode,
+    rs,
+    rt,
+    imm,
+  }
+}
+
+fn value(v: i64) -> Imm {
+  Imm::Expr(Expr::Value(v))
+}
+
+fn fits_signed(v: i64) -> bool {
+  (-0x8000..0x8000).contains(&v)
+}
+
+fn fits_unsigned(v: i64) -> bool {
+  (0..0x10000).contains(&v)
+}
+
+/// `li`: one instruction when the value fits in 16 bits, else two.
+fn load_imm(rd: usize, v: i64) -> Result<Vec<Op>, AsmErrorKind> {
+  if !(i32::MIN as i64..=u32::MAX as i64).contains(&v) {
+    return Err(AsmErrorKind::RANGE(v));
+  }
+
+  Ok(if fits_signed(v) {
+    vec![i(0x09, rd, ZERO, value(v))]
+  } else if fits_unsigned(v) {
+    vec![i(0x0D, rd, ZERO, value(v))]
+  } else {
+    let v = Expr::Value(v as u32 as i64);
+    vec![
+      i(0x0F, AT, ZERO, Imm::Hi(v.clone())),
+      i(0x0D, rd, AT, Imm::Lo(v)),
+    ]
+  })
+}
+
+/// Loads the address `expr` into `$at` for a following `lo($at)` access.
+fn address_hi(expr: &Expr, base: Option<usize>) -> Vec<Op> {
+  let mut ops = vec![i(0x0F, AT, ZERO, Imm::HiAdj(expr.clone()))];
+  if let Some(base) = base {
+    ops.push(r(0x21, AT, AT, base));
+  }
+  ops
+}
+
+/// R-type instructions taking `rd, rs, rt`, with the I-type form used when
+/// the last operand is an immediate.
+fn alu(mnemonic: &str) -> Option<(u32, Option<u32>)> {
+  Some(match mnemonic {
+    "add" => (0x20, Some(0x08)),
+    "addu" => (0x21, Some(0x09)),
+    "sub" => (0x22, None),
+    "subu" => (0x23, None),
+    "and" => (0x24, Some(0x0C)),
+    "or" => (0x25, Some(0x0D)),
+    "xor" => (0x26, Some(0x0E)),
+    "nor" => (0x27, None),
+    "slt" => (0x2A, Some(0x0A)),
+    "sltu" => (0x2B, Some(0x0B)),
+    _ => return None,
+  })
+}
+
+fn alu_imm(mnemonic: &str) -> Option<(u32, u32)> {
+  Some(match mnemonic {
+    "addi" => (0x08, 0x20),
+    "addiu" => (0x09, 0x21),
+    "slti" => (0x0A, 0x2A),
+    "sltiu" => (0x0B, 0x2B),
+    "andi" => (0x0C, 0x24),
+    "ori" => (0x0D, 0x25),
+    "xori" => (0x0E, 0x26),
+    _ => return None,
+  })
+}
+
+fn load_store(mnemonic: &str) -> Option<u32> {
+  Some(match mnemonic {
+    "lb" => 0x20,
+    "lh" => 0x21,
+    "lwl" => 0x22,
+    "lw" => 0x23,
+    "lbu" => 0x24,
+    "lhu" => 0x25,
+    "lwr" => 0x26,
+    "sb" => 0x28,
+    "sh" => 0x29,
+    "swl" => 0x2A,
+    "sw" => 0x2B,
+    "swr" => 0x2E,
+    "ll" => 0x30,
+    "sc" => 0x38,
+    _ => return None,
+  })
+}
+
+/// Comparison pseudo-branches: the `slt` variant, whether its operands are
+/// swapped, and whether to branch when the comparison holds.
+fn compare_branch(mnemonic: &str) -> Option<(u32, bool, bool)> {
+  Some(match mnemonic {
+    "blt" => (0x2A, false, true),
+    "bgt" => (0x2A, true, true),
+    "ble" => (0x2A, true, false),
+    "bge" => (0x2A, false, false),
+    "bltu" => (0x2B, false, true),
+    "bgtu" => (0x2B, true, true),
+    "bleu" => (0x2B, true, false),
+    "bgeu" => (0x2B, false, false),
+    _ => return None,
+  })
+}
+
+fn reg(op: &Operand) -> Result<usize, AsmErrorKind> {
+  match op {
+    Operand::Reg(r) => Ok(*r),
+    _ => Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+  }
+}
+
+fn expr(op: &Operand) -> Result<Expr, AsmErrorKind> {
+  match op {
+    Operand::Expr(e) => Ok(e.clone()),
+    _ => Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+  }
+}
+
+fn literal(op: &Operand) -> Result<i64, AsmErrorKind> {
+  match expr(op)? {
+    Expr::Value(v) => Ok(v),
+    e => Err(AsmErrorKind::OPERAND(format!("{:?}", e))),
+  }
+}
+
+/// A register operand, or an immediate loaded into `$at` first.
+fn reg_or_imm(op: &Operand, ops: &mut Vec<Op>) -> Result<usize, AsmErrorKind> {
+  match op {
+    Operand::Reg(r) => Ok(*r),
+    _ => {
+      ops.extend(load_imm(AT, literal(op)?)?);
+      Ok(AT)
+    }
+  }
+}
+
+/// Expands one statement into machine instructions. The number of
+/// instructions never depends on a label, so addresses are known after
+/// the first pass.
+pub(crate) fn expand(mnemonic: &str, operands: &[Operand]) -> Result<Vec<Op>, AsmErrorKind> {
+  let arity = |n: usize| match operands.len() == n {
+    true => Ok(()),
+    false => Err(AsmErrorKind::ARITY(mnemonic.to_string(), n)),
+  };
+  let o = operands;
+
+  if let Some((funct, imm_op)) = alu(mnemonic) {
+    arity(3)?;
+    let (rd, rs) = (reg(&o[0])?, reg(&o[1])?);
+    return match (&o[2], imm_op) {
+      (Operand::Reg(rt), _) => Ok(vec![r(funct, rd, rs, *rt)]),
+      (_, Some(opcode)) => expand_alu_imm(opcode, funct, rd, rs, literal(&o[2])?),
+      /* sub/subu with an immediate add its negation */
+      (_, None) if funct == 0x22 || funct == 0x23 => {
+        let opcode = if funct == 0x22 { 0x08 } else { 0x09 };
+        expand_alu_imm(opcode, funct - 2, rd, rs, -literal(&o[2])?)
+      }
+      _ => Err(AsmErrorKind::OPERAND(format!("{:?}", o[2]))),
+    };
+  }
+
+  if let Some((opcode, funct)) = alu_imm(mnemonic) {
+    arity(3)?;
+    let (rt, rs) = (reg(&o[0])?, reg(&o[1])?);
+    return match expr(&o[2])? {
+      Expr::Value(v) => expand_alu_imm(opcode, funct, rt, rs, v),
+      e => Ok(vec![i(opcode, rt, rs, Imm::Expr(e))]),
+    };
+  }
+
+  if let Some(opcode) = load_store(mnemonic) {
+    arity(2)?;
+    let rt = reg(&o[0])?;
+    let (offset, base) = match &o[1] {
+      Operand::Mem(offset, base) => (offset.clone(), *base),
+      Operand::Expr(e) => (e.clone(), None),
+      op => return Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+    };
+
+    return Ok(match (&offset, base) {
+      (Expr::Value(v), Some(base)) if fits_signed(*v) => vec![i(opcode, rt, base, value(*v))],
+      _ => {
+        let mut ops = address_hi(&offset, base);
+        ops.push(i(opcode, rt, AT, Imm::Lo(offset)));
+        ops
+      }
+    });
+  }
+
+  if let Some((slt, swap, when)) = compare_branch(mnemonic) {
+    arity(3)?;
+    let mut ops = Vec::new();
+    let rs = reg(&o[0])?;
+    let rt = reg_or_imm(&o[1], &mut ops)?;
+    let (a, b) = if swap { (rt, rs) } else { (rs, rt) };
+    ops.push(r(slt, AT, a, b));
+    let opcode = if when { 0x05 } else { 0x04 };
+    ops.push(i(opcode, ZERO, AT, Imm::Branch(expr(&o[2])?)));
+    return Ok(ops);
+  }
+
+  let ops = match mnemonic {
+    /* ----- Shifts ----- */
+    "sll" | "srl" | "sra" => {
+      arity(3)?;
+      let shamt = literal(&o[2])?;
+      if !(0..32).contains(&shamt) {
+        return Err(AsmErrorKind::RANGE(shamt));
+      }
+      let funct = match mnemonic {
+        "sll" => 0x00,
+        "srl" => 0x02,
+        _ => 0x03,
+      };
+      vec![Op::R {
+        funct,
+        rs: ZERO,
+        rt: reg(&o[1])?,
+        rd: reg(&o[0])?,
+        shamt: shamt as u32,
+      }]
+    }
+    "sllv" | "srlv" | "srav" => {
+      arity(3)?;
+      let funct = match mnemonic {
+        "sllv" => 0x04,
+        "srlv" => 0x06,
+        _ => 0x07,
+      };
+      vec![r(funct, reg(&o[0])?, reg(&o[2])?, reg(&o[1])?)]
+    }
+
+    /* ----- Multiply and divide ----- */
+    "mult" | "multu" | "div" | "divu" if o.len() == 2 => {
+      let funct = match mnemonic {
+        "mult" => 0x18,
+        "multu" => 0x19,
+        "div" => 0x1A,
+        _ => 0x1B,
+      };
+      vec![r(funct, ZERO, reg(&o[0])?, reg(&o[1])?)]
+    }
+    "mul" | "div" | "divu" | "rem" | "remu" => {
+      arity(3)?;
+      let mut ops = Vec::new();
+      let (rd, rs) = (reg(&o[0])?, reg(&o[1])?);
+      let rt = reg_or_imm(&o[2], &mut ops)?;
+      let (funct, from) = match mnemonic {
+        "mul" => (0x18, 0x12),
+        "div" => (0x1A, 0x12),
+        "divu" => (0x1B, 0x12),
+        "rem" => (0x1A, 0x10),
+        _ => (0x1B, 0x10),
+      };
+      ops.push(r(funct, ZERO, rs, rt));
+      ops.push(r(from, rd, ZERO, ZERO));
+      ops
+    }
+    "mfhi" | "mflo" => {
+      arity(1)?;
+      let funct = if mnemonic == "mfhi" { 0x10 } else { 0x12 };
+      vec![r(funct, reg(&o[0])?, ZERO, ZERO)]
+    }
+    "mthi" | "mtlo" => {
+      arity(1)?;
+      let funct = if mnemonic == "mthi" { 0x11 } else { 0x13 };
+      vec![r(funct, ZERO, reg(&o[0])?, ZERO)]
+    }
+
+    /* ----- Traps ----- */
+    "tge" | "tgeu" | "tlt" | "tltu" | "teq" | "tne" => {
+      arity(2)?;
+      let funct = match mnemonic {
+        "tge" => 0x30,
+        "tgeu" => 0x31,
+        "tlt" => 0x32,
+        "tltu" => 0x33,
+        "teq" => 0x34,
+        _ => 0x36,
+      };
+      vec![r(funct, ZERO, reg(&o[0])?, reg(&o[1])?)]
+    }
+
+    /* ----- Immediates and moves ----- */
+    "lui" => {
+      arity(2)?;
+      let v = literal(&o[1])?;
+      if !fits_unsigned(v) {
+        return Err(AsmErrorKind::RANGE(v));
+      }
+      vec![i(0x0F, reg(&o[0])?, ZERO, value(v))]
+    }
+    "li" => {
+      arity(2)?;
+      load_imm(reg(&o[0])?, literal(&o[1])?)?
+    }
+    "la" => {
+      arity(2)?;
+      let rd = reg(&o[0])?;
+      match &o[1] {
+        Operand::Mem(offset, Some(base)) => {
+          let mut ops = address_hi(offset, Some(*base));
+          ops.push(i(0x09, rd, AT, Imm::Lo(offset.clone())));
+          ops
+        }
+        op => {
+          let e = expr(op)?;
+          vec![
+            i(0x0F, AT, ZERO, Imm::Hi(e.clone())),
+            i(0x0D, rd, AT, Imm::Lo(e)),
+          ]
+        }
+      }
+    }
+    "move" => {
+      arity(2)?;
+      vec![r(0x21, reg(&o[0])?, ZERO, reg(&o[1])?)]
+    }
+    "not" => {
+      arity(2)?;
+      vec![r(0x27, reg(&o[0])?, reg(&o[1])?, ZERO)]
+    }
+    "neg" | "negu" => {
+      arity(2)?;
+      let funct = if mnemonic == "neg" { 0x22 } else { 0x23 };
+      vec![r(funct, reg(&o[0])?, ZERO, reg(&o[1])?)]
+    }
+    "subi" | "subiu" => {
+      arity(3)?;
+      let (opcode, funct) = if mnemonic == "subi" {
+        (0x08, 0x20)
+      } else {
+        (0x09, 0x21)
+      };
+      expand_alu_imm(opcode, funct, reg(&o[0])?, reg(&o[1])?, -literal(&o[2])?)?
+    }
+    "nop" => {
+      arity(0)?;
+      vec![r(0x00, ZERO, ZERO, ZERO)]
+    }
+
+    /* ----- Branches ----- */
+    "beq" | "bne" => {
+      arity(3)?;
+      let mut ops = Vec::new();
+      let rs = reg(&o[0])?;
+      let rt = reg_or_imm(&o[1], &mut ops)?;
+      let opcode = if mnemonic == "beq" { 0x04 } else { 0x05 };
+      ops.push(i(opcode, rt, rs, Imm::Branch(expr(&o[2])?)));
+      ops
+    }
+    "beqz" | "bnez" => {
+      arity(2)?;
+      let opcode = if mnemonic == "beqz" { 0x04 } else { 0x05 };
+      vec![i(opcode, ZERO, reg(&o[0])?, Imm::Branch(expr(&o[1])?))]
+    }
+    "blez" | "bgtz" => {
+      arity(2)?;
+      let opcode = if mnemonic == "blez" { 0x06 } else { 0x07 };
+      vec![i(opcode, ZERO, reg(&o[0])?, Imm::Branch(expr(&o[1])?))]
+    }
+    "bltz" | "bgez" | "bltzal" | "bgezal" => {
+      arity(2)?;
+      let rt = match mnemonic {
+        "bltz" => 0x00,
+        "bgez" => 0x01,
+        "bltzal" => 0x10,
+        _ => 0x11,
+      };
+      vec![i(0x01, rt, reg(&o[0])?, Imm::Branch(expr(&o[1])?))]
+    }
+    "b" => {
+      arity(1)?;
+      vec![i(0x04, ZERO, ZERO, Imm::Branch(expr(&o[0])?))]
+    }
+
+    /* ----- Jumps ----- */
+    "j" | "jal" => {
+      arity(1)?;
+      let opcode = if mnemonic == "j" { 0x02 } else { 0x03 };
+      vec![Op::J {
+        opcode,
+        target: expr(&o[0])?,
+      }]
+    }
+    "jr" => {
+      arity(1)?;
+      vec![r(0x08, ZERO, reg(&o[0])?, ZERO)]
+    }
+    "jalr" => match o.len() {
+      1 => vec![r(0x09, Register::RA, reg(&o[0])?, ZERO)],
+      _ => {
+        arity(2)?;
+        vec![r(0x09, reg(&o[0])?, reg(&o[1])?, ZERO)]
+      }
+    },
+
+    /* ----- System ----- */
+    "syscall" => {
+      arity(0)?;
+      vec![r(0x0C, ZERO, ZERO, ZERO)]
+    }
+    "break" => {
+      let code = match o.len() {
+        0 => 0,
+        _ => {
+          arity(1)?;
+          literal(&o[0])?
+        }
+      };
+      if !(0..1 << 20).contains(&code) {
+        return Err(AsmErrorKind::RANGE(code));
+      }
+      vec![Op::R {
+        funct: 0x0D,
+        rs: ZERO,
+        rt: ZERO,
+        rd: ZERO,
+        shamt: 0,
+      }
+      .with_code(code as u32)]
+    }
+
+    _ => return Err(AsmErrorKind::INSTRUCTION(mnemonic.to_string())),
+  };
+
+  Ok(ops)
+}
+
+/// An I-type ALU instruction, going through `$at` when the immediate does
+/// not fit in 16 bits.
+fn expand_alu_imm(
+  opcode: u32,
+  funct: u32,
+  rt: usize,
+  rs: usize,
+  v: i64,
+) -> Result<Vec<Op>, AsmErrorKind> {
+  /* andi, ori and xori zero-extend their immediate */
+  let fits = match opcode {
+    0x0C..=0x0E => fits_unsigned(v),
+    _ => fits_signed(v),
+  };
+
+  if fits {
+    return Ok(vec![i(opcode, rt, rs, value(v))]);
+  }
+
+  let mut ops = load_imm(AT, v)?;
+  ops.push(r(funct, rt, rs, AT));
+  Ok(ops)
+}
+
+impl Op {
+  /// Stores a `break` code in the bits between the registers and funct.
+  fn with_code(self, code: u32) -> Op {
+    match self {
+      Op::R { funct, .. } => Op::R {
+        funct,
+        rs: (code >> 15) as usize & 0x1f,
+        rt: (code >> 10) as usize & 0x1f,
+        rd: (code >> 5) as usize & 0x1f,
+        shamt: code & 0x1f,
+      },
+      op => op,
+    }
+  }
+}
@@ -0,0 +1,482 @@
+//! A two-pass assembler for MARS/SPIM-style MIPS source.
+//!
+//! The first pass expands each statement into machine instructions and
+//! assigns addresses; the second resolves labels and encodes them. The
+//! resulting [`Program`] keeps the source line of every instruction so
+//! debuggers can map addresses back to the file.
+
+mod inst;
+mod parse;
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::emulator::{
+  arch::Endian,
+  cpu::Cpu,
+  interrupt::Interrupt,
+  virt::{MemMap, MemRegion, Perm},
+};
+
+use inst::{Imm, Op};
+use parse::{Expr, Operand};
+
+/// Where `.text` is placed.
+pub const TEXT_BASE: u32 = MemMap::HIGHMEM.base;
+
+/// Where `.data` is placed, leaving 16 MiB for code.
+pub const DATA_BASE: u32 = MemMap::HIGHMEM.base + 0x0100_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("line {line}: {kind}")]
+pub struct AsmError {
+  /// 1-based source line
+  pub line: usize,
+  pub kind: AsmErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AsmErrorKind {
+  #[error("Unknown instruction: {0}")]
+  INSTRUCTION(String),
+
+  #[error("Unknown or misplaced directive: {0}")]
+  DIRECTIVE(String),
+
+  #[error("Undefined label: {0}")]
+  UNDEFINED(String),
+
+  #[error("Label defined twice: {0}")]
+  DUPLICATE(String),
+
+  #[error("Invalid operand: {0}")]
+  OPERAND(String),
+
+  #[error("{0} takes {1} operand(s)")]
+  ARITY(String, usize),
+
+  #[error("Value out of range: {0}")]
+  RANGE(i64),
+}
+
+/// An assembled program, ready to be loaded with [`Cpu::load_program`].
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+  pub text: Vec<u32>,
+  pub data: Vec<u8>,
+  pub text_base: u32,
+  pub data_base: u32,
+  /// The `main` label if there is one, else the start of `.text`
+  pub entry: u32,
+  /// Source line of the first instruction each statement expands to
+  pub lines: BTreeMap<u32, usize>,
+  pub symbols: BTreeMap<String, u32>,
+  pub endian: Endian,
+}
+
+impl Program {
+  pub fn text_end(&self) -> u32 {
+    self.text_base + self.text.len() as u32 * 4
+  }
+
+  /// The source line of the statement containing the instruction at `addr`.
+  pub fn line_at(&self, addr: u32) -> Option<usize> {
+    if !(self.text_base..self.text_end()).contains(&addr) {
+      return None;
+    }
+    self.lines.range(..=addr).next_back().map(|(_, &line)| line)
+  }
+
+  /// The first line at or after `line` that has code, with its address.
+  /// Used to place breakpoints set on blank lines or comments.
+  pub fn resolve_line(&self, line: usize) -> Option<(usize, u32)> {
+    self
+      .lines
+      .iter()
+      .filter(|&(_, &l)| l >= line)
+      .min_by_key(|&(&addr, &l)| (l, addr))
+      .map(|(&addr, &l)| (l, addr))
+  }
+}
+
+impl Cpu {
+  /// Loads an assembled program, mapping `.text` read/execute and `.data`
+  /// read/write and pointing the PC at its entry point.
+  pub fn load_program(&mut self, program: &Program) -> Result<(), Interrupt> {
+    self.bus.dram.endian = program.endian;
+
+    let text: Vec<u8> = program
+      .text
+      .iter()
+      .flat_map(|&word| match program.endian {
+        Endian::Little => word.to_le_bytes(),
+        Endian::Big => word.to_be_bytes(),
+      })
+      .collect();
+    self.bus.write_bytes(program.text_base, &text)?;
+    self.bus.write_bytes(program.data_base, &program.data)?;
+
+    let text = MemRegion {
+      base: program.text_base,
+      size: text.len() as u32,
+    };
+    self.bus.map(".text", text, Perm::RX);
+    if !program.data.is_empty() {
+      let data = MemRegion {
+        base: program.data_base,
+        size: program.data.len() as u32,
+      };
+      self.bus.map(".data", data, Perm::RW);
+    }
+
+    self.pc = program.entry;
+    self.text_end = Some(text.end());
+
+    Ok(())
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+  Text,
+  Data,
+}
+
+/// An instruction awaiting its labels.
+struct Pending {
+  line: usize,
+  addr: u32,
+  op: Op,
+}
+
+/// A `.word` whose value is a label.
+struct Fixup {
+  line: usize,
+  offset: usize,
+  expr: Expr,
+}
+
+#[derive(Default)]
+struct Assembler {
+  ops: Vec<Pending>,
+  /// Words emitted into `.text` by `.word`, by index
+  text_words: BTreeMap<usize, (usize, Expr)>,
+  text_len: usize,
+  data: Vec<u8>,
+  fixups: Vec<Fixup>,
+  symbols: BTreeMap<String, u32>,
+  endian: Endian,
+}
+
+impl Assembler {
+  fn text_addr(&self) -> u32 {
+    TEXT_BASE + self.text_len as u32 * 4
+  }
+
+  fn align_data(&mut self, align: usize) {
+    while !self.data.len().is_multiple_of(align) {
+      self.data.push(0);
+    }
+  }
+
+  fn define(&mut self, label: &str, addr: u32) -> Result<(), AsmErrorKind> {
+    if self.symbols.insert(label.to_string(), addr).is_some() {
+      return Err(AsmErrorKind::DUPLICATE(label.to_string()));
+    }
+    Ok(())
+  }
+
+  fn resolve(&self, expr: &Expr) -> Result<i64, AsmErrorKind> {
+    match expr {
+      Expr::Value(v) => Ok(*v),
+      Expr::Label(label, offset) => self
+        .symbols
+        .get(label)
+        .map(|&addr| addr as i64 + offset)
+        .ok_or_else(|| AsmErrorKind::UNDEFINED(label.clone())),
+    }
+  }
+
+  fn directive(
+    &mut self,
+    section: &mut Section,
+    name: &str,
+    operands: &[Operand],
+    line: usize,
+  ) -> Result<(), AsmErrorKind> {
+    let invalid = || AsmErrorKind::DIRECTIVE(name.to_string());
+    let literal = |op: &Operand| match op {
+      Operand::Expr(Expr::Value(v)) => Ok(*v),
+      op => Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+    };
+
+    match (name, *section) {
+      (".text", _) => *section = Section::Text,
+      (".data", _) => *section = Section::Data,
+      (".globl" | ".global" | ".extern" | ".ent" | ".end" | ".set", _) => {}
+
+      (".word", Section::Text) => {
+        for op in operands {
+          let expr = match op {
+            Operand::Expr(e) => e.clone(),
+            op => return Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+          };
+          self.text_words.insert(self.text_len, (line, expr));
+          self.text_len += 1;
+        }
+      }
+      (".align", Section::Text) => {
+        let align = literal(operands.first().ok_or_else(invalid)?)?;
+        if !(0..=12).contains(&align) {
+          return Err(AsmErrorKind::RANGE(align));
+        }
+        /* Pad with nops */
+        while !(self.text_len * 4).is_multiple_of(1 << align) {
+          self
+            .text_words
+            .insert(self.text_len, (line, Expr::Value(0)));
+          self.text_len += 1;
+        }
+      }
+      (_, Section::Text) => return Err(invalid()),
+
+      (".word", Section::Data) => {
+        self.align_data(4);
+        for op in operands {
+          let expr = match op {
+            Operand::Expr(e) => e.clone(),
+            op => return Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+          };
+          self.fixups.push(Fixup {
+            line,
+            offset: self.data.len(),
+            expr,
+          });
+          self.data.extend_from_slice(&[0; 4]);
+        }
+      }
+      (".half", Section::Data) => {
+        self.align_data(2);
+        for op in operands {
+          let v = literal(op)?;
+          if !(-0x8000..0x10000).contains(&v) {
+            return Err(AsmErrorKind::RANGE(v));
+          }
+          let bytes = match self.endian {
+            Endian::Little => (v as u16).to_le_bytes(),
+            Endian::Big => (v as u16).to_be_bytes(),
+          };
+          self.data.extend_from_slice(&bytes);
+        }
+      }
+      (".byte", Section::Data) => {
+        for op in operands {
+          let v = literal(op)?;
+          if !(-0x80..0x100).contains(&v) {
+            return Err(AsmErrorKind::RANGE(v));
+          }
+          self.data.push(v as u8);
+        }
+      }
+      (".ascii" | ".asciiz", Section::Data) => {
+        for op in operands {
+          match op {
+            Operand::Str(bytes) => self.data.extend_from_slice(bytes),
+            op => return Err(AsmErrorKind::OPERAND(format!("{:?}", op))),
+          }
+          if name == ".asciiz" {
+            self.data.push(0);
+          }
+        }
+      }
+      (".space", Section::Data) => {
+        let size = literal(operands.first().ok_or_else(invalid)?)?;
+        if !(0..0x0100_0000).contains(&size) {
+          return Err(AsmErrorKind::RANGE(size));
+        }
+        self.data.resize(self.data.len() + size as usize, 0);
+      }
+      (".align", Section::Data) => {
+        let align = literal(operands.first().ok_or_else(invalid)?)?;
+        if !(0..=12).contains(&align) {
+          return Err(AsmErrorKind::RANGE(align));
+        }
+        self.align_data(1 << align);
+      }
+      _ => return Err(invalid()),
+    }
+
+    Ok(())
+  }
+
+  /// The first pass: assigns an address to every label and statement.
+  fn layout(&mut self, src: &str) -> Result<(), AsmError> {
+    let mut section = Section::Text;
+
+    for (i, text) in src.lines().enumerate() {
+      let line = i + 1;
+      let at = |kind| AsmError { line, kind };
+      let parsed = parse::parse_line(text).map_err(at)?;
+
+      /* Data labels sit after any alignment the directive applies */
+      if section == Section::Data {
+        match parsed.op {
+          Some(".word") => self.align_data(4),
+          Some(".half") => self.align_data(2),
+          _ => {}
+        }
+      }
+
+      for label in &parsed.labels {
+        let addr = match section {
+          Section::Text => self.text_addr(),
+          Section::Data => DATA_BASE + self.data.len() as u32,
+        };
+        self.define(label, addr).map_err(at)?;
+      }
+
+      let Some(op) = parsed.op else {
+        continue;
+      };
+
+      if op.starts_with('.') {
+        self
+          .directive(&mut section, op, &parsed.operands, line)
+          .map_err(at)?;
+        continue;
+      }
+
+      if section != Section::Text {
+        return Err(at(AsmErrorKind::INSTRUCTION(op.to_string())));
+      }
+
+      for op in inst::expand(&op.to_ascii_lowercase(), &parsed.operands).map_err(at)? {
+        let addr = self.text_addr();
+        self.ops.push(Pending { line, addr, op });
+        self.text_len += 1;
+      }
+    }
+
+    Ok(())
+  }
+
+  fn encode(&self, pending: &Pending) -> Result<u32, AsmErrorKind> {
+    Ok(match &pending.op {
+      Op::R {
+        funct,
+        rs,
+        rt,
+        rd,
+        shamt,
+      } => (*rs as u32) << 21 | (*rt as u32) << 16 | (*rd as u32) << 11 | shamt << 6 | funct,
+      Op::I {
+        opcode,
+        rs,
+        rt,
+        imm,
+      } => {
+        let imm = match imm {
+          Imm::Expr(e) => {
+            let v = self.resolve(e)?;
+            if !(-0x8000..0x10000).contains(&v) {
+              return Err(AsmErrorKind::RANGE(v));
+            }
+            v as u32 & 0xffff
+          }
+          Imm::Hi(e) => (self.resolve(e)? as u32) >> 16,
+          Imm::HiAdj(e) => (self.resolve(e)? as u32).wrapping_add(0x8000) >> 16,
+          Imm::Lo(e) => self.resolve(e)? as u32 & 0xffff,
+          Imm::Branch(e) => {
+            let target = self.resolve(e)?;
+            let offset = target - (pending.addr as i64 + 4);
+            if offset % 4 != 0 || !(-0x20000..0x20000).contains(&offset) {
+              return Err(AsmErrorKind::RANGE(target));
+            }
+            (offset >> 2) as u32 & 0xffff
+          }
+        };
+        opcode << 26 | (*rs as u32) << 21 | (*rt as u32) << 16 | imm
+      }
+      Op::J { opcode, target } => {
+        let target = self.resolve(target)?;
+        let region = (pending.addr.wrapping_add(4) & 0xf000_0000) as i64;
+        if target % 4 != 0 || target & !0x0fff_ffff != region {
+          return Err(AsmErrorKind::RANGE(target));
+        }
+        opcode << 26 | ((target as u32 >> 2) & 0x03ff_ffff)
+      }
+    })
+  }
+
+  /// The second pass: resolves labels and encodes each instruction.
+  fn finish(self) -> Result<Program, AsmError> {
+    let mut text = vec![0; self.text_len];
+    let mut lines = BTreeMap::new();
+
+    for pending in &self.ops {
+      let at = |kind| AsmError {
+        line: pending.line,
+        kind,
+      };
+      let index = ((pending.addr - TEXT_BASE) / 4) as usize;
+      text[index] = self.encode(pending).map_err(at)?;
+      lines.entry(pending.addr).or_insert(pending.line);
+    }
+
+    for (&index, (line, expr)) in &self.text_words {
+      let at = |kind| AsmError { line: *line, kind };
+      text[index] = self.resolve(expr).map_err(at)? as u32;
+      lines.insert(TEXT_BASE + index as u32 * 4, *line);
+    }
+
+    /* Only the first instruction of each statement starts a line */
+    let mut last = None;
+    lines.retain(|_, line| last.replace(*line) != Some(*line));
+
+    let mut data = self.data.clone();
+    for fixup in &self.fixups {
+      let v = self.resolve(&fixup.expr).map_err(|kind| AsmError {
+        line: fixup.line,
+        kind,
+      })?;
+      if !(i32::MIN as i64..=u32::MAX as i64).contains(&v) {
+        return Err(AsmError {
+          line: fixup.line,
+          kind: AsmErrorKind::RANGE(v),
+        });
+      }
+      let bytes = match self.endian {
+        Endian::Little => (v as u32).to_le_bytes(),
+        Endian::Big => (v as u32).to_be_bytes(),
+      };
+      data[fixup.offset..fixup.offset + 4].copy_from_slice(&bytes);
+    }
+
+    Ok(Program {
+      text,
+      data,
+      text_base: TEXT_BASE,
+      data_base: DATA_BASE,
+      entry: self.symbols.get("main").copied().unwrap_or(TEXT_BASE),
+      lines,
+      symbols: self.symbols,
+      endian: self.endian,
+    })
+  }
+}
+
+/// Assembles MIPS source into a little-endian [`Program`].
+pub fn assemble(src: &str) -> Result<Program, AsmError> {
+  assemble_with(src, Endian::Little)
+}
+
+/// Assembles MIPS source into a [`Program`] with the given byte order.
+pub fn assemble_with(src: &str, endian: Endian) -> Result<Program, AsmError> {
+  let mut asm = Assembler {
+    endian,
+    ..Assembler::default()
+  };
+  asm.layout(src)?;
+  asm.finish()
+}
@@ -0,0 +1,188 @@
+use crate::emulator::arch::Register;
+
+use super::AsmErrorKind;
+
+/// A value that may refer to a label resolved in the second pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expr {
+  Value(i64),
+  Label(String, i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Operand {
+  Reg(usize),
+  Expr(Expr),
+  /// `offset($base)`, `($base)` or a bare address
+  Mem(Expr, Option<usize>),
+  Str(Vec<u8>),
+}
+
+/// One source line, split into its labels and statement.
+#[derive(Debug, Default)]
+pub(crate) struct Line<'a> {
+  pub labels: Vec<&'a str>,
+  /// Mnemonic or directive, including the leading `.`
+  pub op: Option<&'a str>,
+  pub operands: Vec<Operand>,
+}
+
+pub(crate) fn parse_line(line: &str) -> Result<Line<'_>, AsmErrorKind> {
+  let mut rest = strip_comment(line).trim();
+  let mut parsed = Line::default();
+
+  /* Any number of leading `label:` */
+  while let Some((label, after)) = rest.split_once(':') {
+    let label = label.trim();
+    if !is_ident(label) {
+      break;
+    }
+    parsed.labels.push(label);
+    rest = after.trim();
+  }
+
+  if rest.is_empty() {
+    return Ok(parsed);
+  }
+
+  let (op, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+  parsed.op = Some(op);
+  parsed.operands = split_operands(args)
+    .into_iter()
+    .map(parse_operand)
+    .collect::<Result<_, _>>()?;
+
+  Ok(parsed)
+}
+
+fn strip_comment(line: &str) -> &str {
+  let mut quoted = None;
+  let mut escaped = false;
+  for (i, c) in line.char_indices() {
+    match (quoted, c) {
+      _ if escaped => escaped = false,
+      (Some(_), '\\') => escaped = true,
+      (Some(q), _) if c == q => quoted = None,
+      (None, '"' | '\'') => quoted = Some(c),
+      (None, '#') => return &line[..i],
+      _ => {}
+    }
+  }
+  line
+}
+
+/// Splits on commas outside of quotes.
+fn split_operands(args: &str) -> Vec<&str> {
+  let mut operands = Vec::new();
+  let mut quoted = None;
+  let mut escaped = false;
+  let mut start = 0;
+
+  for (i, c) in args.char_indices() {
+    match (quoted, c) {
+      _ if escaped => escaped = false,
+      (Some(_), '\\') => escaped = true,
+      (Some(q), _) if c == q => quoted = None,
+      (None, '"' | '\'') => quoted = Some(c),
+      (None, ',') => {
+        operands.push(args[start..i].trim());
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+
+  let last = args[start..].trim();
+  if !last.is_empty() || !operands.is_empty() {
+    operands.push(last);
+  }
+  operands
+}
+
+fn parse_operand(text: &str) -> Result<Operand, AsmErrorKind> {
+  let invalid = || AsmErrorKind::OPERAND(text.to_string());
+
+  if let Some(quoted) = text.strip_prefix('"') {
+    let body = quoted.strip_suffix('"').ok_or_else(invalid)?;
+    return unescape(body).map(Operand::Str).ok_or_else(invalid);
+  }
+
+  if text.starts_with('$') {
+    return Register::from_name(text)
+      .map(Operand::Reg)
+      .ok_or_else(invalid);
+  }
+
+  /* offset($base) */
+  if let Some((offset, base)) = text.strip_suffix(')').and_then(|t| t.split_once('(')) {
+    let base = Register::from_name(base.trim()).ok_or_else(invalid)?;
+    let offset = match offset.trim() {
+      "" => Expr::Value(0),
+      offset => parse_expr(offset).ok_or_else(invalid)?,
+    };
+    return Ok(Operand::Mem(offset, Some(base)));
+  }
+
+  parse_expr(text).map(Operand::Expr).ok_or_else(invalid)
+}
+
+/// Parses a number, character literal, `label`, or `label+offset`.
+fn parse_expr(text: &str) -> Option<Expr> {
+  if let Some(value) = parse_number(text) {
+    return Some(Expr::Value(value));
+  }
+
+  let split = text.rfind(['+', '-']).filter(|&i| i > 0);
+  let (label, offset) = match split {
+    Some(i) => (text[..i].trim(), parse_number(text[i..].trim())?),
+    None => (text, 0),
+  };
+  is_ident(label).then(|| Expr::Label(label.to_string(), offset))
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+  let (negative, digits) = match text.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, text.strip_prefix('+').unwrap_or(text)),
+  };
+
+  let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+    i64::from_str_radix(hex, 16).ok()?
+  } else if let Some(char) = digits.strip_prefix('\'').and_then(|c| c.strip_suffix('\'')) {
+    match unescape(char)?.as_slice() {
+      &[byte] => byte as i64,
+      _ => return None,
+    }
+  } else {
+    digits.parse::<i64>().ok()?
+  };
+
+  Some(if negative { -value } else { value })
+}
+
+fn unescape(text: &str) -> Option<Vec<u8>> {
+  let mut bytes = Vec::with_capacity(text.len());
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    let c = match c {
+      '\\' => match chars.next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        c @ ('\\' | '"' | '\'') => c,
+        _ => return None,
+      },
+      c => c,
+    };
+    let mut buf = [0; 4];
+    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+  }
+  Some(bytes)
+}
+
+fn is_ident(text: &str) -> bool {
+  let mut chars = text.chars();
+  matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
@@ -59,3 +59,45 @@ impl Register {
   /* Return address */
   pub const RA: usize = 31;
 }
+
+impl Register {
+  /// Conventional names of the general-purpose registers, without the `$`.
+  #[rustfmt::skip]
+  pub const NAMES: [&'static str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+  ];
+
+  /// Looks up a register by name or number, with or without the `$`.
+  /// `s8` is accepted as an alias of `fp`.
+  pub fn from_name(name: &str) -> Option<usize> {
+    let name = name.strip_prefix('$').unwrap_or(name);
+    match name.parse::<usize>() {
+      Ok(n) if n < 32 => Some(n),
+      Ok(_) => None,
+      Err(_) if name == "s8" => Some(Register::FP),
+      Err(_) => Register::NAMES.iter().position(|&n| n == name),
+    }
+  }
+}
+
+/// Instruction set revision understood by the decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Isa {
+  /* MIPS32 Release 1-5 */
+  #[default]
+  Mips32,
+
+  /* MIPS32 Release 6 (re-encoded branches, no hi/lo) */
+  Mips32R6,
+}
+
+/// Byte order used for multi-byte memory accesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Endian {
+  #[default]
+  Little,
+  Big,
+}
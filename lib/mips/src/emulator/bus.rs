@@ -1,53 +1,269 @@
+use std::sync::Arc;
+
 use crate::interrupt_exception;
 
 use super::{
   dram::Dram,
-  interrupt::Result,
-  virt::{MemMap, MemRegion},
+  interrupt::{Result, Violation},
+  memory::SparseMemory,
+  monitor::Monitor,
+  virt::{Access, Mapping, MemMap, MemRegion, Perm},
+  watch::{WatchAction, WatchHit, WatchKind, Watchpoint},
 };
 
 #[derive(Debug, Clone)]
 pub struct Bus {
   pub dram: Dram,
+  /// Memory outside of DRAM, such as the segments of an ELF executable
+  /// linked below `0x80000000`.
+  pub segments: Vec<Dram>,
+  pub monitor: Monitor,
+  /// Permission overrides, searched most recent first.
+  pub mappings: Vec<Mapping>,
+  /// Permissions of memory not covered by any mapping.
+  pub default_perm: Perm,
+  pub(crate) watchpoints: Vec<Watchpoint>,
+  /// Hits recorded since the start of the current instruction.
+  watch_hits: Vec<WatchHit>,
+  pub(crate) next_watch: usize,
 }
 
 impl Default for Bus {
   fn default() -> Self {
     Self {
       dram: Dram::default(),
+      segments: Vec::new(),
+      monitor: Monitor::default(),
+      mappings: Vec::new(),
+      default_perm: Perm::RWX,
+      watchpoints: Vec::new(),
+      watch_hits: Vec::new(),
+      next_watch: 1,
     }
   }
 }
 
 impl Bus {
   pub fn load(&self, addr: u32, size: u32) -> Result<u32> {
-    let valid = Bus::ensure_valid_address(
-      addr,
-      MemRegion {
-        base: MemMap::HIGHMEM.base,
-        size: self.dram.size(),
-      },
-    );
+    if !self.is_valid_access(addr, size) {
+      interrupt_exception!(ADDRL(addr))
+    }
+    self.check_perm(addr, Access::Read)?;
 
-    match valid {
-      Ok(_) => self.dram.load(addr, size),
-      Err(_) => interrupt_exception!(ADDRL(addr)),
+    match self.memory(addr, size / 8) {
+      Some(mem) => mem.load(addr, size),
+      None => interrupt_exception!(ADDRL(addr)),
     }
   }
 
   pub fn store(&mut self, addr: u32, size: u32, value: u32) -> Result<()> {
-    let valid = Bus::ensure_valid_address(
-      addr,
-      MemRegion {
-        base: MemMap::HIGHMEM.base,
-        size: self.dram.size(),
-      },
-    );
+    if !self.is_valid_access(addr, size) {
+      interrupt_exception!(ADDRS(addr))
+    }
+    self.check_perm(addr, Access::Write)?;
+
+    let watched = !self.watchpoints.is_empty();
+    let Some(mem) = self.memory_mut(addr, size / 8) else {
+      interrupt_exception!(ADDRS(addr))
+    };
+    let old = match watched {
+      true => mem.load(addr, size)?,
+      false => value,
+    };
+    mem.store(addr, size, value)?;
+    self.monitor.invalidate(addr, size);
+    self.record_watch(addr, size, Access::Write, old, value);
+    Ok(())
+  }
+
+  /// Loads on behalf of the guest, triggering read watchpoints. Host-side
+  /// inspection should use [`Bus::load`] instead.
+  pub fn read(&mut self, addr: u32, size: u32) -> Result<u32> {
+    let value = self.load(addr, size)?;
+    self.record_watch(addr, size, Access::Read, value, value);
+    Ok(value)
+  }
+
+  /// Fetches an instruction word. A misaligned PC is an address error,
+  /// while an aligned PC outside of memory is a bus error.
+  pub fn fetch(&self, addr: u32) -> Result<u32> {
+    if !addr.is_multiple_of(4) {
+      interrupt_exception!(ADDRL(addr))
+    }
+
+    if !self.is_valid_access(addr, 32) {
+      interrupt_exception!(IBUS(addr))
+    }
+    self.check_perm(addr, Access::Execute)?;
+
+    match self.memory(addr, 4) {
+      Some(mem) => mem.load(addr, 32),
+      None => interrupt_exception!(IBUS(addr)),
+    }
+  }
+
+  /// The memory holding all `bytes` bytes starting at `addr`, if any.
+  pub fn memory(&self, addr: u32, bytes: u32) -> Option<&Dram> {
+    std::iter::once(&self.dram)
+      .chain(&self.segments)
+      .find(|mem| mem.holds(addr, bytes))
+  }
+
+  fn memory_mut(&mut self, addr: u32, bytes: u32) -> Option<&mut Dram> {
+    std::iter::once(&mut self.dram)
+      .chain(&mut self.segments)
+      .find(|mem| mem.holds(addr, bytes))
+  }
+
+  /// Backs `region` with zeroed memory in the byte order of DRAM. The
+  /// region may not overlap existing memory.
+  pub fn add_memory(&mut self, region: MemRegion) -> Result<()> {
+    let overlaps = std::iter::once(&self.dram)
+      .chain(&self.segments)
+      .any(|mem| {
+        (mem.base as u64) < region.base as u64 + region.size as u64
+          && (region.base as u64) < mem.base as u64 + mem.size() as u64
+      });
+    if region.size == 0 || overlaps || region.base.checked_add(region.size - 1).is_none() {
+      interrupt_exception!(DBUS(region.base))
+    }
+
+    let mut mem = Dram::at(region.base, Box::new(SparseMemory::new(region.size)));
+    mem.endian = self.dram.endian;
+    self.segments.push(mem);
+    Ok(())
+  }
+
+  /// Gives `region` its own permissions, taking precedence over any
+  /// earlier mapping that overlaps it.
+  pub fn map<S: Into<String>>(&mut self, name: S, region: MemRegion, perm: Perm) {
+    self.mappings.push(Mapping {
+      name: name.into(),
+      region,
+      perm,
+    });
+  }
+
+  /// The mapping that governs `addr`, if any.
+  pub fn mapping(&self, addr: u32) -> Option<&Mapping> {
+    self.mappings.iter().rev().find(|m| m.region.contains(addr))
+  }
 
-    match valid {
-      Ok(_) => self.store(addr, size, value),
-      Err(_) => interrupt_exception!(ADDRS(addr)),
+  fn check_perm(&self, addr: u32, access: Access) -> Result<()> {
+    let (region, perm) = match self.mapping(addr) {
+      Some(mapping) => (mapping.name.as_str(), mapping.perm),
+      None => ("unmapped memory", self.default_perm),
+    };
+
+    if !perm.allows(access) {
+      interrupt_exception!(PROTECTION(Violation {
+        addr,
+        access,
+        region: region.to_string(),
+        perm,
+      }))
+    }
+
+    Ok(())
+  }
+
+  /// Loads a word and places a reservation on its line for `agent`.
+  pub fn load_linked(&mut self, agent: usize, addr: u32) -> Result<u32> {
+    let value = self.read(addr, 32)?;
+    self.monitor.link(agent, addr);
+    Ok(value)
+  }
+
+  /// Stores a word only if `agent` still holds a reservation on its line.
+  /// Returns whether the store took place. The address is checked first,
+  /// so a bad address faults whether or not the reservation is held.
+  pub fn store_conditional(&mut self, agent: usize, addr: u32, value: u32) -> Result<bool> {
+    if !self.is_valid_access(addr, 32) {
+      interrupt_exception!(ADDRS(addr))
+    }
+    self.check_perm(addr, Access::Write)?;
+
+    if !self.monitor.is_linked(agent, addr) {
+      return Ok(false);
+    }
+
+    self.store(addr, 32, value)?;
+    self.monitor.unlink(agent);
+    Ok(true)
+  }
+
+  /// Pauses execution whenever `region` is accessed as `kind`. Returns an
+  /// id for [`Bus::unwatch`].
+  pub fn watch(&mut self, region: MemRegion, kind: WatchKind) -> usize {
+    self.add_watch(region, kind, WatchAction::Pause)
+  }
+
+  /// Calls `callback` whenever `region` is accessed as `kind`, without
+  /// pausing execution.
+  pub fn watch_with<F>(&mut self, region: MemRegion, kind: WatchKind, callback: F) -> usize
+  where
+    F: Fn(&WatchHit) + Send + Sync + 'static,
+  {
+    self.add_watch(region, kind, WatchAction::Callback(Arc::new(callback)))
+  }
+
+  fn add_watch(&mut self, region: MemRegion, kind: WatchKind, action: WatchAction) -> usize {
+    let id = self.next_watch;
+    self.next_watch += 1;
+    self.watchpoints.push(Watchpoint {
+      id,
+      region,
+      kind,
+      action,
+    });
+    id
+  }
+
+  /// Removes a watchpoint, returning whether it existed.
+  pub fn unwatch(&mut self, id: usize) -> bool {
+    let len = self.watchpoints.len();
+    self.watchpoints.retain(|w| w.id != id);
+    self.watchpoints.len() != len
+  }
+
+  pub fn watchpoints(&self) -> &[Watchpoint] {
+    &self.watchpoints
+  }
+
+  fn record_watch(&mut self, addr: u32, size: u32, access: Access, old: u32, new: u32) {
+    for w in &self.watchpoints {
+      if w.triggers(addr, size, access) {
+        self.watch_hits.push(WatchHit {
+          id: w.id,
+          pc: 0,
+          addr,
+          size,
+          access,
+          old,
+          new,
+        });
+      }
+    }
+  }
+
+  /// Attributes the hits recorded since the last call to the instruction at
+  /// `pc`, running callbacks. Returns the first hit that should pause.
+  pub(crate) fn take_watch_hit(&mut self, pc: u32) -> Option<WatchHit> {
+    let mut pause = None;
+    for mut hit in std::mem::take(&mut self.watch_hits) {
+      hit.pc = pc;
+      match self
+        .watchpoints
+        .iter()
+        .find(|w| w.id == hit.id)
+        .map(|w| &w.action)
+      {
+        Some(WatchAction::Callback(callback)) => callback(&hit),
+        Some(WatchAction::Pause) if pause.is_none() => pause = Some(hit),
+        _ => {}
+      }
     }
+    pause
   }
 
   pub fn dram_splice(&mut self, offset: u32, code: Vec<u8>) -> Result<()> {
@@ -66,14 +282,35 @@ impl Bus {
       },
     )?;
 
-    let start = offset as usize;
-    let end = start + code.len();
+    self.dram.mem.write(offset, &code);
 
-    self.dram.dram.splice(start..end, code.iter().cloned());
+    Ok(())
+  }
+
+  /// Copies `bytes` verbatim into memory starting at `addr`.
+  pub fn write_bytes(&mut self, addr: u32, bytes: &[u8]) -> Result<()> {
+    if bytes.is_empty() {
+      return Ok(());
+    }
+
+    let len = u32::try_from(bytes.len()).unwrap_or(u32::MAX);
+    let Some(mem) = self.memory_mut(addr, len) else {
+      interrupt_exception!(ADDRS(addr))
+    };
+
+    mem.write_bytes(addr, bytes);
+    self.monitor.invalidate(addr, bytes.len() as u32 * 8);
 
     Ok(())
   }
 
+  /// Whether a `size`-bit access at `addr` is naturally aligned and lies
+  /// entirely inside memory.
+  fn is_valid_access(&self, addr: u32, size: u32) -> bool {
+    let bytes = (size / 8).max(1);
+    addr.is_multiple_of(bytes) && self.memory(addr, bytes).is_some()
+  }
+
   fn ensure_valid_address(addr: u32, region: MemRegion) -> Result<()> {
     let start = region.base;
     let end = start + region.size;
@@ -82,6 +319,6 @@ impl Bus {
       return Ok(());
     }
 
-    interrupt_exception!(DBUS(format!("Invalid memory address: {:#010X}", addr)))
+    interrupt_exception!(DBUS(addr))
   }
 }
@@ -0,0 +1,233 @@
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+  arch::{Endian, Isa},
+  cpu::{Cpu, HazardLog},
+  dram::Dram,
+  memory::{Memory, SparseMemory},
+  monitor::Monitor,
+  sys::{Stack, Sys},
+  vfs::Vfs,
+  virt::{Mapping, Perm},
+};
+
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"MIPSCKPT";
+
+/// Bumped whenever the layout of [`Checkpoint`] changes.
+pub const CHECKPOINT_VERSION: u32 = 7;
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+  #[error("Not a checkpoint file")]
+  MAGIC,
+
+  #[error("Unsupported checkpoint version: {0}")]
+  VERSION(u32),
+
+  #[error("Error accessing checkpoint: {0}")]
+  IO(#[from] std::io::Error),
+
+  #[error("Malformed checkpoint: {0}")]
+  FORMAT(#[from] bincode::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CpuState {
+  id: usize,
+  regs: [u32; 32],
+  pc: u32,
+  hi: u32,
+  lo: u32,
+  badvaddr: u32,
+  epc: u32,
+  isa: Isa,
+  endian: Endian,
+  delay_slots: bool,
+  load_delay: bool,
+  forbidden_slot: bool,
+  text_end: Option<u32>,
+  halt_addr: Option<u32>,
+  delayed_load: Option<(usize, u32)>,
+  branch_target: Option<u32>,
+  load_hazards: HazardLog,
+}
+
+/// A block of memory as its extent and non-zero pages.
+#[derive(Debug, Serialize, Deserialize)]
+struct MemoryImage {
+  base: u32,
+  size: u32,
+  pages: Vec<(u32, Vec<u8>)>,
+}
+
+impl MemoryImage {
+  fn capture(dram: &Dram) -> MemoryImage {
+    let mut pages = Vec::new();
+    dram
+      .mem
+      .for_each_page(&mut |offset, bytes| pages.push((offset, bytes.to_vec())));
+
+    MemoryImage {
+      base: dram.base,
+      size: dram.size(),
+      pages,
+    }
+  }
+
+  fn restore(self, endian: Endian) -> Dram {
+    let mut mem = SparseMemory::new(self.size);
+    for (offset, bytes) in &self.pages {
+      mem.write(*offset, bytes);
+    }
+
+    let mut dram = Dram::at(self.base, Box::new(mem));
+    dram.endian = endian;
+    dram
+  }
+}
+
+/// Everything needed to resume a [`Sys`] exactly where it left off, apart
+/// from its host I/O streams.
+///
+/// On disk a checkpoint is the magic bytes and version, followed by the
+/// deflate-compressed bincode encoding of this struct. Memory is stored as
+/// a list of non-zero pages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+  cpu: CpuState,
+  dram: MemoryImage,
+  segments: Vec<MemoryImage>,
+  mappings: Vec<Mapping>,
+  default_perm: Perm,
+  monitor: Monitor,
+  brk: u32,
+  stack: Stack,
+  stack_checked: bool,
+  retired: u64,
+  vfs: Vfs,
+  exit_code: Option<i32>,
+}
+
+impl Checkpoint {
+  pub fn capture(sys: &Sys) -> Checkpoint {
+    let cpu = &sys.cpu;
+
+    Checkpoint {
+      cpu: CpuState {
+        id: cpu.id,
+        regs: cpu.regs,
+        pc: cpu.pc,
+        hi: cpu.hi,
+        lo: cpu.lo,
+        badvaddr: cpu.badvaddr,
+        epc: cpu.epc,
+        isa: cpu.isa,
+        endian: cpu.bus.dram.endian,
+        delay_slots: cpu.delay_slots,
+        load_delay: cpu.load_delay,
+        forbidden_slot: cpu.forbidden_slot,
+        text_end: cpu.text_end,
+        halt_addr: cpu.halt_addr,
+        delayed_load: cpu.delayed_load,
+        branch_target: cpu.branch_target,
+        load_hazards: cpu.load_hazards.clone(),
+      },
+      dram: MemoryImage::capture(&cpu.bus.dram),
+      segments: cpu.bus.segments.iter().map(MemoryImage::capture).collect(),
+      mappings: cpu.bus.mappings.clone(),
+      default_perm: cpu.bus.default_perm,
+      monitor: cpu.bus.monitor.clone(),
+      brk: sys.brk,
+      stack: sys.stack,
+      stack_checked: sys.stack_checked,
+      retired: sys.retired,
+      vfs: sys.vfs.clone(),
+      exit_code: sys.exit_code,
+    }
+  }
+
+  /// Replaces the machine state of `sys` with this checkpoint. Memory is
+  /// restored into a sparse backing store. As with [`Sys::restore`],
+  /// watchpoints are debugger state and survive.
+  pub fn apply(self, sys: &mut Sys) {
+    let state = self.cpu;
+
+    let mut cpu = Cpu::new();
+    cpu.id = state.id;
+    cpu.regs = state.regs;
+    cpu.pc = state.pc;
+    cpu.hi = state.hi;
+    cpu.lo = state.lo;
+    cpu.badvaddr = state.badvaddr;
+    cpu.epc = state.epc;
+    cpu.isa = state.isa;
+    cpu.delay_slots = state.delay_slots;
+    cpu.load_delay = state.load_delay;
+    cpu.forbidden_slot = state.forbidden_slot;
+    cpu.text_end = state.text_end;
+    cpu.halt_addr = state.halt_addr;
+    cpu.delayed_load = state.delayed_load;
+    cpu.branch_target = state.branch_target;
+    cpu.load_hazards = state.load_hazards;
+    cpu.bus.dram = self.dram.restore(state.endian);
+    cpu.bus.segments = self
+      .segments
+      .into_iter()
+      .map(|image| image.restore(state.endian))
+      .collect();
+    cpu.bus.mappings = self.mappings;
+    cpu.bus.default_perm = self.default_perm;
+    cpu.bus.monitor = self.monitor;
+
+    sys.replace_cpu(cpu);
+    sys.brk = self.brk;
+    sys.stack = self.stack;
+    sys.stack_checked = self.stack_checked;
+    sys.retired = self.retired;
+    sys.vfs = self.vfs;
+    sys.exit_code = self.exit_code;
+  }
+
+  pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), CheckpointError> {
+    writer.write_all(CHECKPOINT_MAGIC)?;
+    writer.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+
+    let mut encoder = DeflateEncoder::new(writer, Compression::default());
+    bincode::serialize_into(&mut encoder, self)?;
+    encoder.finish()?;
+
+    Ok(())
+  }
+
+  pub fn read_from<R: Read>(mut reader: R) -> Result<Checkpoint, CheckpointError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != CHECKPOINT_MAGIC {
+      return Err(CheckpointError::MAGIC);
+    }
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != CHECKPOINT_VERSION {
+      return Err(CheckpointError::VERSION(version));
+    }
+
+    Ok(bincode::deserialize_from(DeflateDecoder::new(reader))?)
+  }
+}
+
+impl Sys {
+  pub fn save_checkpoint<W: Write>(&self, writer: W) -> Result<(), CheckpointError> {
+    Checkpoint::capture(self).write_to(writer)
+  }
+
+  pub fn load_checkpoint<R: Read>(&mut self, reader: R) -> Result<(), CheckpointError> {
+    Checkpoint::read_from(reader)?.apply(self);
+    Ok(())
+  }
+}
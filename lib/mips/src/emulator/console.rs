@@ -0,0 +1,129 @@
+use std::{
+  collections::VecDeque,
+  io::{self, Read, Write},
+  sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{cpu::Cpu, sys::Sys};
+
+#[derive(Debug, Default)]
+struct State {
+  input: VecDeque<u8>,
+  output: Vec<u8>,
+  transcript: Vec<u8>,
+}
+
+/// Scripted console input and captured console output, for checking a
+/// program's I/O after it runs.
+///
+/// Input is handed out a line at a time, as a terminal would, and every
+/// line the guest reads is echoed into the transcript between its output.
+/// Clones share the same console, so keep one to inspect after giving
+/// another's streams to a machine.
+#[derive(Debug, Clone, Default)]
+pub struct Console(Arc<Mutex<State>>);
+
+impl Console {
+  /// A console with no input.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_input<B: Into<Vec<u8>>>(input: B) -> Self {
+    let console = Console::new();
+    console.state().input.extend(input.into());
+    console
+  }
+
+  /// Input made of `lines`, each terminated by a newline.
+  pub fn with_lines<I, S>(lines: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let input: String = lines
+      .into_iter()
+      .map(|line| format!("{}\n", line.as_ref()))
+      .collect();
+    Console::with_input(input)
+  }
+
+  fn state(&self) -> MutexGuard<'_, State> {
+    self.0.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  /// A writer recording into this console's output.
+  pub fn stdout(&self) -> ConsoleWriter {
+    ConsoleWriter(self.clone())
+  }
+
+  /// A reader serving this console's input.
+  pub fn stdin(&self) -> ConsoleReader {
+    ConsoleReader(self.clone())
+  }
+
+  /// Everything written so far, lossily decoded as UTF-8.
+  pub fn output(&self) -> String {
+    String::from_utf8_lossy(&self.state().output).into_owned()
+  }
+
+  pub fn output_bytes(&self) -> Vec<u8> {
+    self.state().output.clone()
+  }
+
+  /// Output interleaved with the input lines read, as a terminal shows it.
+  pub fn transcript(&self) -> String {
+    String::from_utf8_lossy(&self.state().transcript).into_owned()
+  }
+
+  /// Input not yet read by the guest.
+  pub fn remaining_input(&self) -> Vec<u8> {
+    self.state().input.iter().copied().collect()
+  }
+}
+
+/// The output half of a [`Console`].
+#[derive(Debug, Clone)]
+pub struct ConsoleWriter(Console);
+
+impl Write for ConsoleWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let mut state = self.0.state();
+    state.output.extend_from_slice(buf);
+    state.transcript.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// The input half of a [`Console`].
+#[derive(Debug, Clone)]
+pub struct ConsoleReader(Console);
+
+impl Read for ConsoleReader {
+  /// Reads up to the end of the next line, like a terminal in canonical
+  /// mode.
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let mut state = self.0.state();
+    let line = match state.input.iter().position(|&b| b == b'\n') {
+      Some(end) => end + 1,
+      None => state.input.len(),
+    };
+    let n = line.min(buf.len());
+    for (slot, byte) in buf.iter_mut().zip(state.input.drain(..n)) {
+      *slot = byte;
+    }
+    state.transcript.extend_from_slice(&buf[..n]);
+    Ok(n)
+  }
+}
+
+impl Sys {
+  /// A machine attached to `console` for its input and output.
+  pub fn with_console(cpu: Cpu, console: &Console) -> Sys {
+    Sys::new(cpu, console.stdout(), console.stdin())
+  }
+}
@@ -0,0 +1,200 @@
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender, TryRecvError},
+    Arc,
+  },
+  thread::{self, JoinHandle},
+};
+
+use super::{debug::StopReason, interrupt::Interrupt, sys::Sys};
+
+/// A request for a running [`Sys`] to stop, shareable across threads.
+///
+/// The machine checks the flag between instructions and clears it when it
+/// stops, so running it again carries on from the same place.
+#[derive(Debug, Clone, Default)]
+pub struct StopFlag(Arc<AtomicBool>);
+
+impl StopFlag {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Asks the machine to stop before its next instruction.
+  pub fn request(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_requested(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  /// Clears a pending request, returning whether there was one.
+  pub fn take(&self) -> bool {
+    self.is_requested() && self.0.swap(false, Ordering::Relaxed)
+  }
+}
+
+/// What a [`Worker`] reports when its machine stops running.
+#[derive(Debug)]
+pub enum Event {
+  /// Execution stopped: [`StopReason::Interrupted`] after
+  /// [`Worker::pause`], otherwise at a breakpoint, watchpoint, limit or
+  /// the end of the program
+  Stopped(StopReason),
+  /// Execution stopped on an exception or other interrupt
+  Faulted(Interrupt),
+}
+
+enum Command {
+  Pause,
+  Resume,
+  Inspect(Box<dyn FnOnce(&mut Sys) + Send>),
+  Kill,
+}
+
+/// Runs a [`Sys`] on a background thread and controls it from others.
+///
+/// Commands interrupt the machine through its [`StopFlag`], so they take
+/// effect between two instructions even while a long program is running.
+/// Dropping the worker stops the machine and joins the thread.
+pub struct Worker {
+  commands: Sender<Command>,
+  events: Receiver<Event>,
+  stop: StopFlag,
+  thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+  /// Creates a machine with `build` on a new thread and starts running it.
+  /// The machine is built there because its I/O streams need not be
+  /// [`Send`].
+  pub fn spawn<F>(build: F) -> Worker
+  where
+    F: 'static + FnOnce() -> Sys + Send,
+  {
+    let (commands, received) = mpsc::channel();
+    let (sent, events) = mpsc::channel();
+    let stop = StopFlag::new();
+
+    let flag = stop.clone();
+    let thread = thread::spawn(move || {
+      let mut sys = build();
+      sys.set_stop_flag(flag);
+      work(sys, received, sent);
+    });
+
+    Worker {
+      commands,
+      events,
+      stop,
+      thread: Some(thread),
+    }
+  }
+
+  fn send(&self, command: Command) {
+    if self.commands.send(command).is_ok() {
+      self.stop.request();
+    }
+  }
+
+  /// Stops the machine, reporting [`StopReason::Interrupted`]. Has no
+  /// effect if it is not running.
+  pub fn pause(&self) {
+    self.send(Command::Pause);
+  }
+
+  /// Continues running a stopped machine, unless its program has exited.
+  pub fn resume(&self) {
+    self.send(Command::Resume);
+  }
+
+  /// Runs `f` on the machine between two instructions and returns its
+  /// result, leaving the machine running if it was. `None` if the worker
+  /// thread has gone.
+  pub fn inspect<T, F>(&self, f: F) -> Option<T>
+  where
+    T: 'static + Send,
+    F: 'static + FnOnce(&mut Sys) -> T + Send,
+  {
+    let (sent, result) = mpsc::channel();
+    self.send(Command::Inspect(Box::new(move |sys| {
+      let _ = sent.send(f(sys));
+    })));
+    result.recv().ok()
+  }
+
+  /// Events in the order they happened. Only stopping execution produces
+  /// one; pausing a machine that is not running does not.
+  pub fn events(&self) -> &Receiver<Event> {
+    &self.events
+  }
+
+  /// Stops the machine and waits for the worker thread to finish.
+  pub fn kill(mut self) {
+    self.shutdown();
+  }
+
+  fn shutdown(&mut self) {
+    self.send(Command::Kill);
+    if let Some(thread) = self.thread.take() {
+      let _ = thread.join();
+    }
+  }
+}
+
+impl Drop for Worker {
+  fn drop(&mut self) {
+    self.shutdown();
+  }
+}
+
+fn work(mut sys: Sys, commands: Receiver<Command>, events: Sender<Event>) {
+  let mut running = true;
+  let mut exited = false;
+
+  loop {
+    /* Service commands before running on, and wait for them when stopped */
+    let command = match running {
+      true => match commands.try_recv() {
+        Ok(command) => Some(command),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => return,
+      },
+      false => match commands.recv() {
+        Ok(command) => Some(command),
+        Err(_) => return,
+      },
+    };
+
+    let event = match command {
+      Some(Command::Pause) if running => {
+        sys.stop_clock();
+        Event::Stopped(StopReason::Interrupted)
+      }
+      Some(Command::Pause) => continue,
+      Some(Command::Resume) => {
+        running = !exited;
+        continue;
+      }
+      Some(Command::Inspect(f)) => {
+        f(&mut sys);
+        continue;
+      }
+      Some(Command::Kill) => return,
+      None => match sys.cont_serviced() {
+        /* Interrupted to service a command */
+        Ok(StopReason::Interrupted) => continue,
+        Ok(reason) => {
+          exited = matches!(reason, StopReason::Exited(_));
+          Event::Stopped(reason)
+        }
+        Err(e) => Event::Faulted(e),
+      },
+    };
+
+    running = false;
+    let _ = events.send(event);
+  }
+}
@@ -1,36 +1,163 @@
-use crate::{interrupt_exception, interrupt_software};
+use std::collections::VecDeque;
 
-use super::{arch::Register, bus::Bus, interrupt::*, virt::MemMap};
+use crate::interrupt_exception;
 
-#[allow(dead_code)]
-fn sign_ext(value: u32, from: usize) -> i32 {
+use super::{
+  arch::{Endian, Isa, Register},
+  bus::Bus,
+  decode,
+  interrupt::*,
+  virt::{MemMap, MemRegion, Perm},
+  watch::WatchHit,
+};
+
+pub(crate) fn sign_ext(value: u32, from: usize) -> i32 {
   let sign = value & (1 << (from - 1));
   if sign != 0 {
-    (value | (0xffff_ffff << (32 - from))) as i32
+    (value | (0xffff_ffff << from)) as i32
   } else {
     value as i32
   }
 }
 
+/// A read of a register whose load has not landed yet (MIPS I only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadHazard {
+  pub pc: u32,
+  pub reg: usize,
+}
+
+/// Load hazards seen while running, recorded only once `enabled`. Keeps
+/// the most recent `capacity` entries and drops older ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HazardLog {
+  pub enabled: bool,
+  pub capacity: usize,
+  entries: VecDeque<LoadHazard>,
+}
+
+impl Default for HazardLog {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      capacity: HazardLog::DEFAULT_CAPACITY,
+      entries: VecDeque::new(),
+    }
+  }
+}
+
+impl HazardLog {
+  pub const DEFAULT_CAPACITY: usize = 1024;
+
+  pub fn record(&mut self, hazard: LoadHazard) {
+    if !self.enabled || self.capacity == 0 {
+      return;
+    }
+    if self.entries.len() >= self.capacity {
+      self.entries.pop_front();
+    }
+    self.entries.push_back(hazard);
+  }
+
+  /// Recorded hazards, oldest first.
+  pub fn iter(&self) -> impl Iterator<Item = &LoadHazard> {
+    self.entries.iter()
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Removes and returns the recorded hazards.
+  pub fn take(&mut self) -> Vec<LoadHazard> {
+    self.entries.drain(..).collect()
+  }
+}
+
+/// What happened when the CPU executed one instruction.
+#[derive(Debug, Clone)]
+pub enum StepOutcome {
+  /// The instruction completed normally
+  Retired,
+  /// A `syscall` is waiting to be serviced; the PC is past it
+  Syscall,
+  /// A `break` instruction with its code field
+  Break(u32),
+  /// A trap instruction's condition held
+  Trap,
+  /// The instruction at `pc` raised an exception and did not complete
+  Exception {
+    kind: ExceptionInterrupt,
+    pc: u32,
+    badvaddr: u32,
+  },
+  /// A pausing watchpoint was triggered by a completed access
+  Watch(WatchHit),
+  /// The instruction at `pc` was abandoned on an interrupt that is not an
+  /// exception, such as a request to stop, and left no trace in CP0
+  Interrupted { err: Interrupt, pc: u32 },
+  /// Execution reached the end of the program or the halt address
+  Halted,
+}
+
 #[derive(Debug, Clone)]
 pub struct Cpu {
+  pub id: usize,
   pub regs: [u32; 32],
-  pub pc: usize,
+  pub pc: u32,
   pub tmp: u32,
   pub hi: u32,
   pub lo: u32,
+  /// Address of the last memory access that raised an exception.
+  pub badvaddr: u32,
+  /// Address of the last instruction to raise an exception; `eret`
+  /// returns here.
+  pub epc: u32,
   pub bus: Bus,
+  pub isa: Isa,
+  pub delay_slots: bool,
+  pub load_delay: bool,
+  pub load_hazards: HazardLog,
+  /// End of the loaded program; reaching it, or any address past it that
+  /// no mapping claims, halts.
+  pub text_end: Option<u32>,
+  /// Halts when the PC reaches this address.
+  pub halt_addr: Option<u32>,
+  pub(crate) forbidden_slot: bool,
+  pub(crate) delayed_load: Option<(usize, u32)>,
+  /// The load landing at the end of the current instruction.
+  landing: Option<(usize, u32)>,
+  pub(crate) branch_target: Option<u32>,
 }
 
 impl Default for Cpu {
   fn default() -> Self {
     Self {
+      id: 0,
       regs: [0u32; 32],
-      pc: 0,
+      pc: MemMap::HIGHMEM.base,
       tmp: 0,
       hi: 0,
       lo: 0,
+      badvaddr: 0,
+      epc: 0,
       bus: Bus::default(),
+      isa: Isa::default(),
+      delay_slots: false,
+      load_delay: false,
+      load_hazards: HazardLog::default(),
+      text_end: None,
+      halt_addr: None,
+      forbidden_slot: false,
+      delayed_load: None,
+      landing: None,
+      branch_target: None,
     }
   }
 }
@@ -40,31 +167,201 @@ impl Cpu {
     Self::default()
   }
 
+  /// Loads a raw image at the base of DRAM and maps it read/execute, like
+  /// the `.text` of an assembled program.
   pub fn load(&mut self, code: Vec<u8>) -> Result<()> {
-    self.bus.dram_splice(0, code)
+    let text = MemRegion {
+      base: MemMap::HIGHMEM.base,
+      size: code.len() as u32,
+    };
+    self.bus.dram_splice(0, code)?;
+    self.bus.map(".text", text, Perm::RX);
+    self.text_end = Some(text.end());
+    Ok(())
   }
 
-  pub fn step(&mut self) -> Result<bool> {
-    if self.pc < self.bus.dram.size() as usize {
-      let inst = self.fetch()?;
-      if inst == 0 {
-        Ok(true)
-      } else {
-        self.pc += 4;
-        self.execute(inst)?;
-        Ok(false)
+  /// Whether execution stops before the instruction at `addr`. Besides
+  /// the halt address, this is any aligned address at or past the end of
+  /// the text that is not mapped for something else, so a branch-likely
+  /// that skips its slot at the end of the program halts too. Fetching
+  /// from a misaligned PC still raises an address error.
+  pub fn halts_at(&self, addr: u32) -> bool {
+    let past_text = |end| addr == end || (addr > end && self.bus.mapping(addr).is_none());
+    self.halt_addr == Some(addr) || (addr.is_multiple_of(4) && self.text_end.is_some_and(past_text))
+  }
+
+  pub fn step(&mut self) -> StepOutcome {
+    let pc = self.pc;
+    if self.halts_at(pc) {
+      return StepOutcome::Halted;
+    }
+
+    let inst = match self.fetch() {
+      Ok(inst) => inst,
+      Err(err) => return self.fault(pc, err),
+    };
+
+    let delayed = self.delayed_load.take();
+    let target = self.branch_target.take();
+    if let Some((reg, _)) = delayed {
+      if reg != Register::ZERO && Cpu::reads_early(inst, reg) {
+        self.load_hazards.record(LoadHazard { pc: self.pc, reg });
+      }
+    }
+
+    self.pc = self.pc.wrapping_add(4);
+    self.landing = delayed;
+    let res = self.execute(inst);
+    self.landing = None;
+    let watched = self.bus.take_watch_hit(pc);
+
+    /* A write to the same register in the delay slot cancels the load */
+    if let Some((reg, value)) = delayed {
+      if res.is_err() || decode::dest_reg(inst) != Some(reg) {
+        self.regs[reg] = value;
       }
-    } else {
-      Ok(true)
+    }
+
+    /* This instruction was in a branch delay slot */
+    if let (Some(target), Ok(_)) = (target, &res) {
+      self.pc = target;
+    }
+
+    /* $zero is hardwired; discard anything written to it */
+    self.regs[Register::ZERO] = 0;
+
+    match (res, watched) {
+      (Err(err), _) => {
+        /* Keep a pending branch so the slot can be re-run and still jump */
+        self.pc = pc;
+        self.branch_target = target;
+        self.fault(pc, err)
+      }
+
+      /* The access has completed; pause before the next instruction */
+      (Ok(StepOutcome::Retired), Some(hit)) => StepOutcome::Watch(hit),
+      (Ok(outcome), _) => outcome,
+    }
+  }
+
+  /// Whether `inst` reads `reg` while a load to it is still in flight.
+  /// LWL and LWR merge with a load in flight to their own target, so only
+  /// their base register counts.
+  fn reads_early(inst: u32, reg: usize) -> bool {
+    let [base, rt] = decode::src_regs(inst);
+    match (inst >> 26) & 0x3f {
+      0x22 | 0x26 => base == Some(reg),
+      _ => base == Some(reg) || rt == Some(reg),
     }
   }
 
   fn fetch(&self) -> Result<u32> {
-    let addr = (self.pc as u32) + MemMap::HIGHMEM.base;
-    self.bus.load(addr, 32)
+    self.bus.fetch(self.pc)
+  }
+
+  /// Records the architectural side effects of the instruction at `pc`
+  /// raising `err`. Only exceptions have any; other interrupts are passed
+  /// on untouched.
+  fn fault(&mut self, pc: u32, err: Interrupt) -> StepOutcome {
+    let Interrupt::Exception(kind) = err else {
+      return StepOutcome::Interrupted { err, pc };
+    };
+
+    if let Some(addr) = kind.addr() {
+      self.badvaddr = addr;
+    }
+    self.epc = pc;
+
+    StepOutcome::Exception {
+      kind,
+      pc,
+      badvaddr: self.badvaddr,
+    }
+  }
+
+  /// Condition of a trap instruction, identified by its SPECIAL function
+  /// code; the REGIMM immediate forms map onto the same codes.
+  fn trap_cond(funct: u32, a: u32, b: u32) -> bool {
+    match funct {
+      0x30 => a as i32 >= b as i32,
+      0x31 => a >= b,
+      0x32 => (a as i32) < b as i32,
+      0x33 => a < b,
+      0x34 => a == b,
+      _ => a != b,
+    }
   }
 
-  fn execute(&mut self, inst: u32) -> Result<()> {
+  /// Address written to the link register by jump-and-link instructions.
+  fn link_addr(&self) -> u32 {
+    if self.delay_slots {
+      self.pc.wrapping_add(4)
+    } else {
+      self.pc
+    }
+  }
+
+  /// Transfers control to `target`, after the delay slot when delay slots
+  /// are modelled.
+  fn branch(&mut self, target: u32) {
+    if self.delay_slots {
+      self.branch_target = Some(target);
+    } else {
+      self.pc = target;
+    }
+  }
+
+  fn branch_if(&mut self, cond: bool, inst: u32) {
+    if cond {
+      let imm = sign_ext((inst & 0xffff) << 2, 18);
+      self.branch(self.pc.wrapping_add_signed(imm));
+    }
+  }
+
+  /// Branch-likely instructions annul the delay slot when not taken. Without
+  /// delay slots there is nothing to annul and they fall through.
+  fn branch_likely(&mut self, cond: bool, inst: u32) {
+    if cond {
+      self.branch_if(true, inst);
+    } else if self.delay_slots {
+      self.pc = self.pc.wrapping_add(4);
+    }
+  }
+
+  /// Number of bytes an unaligned access at `addr` shifts by: the byte
+  /// offset for the "left" half on big-endian, mirrored otherwise.
+  fn unaligned_offset(&self, addr: u32, left: bool) -> u32 {
+    let offset = addr & 3;
+    match (self.bus.dram.endian, left) {
+      (Endian::Big, true) | (Endian::Little, false) => offset,
+      _ => 3 - offset,
+    }
+  }
+
+  /// The value LWL and LWR merge into: a load in flight to `reg` if there
+  /// is one, as the pipeline forwards it, or else the register itself.
+  fn merge_base(&self, reg: usize) -> u32 {
+    match self.landing {
+      Some((landing, value)) if landing == reg => value,
+      _ => self.regs[reg],
+    }
+  }
+
+  /// Writes the result of a load, deferring it by one instruction when the
+  /// MIPS I load-delay model is enabled.
+  fn write_loaded(&mut self, reg: usize, value: u32) {
+    if self.load_delay {
+      self.delayed_load = Some((reg, value));
+    } else {
+      self.regs[reg] = value;
+    }
+  }
+
+  fn execute(&mut self, inst: u32) -> Result<StepOutcome> {
+    if self.isa == Isa::Mips32R6 && self.execute_r6(inst)? {
+      return Ok(StepOutcome::Retired);
+    }
+
     let r = &mut self.regs;
     let opcode = (inst >> 26) & 0x3f;
     let rs = ((inst >> 21) & 0x1f) as usize;
@@ -88,10 +385,10 @@ impl Cpu {
           0x03 => r[rd] = ((r[rt] as i32) >> shamt) as u32,
 
           /* SLLV $rd, $rt, $rs*/
-          0x04 => r[rd] = r[rt] << r[rs],
+          0x04 => r[rd] = r[rt] << (r[rs] & 0x1f),
 
           /* SRLV $rd, $rt, $rs */
-          0x06 => r[rd] = r[rt] >> r[rs],
+          0x06 => r[rd] = r[rt] >> (r[rs] & 0x1f),
 
           /* SRAV $rd, $rt, $rs */
           0x07 => r[rd] = ((r[rt] as i32) >> (r[rs] & 0x1f)) as u32,
@@ -99,10 +396,7 @@ impl Cpu {
           /* JR $rs */
           0x08 => {
             let addr = r[rs];
-            if addr % 4 != 0 {
-              interrupt_exception!(ALIGNMENT(addr))
-            }
-            self.pc = addr as usize;
+            self.branch(addr);
           }
 
           /* JALR $rd, $rs */
@@ -111,16 +405,15 @@ impl Cpu {
               interrupt_exception!(UNDEFINED)
             }
             let addr = r[rs];
-            if addr % 4 != 0 {
-              interrupt_exception!(ALIGNMENT(addr))
-            }
-            self.tmp = addr;
-            r[rd] = (self.pc + 8) as u32;
-            self.pc = self.tmp as usize;
+            self.regs[rd] = self.link_addr();
+            self.branch(addr);
           }
 
           /* SYSCALL */
-          0x0C => interrupt_software!(SYSCALL),
+          0x0C => return Ok(StepOutcome::Syscall),
+
+          /* BREAK code */
+          0x0D => return Ok(StepOutcome::Break((inst >> 6) & 0xfffff)),
 
           /* MFHI $rd */
           0x10 => r[rd] = self.hi,
@@ -156,16 +449,20 @@ impl Cpu {
           0x1A => {
             let a = r[rs] as i32 as i64;
             let b = r[rt] as i32 as i64;
-            self.lo = (a / b) as u32;
-            self.hi = (a % b) as u32
+            /* Division by zero leaves hi/lo unpredictable; keep them */
+            if b != 0 {
+              self.lo = (a / b) as u32;
+              self.hi = (a % b) as u32
+            }
           }
 
           /* DIVU $rs, $rt */
           0x1B => {
-            let a = r[rs] as u64;
-            let b = r[rt] as u64;
-            self.lo = (a / b) as u32;
-            self.hi = (a % b) as u32
+            let (a, b) = (r[rs], r[rt]);
+            if let Some(quotient) = a.checked_div(b) {
+              self.lo = quotient;
+              self.hi = a % b;
+            }
           }
 
           /* ADD $rd, $rs, $rt */
@@ -179,7 +476,7 @@ impl Cpu {
           }
 
           /* ADDU $rd, $rs, $rt */
-          0x21 => r[rd] = r[rs] + r[rt],
+          0x21 => r[rd] = r[rs].wrapping_add(r[rt]),
 
           /* SUB $rd, $rs, $rt */
           0x22 => {
@@ -192,7 +489,7 @@ impl Cpu {
           }
 
           /* SUBU $rd, $rs, $rt */
-          0x23 => r[rd] = r[rs] - r[rt],
+          0x23 => r[rd] = r[rs].wrapping_sub(r[rt]),
 
           /* AND $rd, $rs, $rt */
           0x24 => r[rd] = r[rs] & r[rt],
@@ -216,6 +513,65 @@ impl Cpu {
           /* SLTU $rd, $rs, $rt */
           0x2B => r[rd] = if r[rs] < r[rt] { 1 } else { 0 },
 
+          /* TGE, TGEU, TLT, TLTU, TEQ, TNE $rs, $rt */
+          0x30..=0x34 | 0x36 => {
+            if Cpu::trap_cond(funct, r[rs], r[rt]) {
+              return Ok(StepOutcome::Trap);
+            }
+          }
+
+          _ => interrupt_exception!(UNSUPPORTED(inst)),
+        }
+      }
+
+      /* ----- REGIMM Instructions ----- */
+      0x01 => {
+        let a = r[rs] as i32;
+        match rt {
+          /* BLTZ $rs, imm */
+          0x00 => self.branch_if(a < 0, inst),
+
+          /* BGEZ $rs, imm */
+          0x01 => self.branch_if(a >= 0, inst),
+
+          /* BLTZL $rs, imm */
+          0x02 => self.branch_likely(a < 0, inst),
+
+          /* BGEZL $rs, imm */
+          0x03 => self.branch_likely(a >= 0, inst),
+
+          /* TGEI, TGEIU, TLTI, TLTIU, TEQI, TNEI $rs, imm */
+          0x08..=0x0C | 0x0E => {
+            let imm = sign_ext(inst & 0xffff, 16) as u32;
+            if Cpu::trap_cond(rt as u32 + 0x28, r[rs], imm) {
+              return Ok(StepOutcome::Trap);
+            }
+          }
+
+          /* BLTZAL $rs, imm */
+          0x10 => {
+            self.regs[Register::RA] = self.link_addr();
+            self.branch_if(a < 0, inst);
+          }
+
+          /* BGEZAL $rs, imm */
+          0x11 => {
+            self.regs[Register::RA] = self.link_addr();
+            self.branch_if(a >= 0, inst);
+          }
+
+          /* BLTZALL $rs, imm */
+          0x12 => {
+            self.regs[Register::RA] = self.link_addr();
+            self.branch_likely(a < 0, inst);
+          }
+
+          /* BGEZALL $rs, imm */
+          0x13 => {
+            self.regs[Register::RA] = self.link_addr();
+            self.branch_likely(a >= 0, inst);
+          }
+
           _ => interrupt_exception!(UNSUPPORTED(inst)),
         }
       }
@@ -225,61 +581,77 @@ impl Cpu {
       /* J address */
       0x02 => {
         let target = inst & 0x3ffffff;
-        self.pc = (((self.pc + 4) as u32 & 0xf0000000) | (target << 2)) as usize;
+        self.branch((self.pc & 0xf0000000) | (target << 2));
       }
 
       /* JAL address */
       0x03 => {
-        r[Register::RA] = (self.pc + 8) as u32;
+        self.regs[Register::RA] = self.link_addr();
         let target = inst & 0x3ffffff;
-        self.pc = (((self.pc + 4) as u32 & 0xf0000000) | (target << 2)) as usize;
+        self.branch((self.pc & 0xf0000000) | (target << 2));
+      }
+
+      /* ----- Coprocessor 0 ----- */
+
+      /* ERET */
+      0x10 if inst == 0x4200_0018 => {
+        /* Returning from an exception breaks any load-linked reservation */
+        self.bus.monitor.unlink(self.id);
+        self.pc = self.epc;
       }
 
       /* ----- I-Type Instructions ----- */
 
       /* BEQ $rs, $rt, imm */
       0x04 => {
-        if r[rs] == r[rt] {
-          let imm = sign_ext((inst & 0xffff) << 2, 18);
-          self.pc = ((self.pc as i32 + 4) + imm) as usize;
-        }
+        let cond = r[rs] == r[rt];
+        self.branch_if(cond, inst);
       }
 
       /* BNE $rs, $rt, imm */
       0x05 => {
-        if r[rs] != r[rt] {
-          let imm = sign_ext((inst & 0xffff) << 2, 18);
-          self.pc = ((self.pc as i32 + 4) + imm) as usize;
-        }
+        let cond = r[rs] != r[rt];
+        self.branch_if(cond, inst);
       }
 
       /* BLEZ $rs, imm */
       0x06 => {
-        let a = r[rs] as i32;
-        if a <= 0 {
-          let imm = sign_ext((inst & 0xffff) << 2, 18);
-          self.pc = ((self.pc as i32 + 4) + imm) as usize;
-        }
+        let cond = r[rs] as i32 <= 0;
+        self.branch_if(cond, inst);
       }
 
       /* BGTZ $rs, imm */
       0x07 => {
-        let a = r[rs] as i32;
-        if a > 0 {
-          let imm = sign_ext((inst & 0xffff) << 2, 18);
-          self.pc = ((self.pc as i32 + 4) + imm) as usize;
-        }
+        let cond = r[rs] as i32 > 0;
+        self.branch_if(cond, inst);
       }
 
-      /* ADDI $rt, $rs, imm */
-      0x08 => {
-        let a = r[rs] as i32;
-        let imm = sign_ext(inst & 0xffff, 16);
-        r[rt] = (a + imm) as u32;
+      /* BEQL $rs, $rt, imm */
+      0x14 => {
+        let cond = r[rs] == r[rt];
+        self.branch_likely(cond, inst);
       }
 
-      /* ADDIU $rt, $rs, imm */
-      0x09 => {
+      /* BNEL $rs, $rt, imm */
+      0x15 => {
+        let cond = r[rs] != r[rt];
+        self.branch_likely(cond, inst);
+      }
+
+      /* BLEZL $rs, imm */
+      0x16 => {
+        let cond = r[rs] as i32 <= 0;
+        self.branch_likely(cond, inst);
+      }
+
+      /* BGTZL $rs, imm */
+      0x17 => {
+        let cond = r[rs] as i32 > 0;
+        self.branch_likely(cond, inst);
+      }
+
+      /* ADDI $rt, $rs, imm */
+      0x08 => {
         let a = r[rs] as i32;
         let imm = sign_ext(inst & 0xffff, 16);
         match a.checked_add(imm) {
@@ -288,6 +660,9 @@ impl Cpu {
         }
       }
 
+      /* ADDIU $rt, $rs, imm */
+      0x09 => r[rt] = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16)),
+
       /* SLTI $rt, $rs, imm */
       0x0A => {
         let a = r[rs] as i32;
@@ -298,7 +673,7 @@ impl Cpu {
       /* SLTIU $rt, $rs, imm */
       0x0B => {
         let a = r[rs];
-        let imm = inst & 0xffff;
+        let imm = sign_ext(inst & 0xffff, 16) as u32;
         r[rt] = if a < imm { 1 } else { 0 };
       }
 
@@ -316,63 +691,117 @@ impl Cpu {
 
       /* LB $rt, imm($rs) */
       0x20 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
-        let byte = self.bus.load(addr, 8)?;
-        r[rt] = sign_ext(byte, 8) as u32;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let byte = self.bus.read(addr, 8)?;
+        self.write_loaded(rt, sign_ext(byte, 8) as u32);
       }
 
       /* LH $rt, imm($rs) */
       0x21 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
-        let half = self.bus.load(addr, 16)?;
-        r[rt] = sign_ext(half, 16) as u32;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let half = self.bus.read(addr, 16)?;
+        self.write_loaded(rt, sign_ext(half, 16) as u32);
       }
 
-      /* LW $rt, imm($rs) */
+      /* LWL $rt, imm($rs) */
       0x22 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
-        let word = self.bus.load(addr, 32)?;
-        r[rt] = word;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let word = self.bus.read(addr & !3, 32)?;
+        let shift = 8 * self.unaligned_offset(addr, true);
+        let kept = self.merge_base(rt) & ((1u64 << shift) - 1) as u32;
+        self.write_loaded(rt, (word << shift) | kept);
+      }
+
+      /* LW $rt, imm($rs) */
+      0x23 => {
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let word = self.bus.read(addr, 32)?;
+        self.write_loaded(rt, word);
       }
 
       /* LBU $rt, imm($rs) */
       0x24 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
-        let byte = self.bus.load(addr, 8)?;
-        r[rt] = byte & 0xff;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let byte = self.bus.read(addr, 8)?;
+        self.write_loaded(rt, byte & 0xff);
       }
 
       /* LHU $rt, imm($rs) */
       0x25 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
-        let half = self.bus.load(addr, 16)?;
-        r[rt] = half & 0xffff;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let half = self.bus.read(addr, 16)?;
+        self.write_loaded(rt, half & 0xffff);
+      }
+
+      /* LWR $rt, imm($rs) */
+      0x26 => {
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let word = self.bus.read(addr & !3, 32)?;
+        let shift = 8 * self.unaligned_offset(addr, false);
+        let kept = self.merge_base(rt) & !(u32::MAX >> shift);
+        self.write_loaded(rt, (word >> shift) | kept);
       }
 
       /* SB $rt, imm($rs) */
       0x28 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
         let byte = r[rt] & 0xff;
         self.bus.store(addr, 8, byte)?;
       }
 
       /* SH $rt, imm($rs) */
       0x29 => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
         let half = r[rt] & 0xffff;
         self.bus.store(addr, 16, half)?;
       }
 
+      /* SWL $rt, imm($rs) */
+      0x2A => {
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let word = self.bus.load(addr & !3, 32)?;
+        let shift = 8 * self.unaligned_offset(addr, true);
+        let kept = word & !(u32::MAX >> shift);
+        self
+          .bus
+          .store(addr & !3, 32, (self.regs[rt] >> shift) | kept)?;
+      }
+
       /* SW $rt, imm($rs) */
       0x2B => {
-        let addr = ((r[rs] as i32) + sign_ext(inst & 0xffff, 16)) as u32;
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
         let word = r[rt];
         self.bus.store(addr, 32, word)?;
       }
 
+      /* SWR $rt, imm($rs) */
+      0x2E => {
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let word = self.bus.load(addr & !3, 32)?;
+        let shift = 8 * self.unaligned_offset(addr, false);
+        let kept = word & ((1u64 << shift) - 1) as u32;
+        self
+          .bus
+          .store(addr & !3, 32, (self.regs[rt] << shift) | kept)?;
+      }
+
+      /* LL $rt, imm($rs) */
+      0x30 => {
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let word = self.bus.load_linked(self.id, addr)?;
+        self.write_loaded(rt, word);
+      }
+
+      /* SC $rt, imm($rs) */
+      0x38 => {
+        let addr = r[rs].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        let stored = self.bus.store_conditional(self.id, addr, r[rt])?;
+        self.regs[rt] = stored as u32;
+      }
+
       _ => interrupt_exception!(UNSUPPORTED(inst)),
     }
 
-    Ok(())
+    Ok(StepOutcome::Retired)
   }
 }
@@ -0,0 +1,191 @@
+use super::{
+  arch::Register,
+  decode,
+  interrupt::{Interrupt, Result, SoftwareInterrupt},
+  limits::LimitExceeded,
+  sys::Sys,
+  watch::WatchHit,
+};
+
+/// Why execution stopped and control returned to the debugger.
+///
+/// Exceptions and other faults are not stop reasons: the methods that run
+/// the machine return them as `Err(Interrupt)`, with `badvaddr` and `epc`
+/// describing the fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+  /// The requested step completed
+  Step,
+  /// The next instruction is at a breakpoint
+  Breakpoint(u32),
+  /// [`Sys::run_until`] reached its target address
+  Reached(u32),
+  /// A pausing watchpoint was triggered by the last instruction
+  Watchpoint(WatchHit),
+  /// The program finished with the given exit code
+  Exited(i32),
+  /// Execution reached one of the machine's limits
+  LimitExceeded(LimitExceeded),
+  /// Another thread requested a stop through the machine's [`StopFlag`]
+  ///
+  /// [`StopFlag`]: super::control::StopFlag
+  Interrupted,
+}
+
+impl Sys {
+  /// Stops before executing the instruction at `addr`. Returns `false` if
+  /// a breakpoint was already set there.
+  pub fn set_breakpoint(&mut self, addr: u32) -> bool {
+    self.breakpoints.insert(addr)
+  }
+
+  /// Returns `false` if no breakpoint was set at `addr`.
+  pub fn clear_breakpoint(&mut self, addr: u32) -> bool {
+    self.breakpoints.remove(&addr)
+  }
+
+  pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+    self.breakpoints.iter().copied()
+  }
+
+  /// Executes a single instruction.
+  pub fn step(&mut self) -> Result<StopReason> {
+    self.resume(|_| Some(StopReason::Step))
+  }
+
+  /// Executes a single instruction, running a call to completion as if it
+  /// were one step.
+  pub fn step_over(&mut self) -> Result<StopReason> {
+    let link = self
+      .cpu
+      .bus
+      .load(self.cpu.pc, 32)
+      .ok()
+      .and_then(|inst| decode::link_reg(inst, self.cpu.isa));
+    let Some(link) = link else {
+      return self.step();
+    };
+
+    let sp = self.cpu.regs[Register::SP];
+    let reason = self.step()?;
+    let ret = self.cpu.regs[link];
+    if reason != StopReason::Step || self.cpu.pc == ret {
+      return Ok(reason);
+    }
+
+    self.resume(|sys| sys.returned_to(ret, sp))
+  }
+
+  /// Runs until the current function returns to `$ra`.
+  pub fn step_out(&mut self) -> Result<StopReason> {
+    let ret = self.cpu.regs[Register::RA];
+    let sp = self.cpu.regs[Register::SP];
+    self.resume(|sys| sys.returned_to(ret, sp))
+  }
+
+  /// Runs until a breakpoint, watchpoint or the end of the program.
+  pub fn cont(&mut self) -> Result<StopReason> {
+    self.resume(|_| None)
+  }
+
+  /// Like [`Sys::cont`], but hands control back with [`StopReason::Step`]
+  /// after at most `limit` instructions so the caller can poll for input.
+  ///
+  /// Calling it again after such a poll carries on with the same run: the
+  /// clock for the time limit keeps going rather than starting over. A
+  /// caller that stops polling without continuing, say because the user
+  /// interrupted the program, ends the run with [`Sys::stop_clock`].
+  pub fn cont_for(&mut self, limit: u64) -> Result<StopReason> {
+    self.cont_until(limit, |_| false)
+  }
+
+  /// Like [`Sys::cont_for`], but also hands control back early once
+  /// `pause` holds before the next instruction, say because a front end
+  /// has console input to collect first.
+  pub fn cont_until<F>(&mut self, limit: u64, mut pause: F) -> Result<StopReason>
+  where
+    F: FnMut(&Sys) -> bool,
+  {
+    let mut executed = 0;
+    let stop = |sys: &Sys| {
+      executed += 1;
+      let poll = executed >= limit || pause(sys);
+      /* A breakpoint on the next instruction reports itself instead */
+      (poll && !sys.breakpoints.contains(&sys.cpu.pc)).then_some(StopReason::Step)
+    };
+    self.resume_with(stop, |reason| *reason == StopReason::Step)
+  }
+
+  /// Like [`Sys::cont`], but keeps the clock for the time limit running
+  /// when another thread interrupts it through the [`StopFlag`], so a
+  /// [`Worker`] can service commands without extending the run.
+  ///
+  /// [`StopFlag`]: super::control::StopFlag
+  /// [`Worker`]: super::control::Worker
+  pub(crate) fn cont_serviced(&mut self) -> Result<StopReason> {
+    self.resume_with(|_| None, |reason| *reason == StopReason::Interrupted)
+  }
+
+  /// Runs until the instruction at `addr` is next, stopping early at
+  /// breakpoints.
+  pub fn run_until(&mut self, addr: u32) -> Result<StopReason> {
+    self.resume(|sys| (sys.cpu.pc == addr).then_some(StopReason::Reached(addr)))
+  }
+
+  /// Whether control is back at `ret` in a frame no deeper than the one
+  /// that held `sp`, so recursive calls do not stop early.
+  fn returned_to(&self, ret: u32, sp: u32) -> Option<StopReason> {
+    (self.cpu.pc == ret && self.cpu.regs[Register::SP] >= sp).then_some(StopReason::Step)
+  }
+
+  /// Executes instructions until `stop` gives a reason or a breakpoint is
+  /// reached. The first instruction always executes, so resuming from a
+  /// breakpoint makes progress.
+  fn resume<F>(&mut self, stop: F) -> Result<StopReason>
+  where
+    F: FnMut(&Sys) -> Option<StopReason>,
+  {
+    self.resume_with(stop, |_| false)
+  }
+
+  /// Like [`Sys::resume`], but a stop that `keep_clock` accepts does not
+  /// end the run, so the next call goes on against the same deadline.
+  fn resume_with<F, K>(&mut self, mut stop: F, keep_clock: K) -> Result<StopReason>
+  where
+    F: FnMut(&Sys) -> Option<StopReason>,
+    K: FnOnce(&StopReason) -> bool,
+  {
+    if !self.clock_running() {
+      self.start_clock();
+    }
+
+    let res = loop {
+      match self.tick() {
+        Ok(true) => break Ok(StopReason::Exited(self.exit_code.unwrap_or(0))),
+        Ok(false) => {}
+        Err(Interrupt::Software(SoftwareInterrupt::WATCH(hit))) => {
+          break Ok(StopReason::Watchpoint(hit))
+        }
+        Err(Interrupt::Software(SoftwareInterrupt::LIMIT(limit))) => {
+          break Ok(StopReason::LimitExceeded(limit))
+        }
+        Err(Interrupt::Software(SoftwareInterrupt::STOPPED(_))) => {
+          break Ok(StopReason::Interrupted)
+        }
+        Err(err) => break Err(err),
+      }
+
+      if let Some(reason) = stop(self) {
+        break Ok(reason);
+      }
+      if self.breakpoints.contains(&self.cpu.pc) {
+        break Ok(StopReason::Breakpoint(self.cpu.pc));
+      }
+    };
+
+    if !matches!(&res, Ok(reason) if keep_clock(reason)) {
+      self.stop_clock();
+    }
+    res
+  }
+}
@@ -0,0 +1,66 @@
+use super::arch::Isa;
+
+/// Returns the general-purpose register written by `inst`, if any.
+pub fn dest_reg(inst: u32) -> Option<usize> {
+  let opcode = (inst >> 26) & 0x3f;
+  let rt = ((inst >> 16) & 0x1f) as usize;
+  let rd = ((inst >> 11) & 0x1f) as usize;
+
+  match opcode {
+    0x00 => match inst & 0x3f {
+      0x00..=0x07 | 0x09 | 0x10 | 0x12 | 0x20..=0x2B => Some(rd),
+      _ => None,
+    },
+    0x01 => match rt {
+      0x10..=0x13 => Some(31),
+      _ => None,
+    },
+    0x03 => Some(31),
+    0x08..=0x0F | 0x20..=0x26 | 0x30 | 0x38 => Some(rt),
+    _ => None,
+  }
+}
+
+/// Returns the general-purpose registers read by `inst`.
+pub fn src_regs(inst: u32) -> [Option<usize>; 2] {
+  let opcode = (inst >> 26) & 0x3f;
+  let rs = ((inst >> 21) & 0x1f) as usize;
+  let rt = ((inst >> 16) & 0x1f) as usize;
+
+  match opcode {
+    0x00 => match inst & 0x3f {
+      0x00..=0x03 => [Some(rt), None],
+      0x08 | 0x09 | 0x11 | 0x13 => [Some(rs), None],
+      0x04..=0x07 | 0x18..=0x1B | 0x20..=0x2B | 0x30..=0x36 => [Some(rs), Some(rt)],
+      _ => [None, None],
+    },
+    0x01 | 0x06 | 0x07 | 0x16 | 0x17 => [Some(rs), None],
+    0x04 | 0x05 | 0x14 | 0x15 => [Some(rs), Some(rt)],
+    0x08..=0x0E | 0x20..=0x21 | 0x23..=0x25 | 0x30 => [Some(rs), None],
+    0x22 | 0x26 | 0x28..=0x2E | 0x38 => [Some(rs), Some(rt)],
+    _ => [None, None],
+  }
+}
+
+/// Returns the register a call instruction writes its return address to,
+/// or `None` if `inst` is not a call.
+pub fn link_reg(inst: u32, isa: Isa) -> Option<usize> {
+  let opcode = (inst >> 26) & 0x3f;
+  let rs = ((inst >> 21) & 0x1f) as usize;
+  let rt = ((inst >> 16) & 0x1f) as usize;
+  let rd = ((inst >> 11) & 0x1f) as usize;
+
+  match (opcode, isa) {
+    (0x00, _) if inst & 0x3f == 0x09 => Some(rd),
+    (0x01, Isa::Mips32) if (0x10..=0x13).contains(&rt) => Some(31),
+    (0x01, Isa::Mips32R6) if rs == 0 && (rt == 0x10 || rt == 0x11) => Some(31),
+    (0x03, _) => Some(31),
+
+    /* Compact branch-and-link */
+    (0x06 | 0x07, Isa::Mips32R6) if rt != 0 && (rs == 0 || rs == rt) => Some(31),
+    (0x08 | 0x18, Isa::Mips32R6) if rs == 0 && rt != 0 => Some(31),
+    (0x3A, Isa::Mips32R6) => Some(31),
+    (0x3E, Isa::Mips32R6) if rs == 0 => Some(31),
+    _ => None,
+  }
+}
@@ -0,0 +1,177 @@
+use super::arch::{Isa, Register};
+
+/// Formats `inst`, located at `pc`, as assembly. Branch and jump targets
+/// are shown as absolute addresses. Words that do not decode to a known
+/// instruction are shown as `.word`.
+pub fn disassemble(inst: u32, pc: u32, isa: Isa) -> String {
+  decode(inst, pc, isa).unwrap_or_else(|| format!(".word 0x{:08x}", inst))
+}
+
+fn reg(n: u32) -> String {
+  format!("${}", Register::NAMES[n as usize & 0x1f])
+}
+
+/// Encodings MIPS32 Release 6 removed or reassigned.
+fn removed_in_r6(inst: u32) -> bool {
+  let opcode = inst >> 26;
+  match opcode {
+    0x00 => matches!(inst & 0x3f, 0x08 | 0x10..=0x1B),
+    0x01 => match (inst >> 16) & 0x1f {
+      0x00 | 0x01 => false,
+      0x10 | 0x11 => (inst >> 21) & 0x1f != 0,
+      _ => true,
+    },
+    0x06..=0x08 | 0x14..=0x18 | 0x1C..=0x1F => true,
+    /* Unaligned and linked accesses */
+    0x22 | 0x26 | 0x2A | 0x2E | 0x30 | 0x38 => true,
+    0x32 | 0x36 | 0x3A..=0x3E => true,
+    _ => false,
+  }
+}
+
+fn decode(inst: u32, pc: u32, isa: Isa) -> Option<String> {
+  if isa == Isa::Mips32R6 && removed_in_r6(inst) {
+    return None;
+  }
+
+  let opcode = inst >> 26;
+  let (rs_n, rt_n, rd_n) = (
+    (inst >> 21) & 0x1f,
+    (inst >> 16) & 0x1f,
+    (inst >> 11) & 0x1f,
+  );
+  let (rs, rt, rd) = (reg(rs_n), reg(rt_n), reg(rd_n));
+  let shamt = (inst >> 6) & 0x1f;
+  let funct = inst & 0x3f;
+  let imm = inst & 0xffff;
+  let simm = imm as i16 as i32;
+  let target = pc.wrapping_add(4).wrapping_add((simm << 2) as u32);
+
+  Some(match opcode {
+    0x00 => match funct {
+      _ if inst == 0 => "nop".to_string(),
+      0x00 => format!("sll {}, {}, {}", rd, rt, shamt),
+      0x02 => format!("srl {}, {}, {}", rd, rt, shamt),
+      0x03 => format!("sra {}, {}, {}", rd, rt, shamt),
+      0x04 => format!("sllv {}, {}, {}", rd, rt, rs),
+      0x06 => format!("srlv {}, {}, {}", rd, rt, rs),
+      0x07 => format!("srav {}, {}, {}", rd, rt, rs),
+      0x08 => format!("jr {}", rs),
+      0x09 if rd_n == 31 => format!("jalr {}", rs),
+      0x09 => format!("jalr {}, {}", rd, rs),
+      0x0C => "syscall".to_string(),
+      0x0D => match (inst >> 6) & 0xfffff {
+        0 => "break".to_string(),
+        code => format!("break 0x{:x}", code),
+      },
+      0x10 => format!("mfhi {}", rd),
+      0x11 => format!("mthi {}", rs),
+      0x12 => format!("mflo {}", rd),
+      0x13 => format!("mtlo {}", rs),
+      0x18 => format!("mult {}, {}", rs, rt),
+      0x19 => format!("multu {}, {}", rs, rt),
+      0x1A => format!("div {}, {}", rs, rt),
+      0x1B => format!("divu {}, {}", rs, rt),
+      0x20 => format!("add {}, {}, {}", rd, rs, rt),
+      0x21 if rt_n == 0 => format!("move {}, {}", rd, rs),
+      0x21 => format!("addu {}, {}, {}", rd, rs, rt),
+      0x22 => format!("sub {}, {}, {}", rd, rs, rt),
+      0x23 => format!("subu {}, {}, {}", rd, rs, rt),
+      0x24 => format!("and {}, {}, {}", rd, rs, rt),
+      0x25 => format!("or {}, {}, {}", rd, rs, rt),
+      0x26 => format!("xor {}, {}, {}", rd, rs, rt),
+      0x27 => format!("nor {}, {}, {}", rd, rs, rt),
+      0x2A => format!("slt {}, {}, {}", rd, rs, rt),
+      0x2B => format!("sltu {}, {}, {}", rd, rs, rt),
+      0x30 => format!("tge {}, {}", rs, rt),
+      0x31 => format!("tgeu {}, {}", rs, rt),
+      0x32 => format!("tlt {}, {}", rs, rt),
+      0x33 => format!("tltu {}, {}", rs, rt),
+      0x34 => format!("teq {}, {}", rs, rt),
+      0x36 => format!("tne {}, {}", rs, rt),
+      _ => return None,
+    },
+
+    0x01 => {
+      let mnemonic = match rt_n {
+        0x00 => "bltz",
+        0x01 => "bgez",
+        0x02 => "bltzl",
+        0x03 => "bgezl",
+        0x08 => return Some(format!("tgei {}, {}", rs, simm)),
+        0x09 => return Some(format!("tgeiu {}, {}", rs, simm)),
+        0x0A => return Some(format!("tlti {}, {}", rs, simm)),
+        0x0B => return Some(format!("tltiu {}, {}", rs, simm)),
+        0x0C => return Some(format!("teqi {}, {}", rs, simm)),
+        0x0E => return Some(format!("tnei {}, {}", rs, simm)),
+        0x10 => "bltzal",
+        0x11 if rs_n == 0 => return Some(format!("bal 0x{:08x}", target)),
+        0x11 => "bgezal",
+        0x12 => "bltzall",
+        0x13 => "bgezall",
+        _ => return None,
+      };
+      format!("{} {}, 0x{:08x}", mnemonic, rs, target)
+    }
+
+    0x02 | 0x03 => {
+      let addr = (pc.wrapping_add(4) & 0xf000_0000) | ((inst & 0x03ff_ffff) << 2);
+      let mnemonic = if opcode == 0x02 { "j" } else { "jal" };
+      format!("{} 0x{:08x}", mnemonic, addr)
+    }
+
+    0x04 if rs_n == 0 && rt_n == 0 => format!("b 0x{:08x}", target),
+    0x04 | 0x05 | 0x14 | 0x15 => {
+      let mnemonic = match opcode {
+        0x04 => "beq",
+        0x05 => "bne",
+        0x14 => "beql",
+        _ => "bnel",
+      };
+      format!("{} {}, {}, 0x{:08x}", mnemonic, rs, rt, target)
+    }
+    0x06 | 0x07 | 0x16 | 0x17 => {
+      let mnemonic = match opcode {
+        0x06 => "blez",
+        0x07 => "bgtz",
+        0x16 => "blezl",
+        _ => "bgtzl",
+      };
+      format!("{} {}, 0x{:08x}", mnemonic, rs, target)
+    }
+
+    0x08 => format!("addi {}, {}, {}", rt, rs, simm),
+    0x09 if rs_n == 0 => format!("li {}, {}", rt, simm),
+    0x09 => format!("addiu {}, {}, {}", rt, rs, simm),
+    0x0A => format!("slti {}, {}, {}", rt, rs, simm),
+    0x0B => format!("sltiu {}, {}, {}", rt, rs, simm),
+    0x0C => format!("andi {}, {}, 0x{:x}", rt, rs, imm),
+    0x0D => format!("ori {}, {}, 0x{:x}", rt, rs, imm),
+    0x0E => format!("xori {}, {}, 0x{:x}", rt, rs, imm),
+    0x0F => format!("lui {}, 0x{:x}", rt, imm),
+
+    0x10 if inst == 0x4200_0018 => "eret".to_string(),
+
+    0x20..=0x26 | 0x28..=0x2B | 0x2E | 0x30 | 0x38 => {
+      let mnemonic = match opcode {
+        0x20 => "lb",
+        0x21 => "lh",
+        0x22 => "lwl",
+        0x23 => "lw",
+        0x24 => "lbu",
+        0x25 => "lhu",
+        0x26 => "lwr",
+        0x28 => "sb",
+        0x29 => "sh",
+        0x2A => "swl",
+        0x2B => "sw",
+        0x2E => "swr",
+        0x30 => "ll",
+        _ => "sc",
+      };
+      format!("{} {}, {}({})", mnemonic, rt, simm, rs)
+    }
+
+    _ => return None,
+  })
+}
@@ -1,32 +1,70 @@
 use crate::interrupt_exception;
 
-use super::{interrupt::Result, virt::MemMap};
+use super::{
+  arch::Endian,
+  interrupt::Result,
+  memory::{FlatMemory, Memory, SparseMemory},
+  virt::MemMap,
+};
 
 pub const DRAM_SIZE: u64 = 1024 * 1024 * 128; // 128 MiB
 
 #[derive(Debug, Clone)]
 pub struct Dram {
-  pub dram: Vec<u8>,
+  pub mem: Box<dyn Memory>,
+  pub endian: Endian,
+  /// Address of the first byte; `0x80000000` for main memory.
+  pub base: u32,
 }
 
 impl Default for Dram {
   fn default() -> Self {
-    Dram {
-      dram: vec![0; DRAM_SIZE as usize],
-    }
+    Dram::with_memory(Box::new(SparseMemory::new(DRAM_SIZE as u32)))
   }
 }
 
 impl Dram {
   pub fn new(code: Vec<u8>) -> Dram {
-    let mut dram = vec![0; DRAM_SIZE as usize];
-    dram.splice(..code.len(), code.iter().cloned());
+    let mut dram = Dram::default();
+    dram.mem.write(0, &code);
+    dram
+  }
+
+  /// DRAM backed by a single eagerly allocated buffer.
+  pub fn flat() -> Dram {
+    Dram::with_memory(Box::new(FlatMemory::new(DRAM_SIZE as u32)))
+  }
 
-    Self { dram }
+  pub fn with_memory(mem: Box<dyn Memory>) -> Dram {
+    Dram::at(MemMap::HIGHMEM.base, mem)
+  }
+
+  /// Memory starting at `base` rather than at the start of DRAM.
+  pub fn at(base: u32, mem: Box<dyn Memory>) -> Dram {
+    Dram {
+      mem,
+      endian: Endian::default(),
+      base,
+    }
   }
 
   pub fn size(&self) -> u32 {
-    self.dram.len() as u32
+    self.mem.size()
+  }
+
+  /// Whether the `bytes` bytes starting at `addr` all lie in this memory.
+  pub fn holds(&self, addr: u32, bytes: u32) -> bool {
+    let start = self.base as u64;
+    let end = start + self.size() as u64;
+    addr as u64 >= start && addr as u64 + bytes as u64 <= end
+  }
+
+  pub fn read_bytes(&self, addr: u32, buf: &mut [u8]) {
+    self.mem.read(self.index(addr), buf);
+  }
+
+  pub fn write_bytes(&mut self, addr: u32, data: &[u8]) {
+    self.mem.write(self.index(addr), data);
   }
 
   pub fn load(&self, addr: u32, size: u32) -> Result<u32> {
@@ -34,58 +72,67 @@ impl Dram {
       8 => Ok(self.load8(addr)),
       16 => Ok(self.load16(addr)),
       32 => Ok(self.load32(addr)),
-      _ => interrupt_exception!(DBUS(format!("Cannot load value of {} bytes", size))),
+      _ => interrupt_exception!(DBUS(addr)),
     }
   }
 
   pub fn store(&mut self, addr: u32, size: u32, value: u32) -> Result<()> {
     match size {
-      8 => Ok(self.store8(addr, value)),
-      16 => Ok(self.store16(addr, value)),
-      32 => Ok(self.store32(addr, value)),
-      _ => interrupt_exception!(DBUS(format!("Cannot store value of {} bytes", size))),
+      8 => self.store8(addr, value),
+      16 => self.store16(addr, value),
+      32 => self.store32(addr, value),
+      _ => interrupt_exception!(DBUS(addr)),
     }
+
+    Ok(())
   }
 
   #[inline]
-  fn get_index(addr: u32) -> usize {
-    (addr - MemMap::HIGHMEM.base) as usize
+  fn index(&self, addr: u32) -> u32 {
+    addr - self.base
   }
 
   fn load8(&self, addr: u32) -> u32 {
-    let index = Dram::get_index(addr);
-    return self.dram[index] as u32;
+    let mut bytes = [0; 1];
+    self.read_bytes(addr, &mut bytes);
+    bytes[0] as u32
   }
 
   fn load16(&self, addr: u32) -> u32 {
-    let index = Dram::get_index(addr);
-    return (self.dram[index] as u32) | ((self.dram[index + 1] as u32) << 8);
+    let mut bytes = [0; 2];
+    self.read_bytes(addr, &mut bytes);
+    match self.endian {
+      Endian::Little => u16::from_le_bytes(bytes) as u32,
+      Endian::Big => u16::from_be_bytes(bytes) as u32,
+    }
   }
 
   fn load32(&self, addr: u32) -> u32 {
-    let index = Dram::get_index(addr);
-    return (self.dram[index] as u32)
-      | ((self.dram[index + 1] as u32) << 8)
-      | ((self.dram[index + 2] as u32) << 16)
-      | ((self.dram[index + 3] as u32) << 24);
+    let mut bytes = [0; 4];
+    self.read_bytes(addr, &mut bytes);
+    match self.endian {
+      Endian::Little => u32::from_le_bytes(bytes),
+      Endian::Big => u32::from_be_bytes(bytes),
+    }
   }
 
   fn store8(&mut self, addr: u32, value: u32) {
-    let index = Dram::get_index(addr);
-    self.dram[index] = (value & 0xff) as u8;
+    self.write_bytes(addr, &[(value & 0xff) as u8]);
   }
 
   fn store16(&mut self, addr: u32, value: u32) {
-    let index = Dram::get_index(addr);
-    self.dram[index] = (value & 0xff) as u8;
-    self.dram[index + 1] = ((value >> 8) & 0xff) as u8;
+    let bytes = match self.endian {
+      Endian::Little => (value as u16).to_le_bytes(),
+      Endian::Big => (value as u16).to_be_bytes(),
+    };
+    self.write_bytes(addr, &bytes);
   }
 
   fn store32(&mut self, addr: u32, value: u32) {
-    let index = Dram::get_index(addr);
-    self.dram[index] = (value & 0xff) as u8;
-    self.dram[index + 1] = ((value >> 8) & 0xff) as u8;
-    self.dram[index + 2] = ((value >> 16) & 0xff) as u8;
-    self.dram[index + 3] = ((value >> 24) & 0xff) as u8;
+    let bytes = match self.endian {
+      Endian::Little => value.to_le_bytes(),
+      Endian::Big => value.to_be_bytes(),
+    };
+    self.write_bytes(addr, &bytes);
   }
 }
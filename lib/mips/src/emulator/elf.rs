@@ -0,0 +1,221 @@
+use thiserror::Error;
+
+use super::{
+  arch::Endian,
+  bus::Bus,
+  cpu::Cpu,
+  interrupt::Interrupt,
+  memory::PAGE_SIZE,
+  virt::{Access, MemRegion, Perm},
+};
+
+const EM_MIPS: u16 = 8;
+const PT_LOAD: u32 = 1;
+
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+
+#[derive(Debug, Error)]
+pub enum ElfError {
+  #[error("Not an ELF file")]
+  MAGIC,
+
+  #[error("Only 32-bit ELF files are supported")]
+  CLASS,
+
+  #[error("Unknown byte order: {0}")]
+  DATA(u8),
+
+  #[error("Not a MIPS executable (e_machine = {0})")]
+  MACHINE(u16),
+
+  #[error("Truncated ELF file")]
+  TRUNCATED,
+
+  #[error("Segment at {0:#010X} of {1} bytes does not fit in memory")]
+  SIZE(u32, u32),
+
+  #[error("Cannot load segment: {0}")]
+  LOAD(Interrupt),
+}
+
+/// A loadable segment of an ELF executable.
+#[derive(Debug, Clone)]
+pub struct Segment {
+  pub vaddr: u32,
+  pub mem_size: u32,
+  pub flags: u32,
+  pub data: Vec<u8>,
+}
+
+impl Segment {
+  pub fn perm(&self) -> Perm {
+    [(PF_R, Perm::R), (PF_W, Perm::W), (PF_X, Perm::X)]
+      .into_iter()
+      .filter(|&(flag, _)| self.flags & flag != 0)
+      .fold(Perm::NONE, |perm, (_, bit)| perm | bit)
+  }
+}
+
+/// The parts of a 32-bit MIPS ELF executable needed to run it.
+#[derive(Debug, Clone)]
+pub struct Elf {
+  pub endian: Endian,
+  pub entry: u32,
+  pub segments: Vec<Segment>,
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  endian: Endian,
+}
+
+impl Reader<'_> {
+  fn slice(&self, offset: usize, len: usize) -> Result<&[u8], ElfError> {
+    offset
+      .checked_add(len)
+      .and_then(|end| self.bytes.get(offset..end))
+      .ok_or(ElfError::TRUNCATED)
+  }
+
+  fn u16(&self, offset: usize) -> Result<u16, ElfError> {
+    let b = self.slice(offset, 2)?.try_into().unwrap();
+    Ok(match self.endian {
+      Endian::Little => u16::from_le_bytes(b),
+      Endian::Big => u16::from_be_bytes(b),
+    })
+  }
+
+  fn u32(&self, offset: usize) -> Result<u32, ElfError> {
+    let b = self.slice(offset, 4)?.try_into().unwrap();
+    Ok(match self.endian {
+      Endian::Little => u32::from_le_bytes(b),
+      Endian::Big => u32::from_be_bytes(b),
+    })
+  }
+}
+
+impl Elf {
+  pub fn parse(bytes: &[u8]) -> Result<Elf, ElfError> {
+    if bytes.len() < 16 || &bytes[..4] != b"\x7fELF" {
+      return Err(ElfError::MAGIC);
+    }
+
+    if bytes[4] != 1 {
+      return Err(ElfError::CLASS);
+    }
+
+    let endian = match bytes[5] {
+      1 => Endian::Little,
+      2 => Endian::Big,
+      other => return Err(ElfError::DATA(other)),
+    };
+
+    let r = Reader { bytes, endian };
+
+    let machine = r.u16(18)?;
+    if machine != EM_MIPS {
+      return Err(ElfError::MACHINE(machine));
+    }
+
+    let entry = r.u32(24)?;
+    let phoff = r.u32(28)? as usize;
+    let phentsize = r.u16(42)? as usize;
+    let phnum = r.u16(44)? as usize;
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+      let ph = phoff + i * phentsize;
+      if r.u32(ph)? != PT_LOAD {
+        continue;
+      }
+
+      let offset = r.u32(ph + 4)? as usize;
+      let vaddr = r.u32(ph + 8)?;
+      let file_size = r.u32(ph + 16)? as usize;
+      let mem_size = r.u32(ph + 20)?;
+      let flags = r.u32(ph + 24)?;
+
+      segments.push(Segment {
+        vaddr,
+        mem_size,
+        flags,
+        data: r.slice(offset, file_size)?.to_vec(),
+      });
+    }
+
+    Ok(Elf {
+      endian,
+      entry,
+      segments,
+    })
+  }
+}
+
+impl Cpu {
+  /// Loads an ELF executable, switching the machine to the byte order it
+  /// was built for and pointing the PC at its entry point. Each segment is
+  /// mapped with the permissions recorded in its program header, and
+  /// segments linked outside of DRAM get memory of their own.
+  pub fn load_elf(&mut self, bytes: &[u8]) -> Result<(), ElfError> {
+    let elf = Elf::parse(bytes)?;
+
+    self.bus.dram.endian = elf.endian;
+
+    for segment in &elf.segments {
+      /* p_memsz comes from the file; no segment may outgrow DRAM or wrap */
+      let size = segment.mem_size.max(segment.data.len() as u32);
+      if size > self.bus.dram.size() || segment.vaddr.checked_add(size).is_none() {
+        return Err(ElfError::SIZE(segment.vaddr, size));
+      }
+
+      let mut data = segment.data.clone();
+      data.resize(size as usize, 0);
+      back_segment(&mut self.bus, segment.vaddr, data.len() as u32).map_err(ElfError::LOAD)?;
+      self
+        .bus
+        .write_bytes(segment.vaddr, &data)
+        .map_err(ElfError::LOAD)?;
+
+      let perm = segment.perm();
+      let name = if perm.allows(Access::Execute) {
+        ".text"
+      } else {
+        ".data"
+      };
+      let region = MemRegion {
+        base: segment.vaddr,
+        size: data.len() as u32,
+      };
+      if perm.allows(Access::Execute) && region.contains(elf.entry) {
+        self.text_end = Some(region.end());
+      }
+      self.bus.map(name, region, perm);
+    }
+
+    self.pc = elf.entry;
+
+    Ok(())
+  }
+}
+
+/// Adds memory for the pages spanned by `size` bytes at `vaddr` that are
+/// not backed yet. Segments are sorted by address, so only the start of
+/// one can share a page with memory added for the previous one.
+fn back_segment(bus: &mut Bus, vaddr: u32, size: u32) -> Result<(), Interrupt> {
+  let page = PAGE_SIZE as u64;
+  let mut start = vaddr as u64 / page * page;
+  let end = (vaddr as u64 + size as u64).div_ceil(page) * page;
+  if let Some(mem) = bus.memory(start as u32, 1) {
+    start = start.max(mem.base as u64 + mem.size() as u64);
+  }
+  if start >= end || bus.memory(vaddr, size).is_some() {
+    return Ok(());
+  }
+
+  bus.add_memory(MemRegion {
+    base: start as u32,
+    size: (end - start).min(u32::MAX as u64 + 1 - start) as u32,
+  })
+}
@@ -1,9 +1,15 @@
 use std::fmt::Display;
 use thiserror::Error;
 
+use super::{
+  limits::LimitExceeded,
+  virt::{Access, Perm},
+  watch::WatchHit,
+};
+
 pub type Result<T> = std::result::Result<T, Interrupt>;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum Interrupt {
   Software(SoftwareInterrupt),
   Hardware(HardwareInterrupt),
@@ -20,11 +26,40 @@ impl Display for Interrupt {
   }
 }
 
-#[derive(Debug, Error)]
-pub enum SoftwareInterrupt {
-  #[error("syscall")]
-  SYSCALL,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackFaultKind {
+  /// `$sp` went below the stack limit
+  Overflow,
+  /// `$sp` went below the program break
+  HeapCollision,
+}
+
+/// The stack pointer left the stack, with the instruction that moved it.
+#[derive(Debug, Clone)]
+pub struct StackFault {
+  pub kind: StackFaultKind,
+  pub sp: u32,
+  pub depth: u32,
+  pub pc: u32,
+  pub inst: u32,
+}
 
+impl Display for StackFault {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let what = match self.kind {
+      StackFaultKind::Overflow => "Stack overflow",
+      StackFaultKind::HeapCollision => "Stack collided with heap",
+    };
+    write!(
+      f,
+      "{}: $sp = {:#010X} ({} bytes deep) after {:#010X} at {:#010X}",
+      what, self.sp, self.depth, self.inst, self.pc
+    )
+  }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum SoftwareInterrupt {
   #[error("Error writing to stdout: {0:?}")]
   STDOUT(String),
 
@@ -33,12 +68,51 @@ pub enum SoftwareInterrupt {
 
   #[error("Unsupported syscall: {0:#4X}")]
   UNSUPPORTED(u32),
+
+  #[error("Heap request exceeds available memory: {0} bytes")]
+  HEAP(u32),
+
+  #[error("{0}")]
+  STACK(StackFault),
+
+  #[error("{0}")]
+  WATCH(WatchHit),
+
+  #[error("{0}")]
+  LIMIT(LimitExceeded),
+
+  #[error("Stopped on request at {0:#010X}")]
+  STOPPED(u32),
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum HardwareInterrupt {}
 
-#[derive(Debug, Error)]
+/// An access denied by the permissions of the memory it targets.
+#[derive(Debug, Clone)]
+pub struct Violation {
+  pub addr: u32,
+  pub access: Access,
+  pub region: String,
+  pub perm: Perm,
+}
+
+impl Display for Violation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let action = match self.access {
+      Access::Read => "Read from",
+      Access::Write => "Write to",
+      Access::Execute => "Execution of",
+    };
+    write!(
+      f,
+      "{} {:#010X} denied by {} ({})",
+      action, self.addr, self.region, self.perm
+    )
+  }
+}
+
+#[derive(Debug, Clone, Error)]
 pub enum ExceptionInterrupt {
   #[error("Load from an illegal address: {0:#010X}")]
   ADDRL(u32),
@@ -46,15 +120,21 @@ pub enum ExceptionInterrupt {
   #[error("Store to an illegal address {0:#010X}")]
   ADDRS(u32),
 
-  #[error("Bus error on instruction fetch: {0:?}")]
-  IBUS(String),
+  #[error("Bus error on instruction fetch: {0:#010X}")]
+  IBUS(u32),
 
-  #[error("Bus error on data reference: {0:?}")]
-  DBUS(String),
+  #[error("Bus error on data reference: {0:#010X}")]
+  DBUS(u32),
 
   #[error("Arithmetic overflow")]
   OVF,
 
+  #[error("Break instruction executed: code {0}")]
+  BREAK(u32),
+
+  #[error("Trap")]
+  TRAP,
+
   #[error("Value not word-aligned: {0:#010X}")]
   ALIGNMENT(u32),
 
@@ -63,18 +143,39 @@ pub enum ExceptionInterrupt {
 
   #[error("Unsupported instruction: {0:#010X}")]
   UNSUPPORTED(u32),
+
+  #[error("Control transfer in forbidden slot: {0:#010X}")]
+  FORBIDDEN(u32),
+
+  #[error("Memory protection violation: {0}")]
+  PROTECTION(Violation),
+}
+
+impl ExceptionInterrupt {
+  /// The address that caused the exception, for those raised by an
+  /// access to memory.
+  pub fn addr(&self) -> Option<u32> {
+    match self {
+      ExceptionInterrupt::ADDRL(addr)
+      | ExceptionInterrupt::ADDRS(addr)
+      | ExceptionInterrupt::IBUS(addr)
+      | ExceptionInterrupt::DBUS(addr) => Some(*addr),
+      ExceptionInterrupt::PROTECTION(violation) => Some(violation.addr),
+      _ => None,
+    }
+  }
 }
 
 #[macro_export]
 macro_rules! interrupt_software {
   ($x:ident) => {
-    return Err(crate::emulator::interrupt::Interrupt::Software(
-      crate::emulator::interrupt::SoftwareInterrupt::$x,
+    return Err($crate::emulator::interrupt::Interrupt::Software(
+      $crate::emulator::interrupt::SoftwareInterrupt::$x,
     ))
   };
   ($x:ident($e:expr)) => {
-    return Err(crate::emulator::interrupt::Interrupt::Software(
-      crate::emulator::interrupt::SoftwareInterrupt::$x($e),
+    return Err($crate::emulator::interrupt::Interrupt::Software(
+      $crate::emulator::interrupt::SoftwareInterrupt::$x($e),
     ))
   };
 }
@@ -82,13 +183,13 @@ macro_rules! interrupt_software {
 #[macro_export]
 macro_rules! interrupt_hardware {
   ($x:ident) => {
-    return Err(crate::emulator::interrupt::Interrupt::Hardware(
-      crate::emulator::interrupt::HardwareInterrupt::$x,
+    return Err($crate::emulator::interrupt::Interrupt::Hardware(
+      $crate::emulator::interrupt::HardwareInterrupt::$x,
     ))
   };
   ($x:ident($e:expr)) => {
-    return Err(crate::emulator::interrupt::Interrupt::Hardware(
-      crate::emulator::interrupt::HardwareInterrupt::$x($e),
+    return Err($crate::emulator::interrupt::Interrupt::Hardware(
+      $crate::emulator::interrupt::HardwareInterrupt::$x($e),
     ))
   };
 }
@@ -96,13 +197,13 @@ macro_rules! interrupt_hardware {
 #[macro_export]
 macro_rules! interrupt_exception {
   ($x:ident) => {
-    return Err(crate::emulator::interrupt::Interrupt::Exception(
-      crate::emulator::interrupt::ExceptionInterrupt::$x,
+    return Err($crate::emulator::interrupt::Interrupt::Exception(
+      $crate::emulator::interrupt::ExceptionInterrupt::$x,
     ))
   };
   ($x:ident($e:expr)) => {
-    return Err(crate::emulator::interrupt::Interrupt::Exception(
-      crate::emulator::interrupt::ExceptionInterrupt::$x($e),
+    return Err($crate::emulator::interrupt::Interrupt::Exception(
+      $crate::emulator::interrupt::ExceptionInterrupt::$x($e),
     ))
   };
 }
@@ -0,0 +1,53 @@
+use std::{fmt::Display, time::Duration};
+
+/// How often, in retired instructions, the wall-clock limit is checked.
+pub const CLOCK_INTERVAL: u64 = 4096;
+
+/// Bounds on a run, so runaway guest programs cannot hang the host.
+///
+/// What counts against the instruction and output limits is machine state:
+/// [`Sys::restore`] rewinds it with the rest of a [`Snapshot`], and
+/// [`Sys::fork`] copies it.
+///
+/// [`Snapshot`]: super::sys::Snapshot
+/// [`Sys::restore`]: super::sys::Sys::restore
+/// [`Sys::fork`]: super::sys::Sys::fork
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+  /// Instructions the machine may retire in total
+  pub instructions: Option<u64>,
+  /// Wall-clock time each call to [`Sys::run`] or a resuming debugger
+  /// command may take
+  ///
+  /// [`Sys::run`]: super::sys::Sys::run
+  pub time: Option<Duration>,
+  /// Bytes the guest may write to its console
+  pub output: Option<u64>,
+}
+
+/// A limit that was exceeded, with its configured value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+  Instructions(u64),
+  Time(Duration),
+  Output(u64),
+}
+
+/// Execution stopped because it reached one of its [`Limits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+  pub kind: LimitKind,
+  /// Address of the next instruction to execute
+  pub pc: u32,
+}
+
+impl Display for LimitExceeded {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.kind {
+      LimitKind::Instructions(n) => write!(f, "Instruction limit of {}", n)?,
+      LimitKind::Time(t) => write!(f, "Time limit of {:?}", t)?,
+      LimitKind::Output(n) => write!(f, "Output limit of {} bytes", n)?,
+    }
+    write!(f, " reached at {:#010X}", self.pc)
+  }
+}
@@ -0,0 +1,167 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+pub const PAGE_SIZE: usize = 4096; // 4 KiB
+
+/// Byte-addressable backing store for guest memory.
+///
+/// Offsets are relative to the start of the store; accesses are assumed to
+/// have been bounds-checked by the caller.
+pub trait Memory: Debug + Send {
+  fn size(&self) -> u32;
+
+  fn read(&self, offset: u32, buf: &mut [u8]);
+
+  fn write(&mut self, offset: u32, data: &[u8]);
+
+  fn box_clone(&self) -> Box<dyn Memory>;
+
+  /// Visits every page that holds a non-zero byte, in ascending order.
+  fn for_each_page(&self, f: &mut dyn FnMut(u32, &[u8])) {
+    let mut page = [0; PAGE_SIZE];
+    for offset in (0..self.size()).step_by(PAGE_SIZE) {
+      let len = PAGE_SIZE.min((self.size() - offset) as usize);
+      self.read(offset, &mut page[..len]);
+      if page[..len].iter().any(|&b| b != 0) {
+        f(offset, &page[..len]);
+      }
+    }
+  }
+}
+
+impl Clone for Box<dyn Memory> {
+  fn clone(&self) -> Self {
+    self.box_clone()
+  }
+}
+
+/// Memory backed by a single contiguous allocation.
+#[derive(Debug, Clone)]
+pub struct FlatMemory {
+  bytes: Vec<u8>,
+}
+
+impl FlatMemory {
+  pub fn new(size: u32) -> Self {
+    Self {
+      bytes: vec![0; size as usize],
+    }
+  }
+}
+
+impl Memory for FlatMemory {
+  fn size(&self) -> u32 {
+    self.bytes.len() as u32
+  }
+
+  fn read(&self, offset: u32, buf: &mut [u8]) {
+    let start = offset as usize;
+    buf.copy_from_slice(&self.bytes[start..start + buf.len()]);
+  }
+
+  fn write(&mut self, offset: u32, data: &[u8]) {
+    let start = offset as usize;
+    self.bytes[start..start + data.len()].copy_from_slice(data);
+  }
+
+  fn box_clone(&self) -> Box<dyn Memory> {
+    Box::new(self.clone())
+  }
+}
+
+/// Memory allocated a page at a time on first write. Pages that were never
+/// written read as zero.
+///
+/// Pages are shared copy-on-write between clones, so cloning costs one
+/// reference count per resident page and a page is only duplicated once
+/// either side writes to it.
+#[derive(Debug, Clone)]
+pub struct SparseMemory {
+  size: u32,
+  pages: HashMap<u32, Arc<[u8; PAGE_SIZE]>>,
+}
+
+impl SparseMemory {
+  pub fn new(size: u32) -> Self {
+    Self {
+      size,
+      pages: HashMap::new(),
+    }
+  }
+
+  /// Number of pages that have been allocated so far.
+  pub fn resident_pages(&self) -> usize {
+    self.pages.len()
+  }
+
+  /// Splits `[offset, offset + len)` into per-page `(page, start, len)` runs.
+  fn chunks(offset: u32, len: usize) -> impl Iterator<Item = (u32, usize, usize)> {
+    let mut offset = offset as usize;
+    let end = offset + len;
+    std::iter::from_fn(move || {
+      if offset >= end {
+        return None;
+      }
+
+      let page = offset / PAGE_SIZE;
+      let start = offset % PAGE_SIZE;
+      let len = (PAGE_SIZE - start).min(end - offset);
+      offset += len;
+
+      Some((page as u32, start, len))
+    })
+  }
+}
+
+impl Memory for SparseMemory {
+  fn size(&self) -> u32 {
+    self.size
+  }
+
+  fn read(&self, offset: u32, buf: &mut [u8]) {
+    let mut done = 0;
+    for (page, start, len) in SparseMemory::chunks(offset, buf.len()) {
+      let out = &mut buf[done..done + len];
+      match self.pages.get(&page) {
+        Some(bytes) => out.copy_from_slice(&bytes[start..start + len]),
+        None => out.fill(0),
+      }
+      done += len;
+    }
+  }
+
+  fn write(&mut self, offset: u32, data: &[u8]) {
+    /* Pages are allocated lazily, so nothing else would stop a stray write */
+    assert!(
+      offset as usize + data.len() <= self.size as usize,
+      "write of {} bytes at {offset:#x} past the end of {:#x} bytes",
+      data.len(),
+      self.size
+    );
+
+    let mut done = 0;
+    for (page, start, len) in SparseMemory::chunks(offset, data.len()) {
+      let bytes = self
+        .pages
+        .entry(page)
+        .or_insert_with(|| Arc::new([0; PAGE_SIZE]));
+      Arc::make_mut(bytes)[start..start + len].copy_from_slice(&data[done..done + len]);
+      done += len;
+    }
+  }
+
+  fn box_clone(&self) -> Box<dyn Memory> {
+    Box::new(self.clone())
+  }
+
+  fn for_each_page(&self, f: &mut dyn FnMut(u32, &[u8])) {
+    let mut pages: Vec<_> = self.pages.iter().collect();
+    pages.sort_unstable_by_key(|(page, _)| **page);
+    for (page, bytes) in pages {
+      let offset = page * PAGE_SIZE as u32;
+      let len = PAGE_SIZE.min((self.size - offset) as usize);
+      if bytes[..len].iter().any(|&b| b != 0) {
+        f(offset, &bytes[..len]);
+      }
+    }
+  }
+}
@@ -1,7 +1,21 @@
+pub mod arch;
 pub mod bus;
+#[cfg(feature = "serde")]
+pub mod checkpoint;
+pub mod console;
+pub mod control;
 pub mod cpu;
+pub mod debug;
+pub mod decode;
+pub mod disasm;
 pub mod dram;
-pub mod virt;
-pub mod arch;
+pub mod elf;
+pub mod interrupt;
+pub mod limits;
+pub mod memory;
+pub mod monitor;
+mod r6;
 pub mod sys;
-pub mod interrupt;
\ No newline at end of file
+pub mod vfs;
+pub mod virt;
+pub mod watch;
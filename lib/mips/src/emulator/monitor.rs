@@ -0,0 +1,48 @@
+/// Size in bytes of the block guarded by a load-linked reservation.
+pub const LINK_LINE: u32 = 16;
+
+/// Tracks load-linked reservations for every agent sharing a bus.
+///
+/// A reservation is broken by any store that touches the linked line,
+/// whether a guest store or a host write such as a syscall filling a
+/// buffer, and by its owner executing `eret`.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Monitor {
+  links: Vec<(usize, u32)>,
+}
+
+impl Monitor {
+  #[inline]
+  fn line(addr: u32) -> u32 {
+    addr & !(LINK_LINE - 1)
+  }
+
+  pub fn link(&mut self, agent: usize, addr: u32) {
+    self.unlink(agent);
+    self.links.push((agent, Monitor::line(addr)));
+  }
+
+  pub fn unlink(&mut self, agent: usize) {
+    self.links.retain(|&(owner, _)| owner != agent);
+  }
+
+  pub fn is_linked(&self, agent: usize, addr: u32) -> bool {
+    let line = Monitor::line(addr);
+    self
+      .links
+      .iter()
+      .any(|&(owner, linked)| owner == agent && linked == line)
+  }
+
+  /// Breaks every reservation on the lines covered by a store of `size` bits.
+  pub fn invalidate(&mut self, addr: u32, size: u32) {
+    let first = Monitor::line(addr);
+    let last = Monitor::line(addr.wrapping_add(size / 8).wrapping_sub(1));
+    /* Measured from `first` so a span that wraps around still matches */
+    let span = last.wrapping_sub(first);
+    self
+      .links
+      .retain(|&(_, line)| line.wrapping_sub(first) > span);
+  }
+}
@@ -0,0 +1,299 @@
+use crate::interrupt_exception;
+
+use super::{
+  arch::Register,
+  cpu::{sign_ext, Cpu},
+  interrupt::*,
+};
+
+impl Cpu {
+  /// Decodes `inst` under the Release 6 encoding rules.
+  ///
+  /// Returns `Ok(true)` when the instruction was new or re-encoded in
+  /// Release 6 and has been executed, `Ok(false)` when it decodes the same
+  /// way as in earlier releases, and an `UNSUPPORTED` exception when it was
+  /// removed from the architecture.
+  pub(crate) fn execute_r6(&mut self, inst: u32) -> Result<bool> {
+    if std::mem::take(&mut self.forbidden_slot) && Cpu::is_control_transfer_r6(inst) {
+      interrupt_exception!(FORBIDDEN(inst))
+    }
+
+    let r = &mut self.regs;
+    let opcode = (inst >> 26) & 0x3f;
+    let rs = ((inst >> 21) & 0x1f) as usize;
+    let rt = ((inst >> 16) & 0x1f) as usize;
+    let rd = ((inst >> 11) & 0x1f) as usize;
+    let off16 = sign_ext((inst & 0xffff) << 2, 18);
+
+    match opcode {
+      /* ----- R-Type Instructions ----- */
+      0x00 => {
+        let funct = inst & 0x3f;
+        let shamt = (inst >> 6) & 0x1f;
+
+        match (funct, shamt) {
+          /* LSA $rd, $rs, $rt, sa */
+          (0x05, _) => {
+            let sa = (shamt & 0x3) + 1;
+            r[rd] = (r[rs] << sa).wrapping_add(r[rt]);
+          }
+
+          /* JR $rs (removed; encoded as JALR $zero, $rs) */
+          (0x08, _) => interrupt_exception!(UNSUPPORTED(inst)),
+
+          /* MFHI, MTHI, MFLO, MTLO (removed) */
+          (0x10..=0x13, _) => interrupt_exception!(UNSUPPORTED(inst)),
+
+          /* MUL $rd, $rs, $rt */
+          (0x18, 0x02) => r[rd] = (r[rs] as i32).wrapping_mul(r[rt] as i32) as u32,
+
+          /* MUH $rd, $rs, $rt */
+          (0x18, 0x03) => {
+            let res = (r[rs] as i32 as i64) * (r[rt] as i32 as i64);
+            r[rd] = (res >> 32) as u32;
+          }
+
+          /* MULU $rd, $rs, $rt */
+          (0x19, 0x02) => r[rd] = r[rs].wrapping_mul(r[rt]),
+
+          /* MUHU $rd, $rs, $rt */
+          (0x19, 0x03) => {
+            let res = (r[rs] as u64) * (r[rt] as u64);
+            r[rd] = (res >> 32) as u32;
+          }
+
+          /* DIV $rd, $rs, $rt */
+          (0x1A, 0x02) => {
+            let (a, b) = (r[rs] as i32, r[rt] as i32);
+            r[rd] = if b == 0 { 0 } else { a.wrapping_div(b) as u32 };
+          }
+
+          /* MOD $rd, $rs, $rt */
+          (0x1A, 0x03) => {
+            let (a, b) = (r[rs] as i32, r[rt] as i32);
+            r[rd] = if b == 0 { 0 } else { a.wrapping_rem(b) as u32 };
+          }
+
+          /* DIVU $rd, $rs, $rt */
+          (0x1B, 0x02) => r[rd] = r[rs].checked_div(r[rt]).unwrap_or(0),
+
+          /* MODU $rd, $rs, $rt */
+          (0x1B, 0x03) => r[rd] = r[rs].checked_rem(r[rt]).unwrap_or(0),
+
+          /* MULT, MULTU, DIV, DIVU (removed) */
+          (0x18..=0x1B, _) => interrupt_exception!(UNSUPPORTED(inst)),
+
+          /* SELEQZ $rd, $rs, $rt */
+          (0x35, 0x00) => r[rd] = if r[rt] == 0 { r[rs] } else { 0 },
+
+          /* SELNEZ $rd, $rs, $rt */
+          (0x37, 0x00) => r[rd] = if r[rt] != 0 { r[rs] } else { 0 },
+
+          _ => return Ok(false),
+        }
+      }
+
+      /* ----- REGIMM Instructions ----- */
+      0x01 => match rt {
+        /* BLTZL, BGEZL, BLTZALL, BGEZALL, trap immediates (removed) */
+        0x02 | 0x03 | 0x08..=0x0C | 0x0E | 0x12 | 0x13 => interrupt_exception!(UNSUPPORTED(inst)),
+
+        /* BLTZAL, BGEZAL (removed; only NAL and BAL remain) */
+        0x10 | 0x11 if rs != 0 => interrupt_exception!(UNSUPPORTED(inst)),
+
+        _ => return Ok(false),
+      },
+
+      /* POP06: BLEZALC, BGEZALC, BGEUC */
+      0x06 if rt != 0 => {
+        let (a, b) = (r[rs], r[rt]);
+        match rs {
+          0 => self.compact_branch((b as i32) <= 0, off16, true),
+          _ if rs == rt => self.compact_branch((b as i32) >= 0, off16, true),
+          _ => self.compact_branch(a >= b, off16, false),
+        }
+      }
+
+      /* POP07: BGTZALC, BLTZALC, BLTUC */
+      0x07 if rt != 0 => {
+        let (a, b) = (r[rs], r[rt]);
+        match rs {
+          0 => self.compact_branch((b as i32) > 0, off16, true),
+          _ if rs == rt => self.compact_branch((b as i32) < 0, off16, true),
+          _ => self.compact_branch(a < b, off16, false),
+        }
+      }
+
+      /* POP10: BOVC, BEQZALC, BEQC */
+      0x08 => {
+        let (a, b) = (r[rs], r[rt]);
+        if rs >= rt {
+          let overflow = (a as i32).checked_add(b as i32).is_none();
+          self.compact_branch(overflow, off16, false);
+        } else if rs == 0 {
+          self.compact_branch(b == 0, off16, true);
+        } else {
+          self.compact_branch(a == b, off16, false);
+        }
+      }
+
+      /* AUI $rt, $rs, imm */
+      0x0F if rs != 0 => r[rt] = r[rs].wrapping_add((inst & 0xffff) << 16),
+
+      /* BEQL, BNEL (removed) */
+      0x14 | 0x15 => interrupt_exception!(UNSUPPORTED(inst)),
+
+      /* POP26: BLEZC, BGEZC, BGEC */
+      0x16 => {
+        let (a, b) = (r[rs] as i32, r[rt] as i32);
+        match rs {
+          _ if rt == 0 => interrupt_exception!(UNSUPPORTED(inst)),
+          0 => self.compact_branch(b <= 0, off16, false),
+          _ if rs == rt => self.compact_branch(b >= 0, off16, false),
+          _ => self.compact_branch(a >= b, off16, false),
+        }
+      }
+
+      /* POP27: BGTZC, BLTZC, BLTC */
+      0x17 => {
+        let (a, b) = (r[rs] as i32, r[rt] as i32);
+        match rs {
+          _ if rt == 0 => interrupt_exception!(UNSUPPORTED(inst)),
+          0 => self.compact_branch(b > 0, off16, false),
+          _ if rs == rt => self.compact_branch(b < 0, off16, false),
+          _ => self.compact_branch(a < b, off16, false),
+        }
+      }
+
+      /* POP30: BNVC, BNEZALC, BNEC */
+      0x18 => {
+        let (a, b) = (r[rs], r[rt]);
+        if rs >= rt {
+          let overflow = (a as i32).checked_add(b as i32).is_none();
+          self.compact_branch(!overflow, off16, false);
+        } else if rs == 0 {
+          self.compact_branch(b != 0, off16, true);
+        } else {
+          self.compact_branch(a != b, off16, false);
+        }
+      }
+
+      /* SPECIAL3: LL, SC */
+      0x1F => {
+        let addr = r[rs].wrapping_add_signed(sign_ext((inst >> 7) & 0x1ff, 9));
+        match inst & 0x3f {
+          /* LL $rt, offset($rs) */
+          0x36 => {
+            let word = self.bus.load_linked(self.id, addr)?;
+            self.regs[rt] = word;
+          }
+
+          /* SC $rt, offset($rs) */
+          0x26 => {
+            let stored = self.bus.store_conditional(self.id, addr, r[rt])?;
+            self.regs[rt] = stored as u32;
+          }
+
+          _ => return Ok(false),
+        }
+      }
+
+      /* LWL, LWR, SWL, SWR (removed) */
+      0x22 | 0x26 | 0x2A | 0x2E => interrupt_exception!(UNSUPPORTED(inst)),
+
+      /* LL, SC (moved to SPECIAL3) */
+      0x30 | 0x38 => interrupt_exception!(UNSUPPORTED(inst)),
+
+      /* BC offset */
+      0x32 => {
+        self.pc = self
+          .pc
+          .wrapping_add_signed(sign_ext((inst & 0x3ffffff) << 2, 28))
+      }
+
+      /* POP66: BEQZC $rs, offset / JIC $rt, offset */
+      0x36 => {
+        if rs != 0 {
+          let cond = r[rs] == 0;
+          self.compact_branch(cond, sign_ext((inst & 0x1fffff) << 2, 23), false);
+        } else {
+          self.pc = r[rt].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+        }
+      }
+
+      /* BALC offset */
+      0x3A => {
+        r[Register::RA] = self.pc;
+        self.pc = self
+          .pc
+          .wrapping_add_signed(sign_ext((inst & 0x3ffffff) << 2, 28));
+      }
+
+      /* PCREL: ADDIUPC, LWPC, AUIPC, ALUIPC */
+      0x3B => {
+        let pc = self.pc.wrapping_sub(4);
+        let imm19 = sign_ext((inst & 0x7ffff) << 2, 21);
+        match (rt >> 3, rt) {
+          /* ADDIUPC $rs, imm */
+          (0x0, _) => r[rs] = pc.wrapping_add_signed(imm19),
+
+          /* LWPC $rs, imm */
+          (0x1, _) => {
+            let word = self.bus.read(pc.wrapping_add_signed(imm19), 32)?;
+            self.regs[rs] = word;
+          }
+
+          /* AUIPC $rs, imm */
+          (_, 0x1E) => r[rs] = pc.wrapping_add((inst & 0xffff) << 16),
+
+          /* ALUIPC $rs, imm */
+          (_, 0x1F) => r[rs] = pc.wrapping_add((inst & 0xffff) << 16) & !0xffff,
+
+          _ => interrupt_exception!(UNSUPPORTED(inst)),
+        }
+      }
+
+      /* POP76: BNEZC $rs, offset / JIALC $rt, offset */
+      0x3E => {
+        if rs != 0 {
+          let cond = r[rs] != 0;
+          self.compact_branch(cond, sign_ext((inst & 0x1fffff) << 2, 23), false);
+        } else {
+          let target = r[rt].wrapping_add_signed(sign_ext(inst & 0xffff, 16));
+          r[Register::RA] = self.pc;
+          self.pc = target;
+        }
+      }
+
+      _ => return Ok(false),
+    }
+
+    Ok(true)
+  }
+
+  /// Conditional compact branches have no delay slot; when not taken, the
+  /// following instruction sits in a forbidden slot and may not be a CTI.
+  fn compact_branch(&mut self, cond: bool, offset: i32, link: bool) {
+    if link {
+      self.regs[Register::RA] = self.pc;
+    }
+
+    if cond {
+      self.pc = self.pc.wrapping_add_signed(offset);
+    } else {
+      self.forbidden_slot = true;
+    }
+  }
+
+  fn is_control_transfer_r6(inst: u32) -> bool {
+    let opcode = (inst >> 26) & 0x3f;
+    let rt = (inst >> 16) & 0x1f;
+
+    match opcode {
+      0x00 => matches!(inst & 0x3f, 0x08 | 0x09),
+      0x01 => matches!(rt, 0x00 | 0x01 | 0x10 | 0x11),
+      0x02..=0x08 | 0x16..=0x18 | 0x32 | 0x36 | 0x3A | 0x3E => true,
+      _ => false,
+    }
+  }
+}
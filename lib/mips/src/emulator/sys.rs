@@ -1,66 +1,564 @@
 use std::{
+  collections::BTreeSet,
   fmt::Display,
   io::{Read, Write},
+  time::Instant,
 };
 
-use crate::interrupt_software;
+use crate::{interrupt_exception, interrupt_software};
+
+use super::{
+  arch::Register,
+  control::StopFlag,
+  cpu::{Cpu, StepOutcome},
+  decode,
+  dram::DRAM_SIZE,
+  interrupt::*,
+  limits::{LimitExceeded, LimitKind, Limits, CLOCK_INTERVAL},
+  vfs::{OpenMode, Vfs},
+  virt::{MemMap, MemRegion, Perm},
+};
 
-use super::{arch::Register, cpu::Cpu, interrupt::*};
+/// Initial program break: the heap occupies the upper half of DRAM.
+pub const HEAP_BASE: u32 = MemMap::HIGHMEM.base + (DRAM_SIZE / 2) as u32;
 
-pub struct Sys {
+/// Default maximum depth of the guest stack.
+pub const STACK_SIZE: u32 = 1024 * 1024 * 8; // 8 MiB
+
+/// Space MARS leaves between the top of the stack, where program argument
+/// strings go, and the initial `$sp`.
+pub const ARGS_GAP: u32 = 0x1000;
+
+/// The range `$sp` may move within. The stack grows down from `top`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stack {
+  /// Initial `$sp`
+  pub top: u32,
+  /// Lowest address `$sp` may reach
+  pub limit: u32,
+}
+
+impl Stack {
+  /// A stack of `size` bytes ending at the exclusive address `end`.
+  pub fn below(end: u32, size: u32) -> Stack {
+    Stack {
+      top: end - 4,
+      limit: end - size,
+    }
+  }
+
+  /// Whether `sp` points into this stack.
+  pub fn holds(&self, sp: u32) -> bool {
+    (self.limit..=self.top).contains(&sp)
+  }
+}
+
+/// The system call services available to the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syscalls {
+  /// The MARS services, a superset of SPIM's
+  #[default]
+  Mars,
+  /// Only the services SPIM provides (1-17)
+  Spim,
+  /// No services: every `syscall` is unsupported, as on bare hardware
+  Bare,
+}
+
+impl Syscalls {
+  pub fn supports(self, service: u32) -> bool {
+    match self {
+      Syscalls::Mars => true,
+      Syscalls::Spim => (0x01..=0x11).contains(&service),
+      Syscalls::Bare => false,
+    }
+  }
+}
+
+/// Machine state captured by [`Sys::snapshot`].
+///
+/// Memory pages are shared with the machine it was taken from and only
+/// copied when either side writes to them.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
   cpu: Cpu,
+  brk: u32,
+  stack: Stack,
+  stack_checked: bool,
+  retired: u64,
+  output: u64,
+  vfs: Vfs,
+  exit_code: Option<i32>,
+}
+
+pub struct Sys {
+  pub(crate) cpu: Cpu,
   running: bool,
   stdout: Box<dyn Write>,
   stdin: Box<dyn Read>,
-  exit_code: Option<i32>,
+  pub(crate) brk: u32,
+  pub(crate) stack: Stack,
+  pub(crate) stack_checked: bool,
+  syscalls: Syscalls,
+  limits: Limits,
+  pub(crate) retired: u64,
+  output: u64,
+  deadline: Option<Instant>,
+  stop: StopFlag,
+  pub(crate) vfs: Vfs,
+  pub(crate) exit_code: Option<i32>,
+  pub(crate) breakpoints: BTreeSet<u32>,
 }
 
 impl Sys {
-  pub fn new<O, I>(cpu: Cpu, stdout: O, stdin: I) -> Self
+  pub fn new<O, I>(mut cpu: Cpu, stdout: O, stdin: I) -> Self
   where
     O: 'static + Write,
     I: 'static + Read,
   {
-    Sys {
+    let stack = Stack::below(MemMap::HIGHMEM.base + cpu.bus.dram.size(), STACK_SIZE);
+    if cpu.regs[Register::SP] == 0 {
+      cpu.regs[Register::SP] = stack.top;
+    }
+    let stack_checked = stack.holds(cpu.regs[Register::SP]);
+
+    let mut sys = Sys {
       cpu,
       stdout: Box::new(stdout),
       stdin: Box::new(stdin),
       running: false,
+      brk: HEAP_BASE,
+      stack,
+      stack_checked,
+      syscalls: Syscalls::default(),
+      limits: Limits::default(),
+      retired: 0,
+      output: 0,
+      deadline: None,
+      stop: StopFlag::new(),
+      vfs: Vfs::new(),
       exit_code: None,
+      breakpoints: BTreeSet::new(),
+    };
+    sys.map_data();
+    sys
+  }
+
+  /// Maps the heap and stack as readable and writable, but not executable.
+  fn map_data(&mut self) {
+    let bus = &mut self.cpu.bus;
+    let end = MemMap::HIGHMEM.base + bus.dram.size();
+    bus
+      .mappings
+      .retain(|m| m.name != "heap" && m.name != "stack");
+
+    let heap = MemRegion {
+      base: HEAP_BASE,
+      size: self.stack.limit.saturating_sub(HEAP_BASE),
+    };
+    let stack = MemRegion {
+      base: self.stack.limit,
+      size: end - self.stack.limit,
+    };
+    bus.map("heap", heap, Perm::RW);
+    bus.map("stack", stack, Perm::RW);
+  }
+
+  pub fn stack(&self) -> Stack {
+    self.stack
+  }
+
+  /// Whether `$sp` is still checked against [`Sys::stack`]. The check is
+  /// dropped once the program points `$sp` somewhere else without deriving
+  /// it from its old value, as a C runtime setting up its own stack does,
+  /// and comes back with [`Sys::set_stack`].
+  pub fn stack_checked(&self) -> bool {
+    self.stack_checked
+  }
+
+  /// Moves the stack, resetting `$sp` to its new top.
+  pub fn set_stack(&mut self, stack: Stack) {
+    self.stack = stack;
+    self.stack_checked = true;
+    self.cpu.regs[Register::SP] = stack.top;
+    self.map_data();
+  }
+
+  /// Passes program arguments the way MARS does. The strings are packed
+  /// downwards from the top of the stack, first argument highest. `argc`
+  /// and the null-terminated `argv` array sit [`ARGS_GAP`] below the top,
+  /// or just under the strings if they are longer. `$sp` points at `argc`,
+  /// `$a0` holds argc and `$a1` argv.
+  ///
+  /// As in MARS, the stack is left untouched when there are no arguments.
+  pub fn set_args<S: AsRef<str>>(&mut self, args: &[S]) -> Result<()> {
+    if args.is_empty() {
+      return Ok(());
+    }
+
+    let bus = &mut self.cpu.bus;
+    let mut high = self.stack.top;
+    let mut argv = Vec::with_capacity(args.len());
+    for arg in args {
+      let bytes = arg.as_ref().as_bytes();
+      let start = high - bytes.len() as u32;
+      bus.write_bytes(high, &[0])?;
+      bus.write_bytes(start, bytes)?;
+      argv.push(start);
+      high = start - 1;
     }
+
+    let mut sp = self.stack.top - ARGS_GAP;
+    if high < sp {
+      sp = high - high % 4 - 4;
+    }
+    bus.dram.store(sp, 32, 0)?;
+    for &ptr in argv.iter().rev() {
+      sp -= 4;
+      bus.dram.store(sp, 32, ptr)?;
+    }
+    sp -= 4;
+    bus.dram.store(sp, 32, argv.len() as u32)?;
+
+    let r = &mut self.cpu.regs;
+    r[Register::SP] = sp;
+    r[Register::A0] = argv.len() as u32;
+    r[Register::A1] = sp + 4;
+    Ok(())
+  }
+
+  pub fn syscalls(&self) -> Syscalls {
+    self.syscalls
+  }
+
+  pub fn set_syscalls(&mut self, syscalls: Syscalls) {
+    self.syscalls = syscalls;
+  }
+
+  pub fn limits(&self) -> Limits {
+    self.limits
+  }
+
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.limits = limits;
+  }
+
+  /// A handle other threads can use to stop the machine between
+  /// instructions.
+  pub fn stop_flag(&self) -> StopFlag {
+    self.stop.clone()
+  }
+
+  pub fn set_stop_flag(&mut self, stop: StopFlag) {
+    self.stop = stop;
+  }
+
+  /// Instructions retired since the machine was created.
+  pub fn retired(&self) -> u64 {
+    self.retired
+  }
+
+  pub fn cpu(&self) -> &Cpu {
+    &self.cpu
+  }
+
+  pub fn cpu_mut(&mut self) -> &mut Cpu {
+    &mut self.cpu
+  }
+
+  pub fn vfs(&self) -> &Vfs {
+    &self.vfs
+  }
+
+  pub fn vfs_mut(&mut self) -> &mut Vfs {
+    &mut self.vfs
+  }
+
+  /// Current program break, as moved by the `sbrk` syscall.
+  pub fn brk(&self) -> u32 {
+    self.brk
+  }
+
+  pub fn exit_code(&self) -> Option<i32> {
+    self.exit_code
+  }
+
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      cpu: self.cpu.clone(),
+      brk: self.brk,
+      stack: self.stack,
+      stack_checked: self.stack_checked,
+      retired: self.retired,
+      output: self.output,
+      vfs: self.vfs.clone(),
+      exit_code: self.exit_code,
+    }
+  }
+
+  /// Rewinds to `snapshot`. Watchpoints are debugger state and survive.
+  pub fn restore(&mut self, snapshot: &Snapshot) {
+    self.replace_cpu(snapshot.cpu.clone());
+    self.brk = snapshot.brk;
+    self.stack = snapshot.stack;
+    self.stack_checked = snapshot.stack_checked;
+    self.retired = snapshot.retired;
+    self.output = snapshot.output;
+    self.vfs = snapshot.vfs.clone();
+    self.exit_code = snapshot.exit_code;
+  }
+
+  /// Puts `cpu` in place of the current processor, as when rewinding to a
+  /// snapshot or checkpoint, keeping the debugger's watchpoints and the
+  /// ids it hands out for new ones.
+  pub(crate) fn replace_cpu(&mut self, cpu: Cpu) {
+    let watchpoints = std::mem::take(&mut self.cpu.bus.watchpoints);
+    let next_watch = self.cpu.bus.next_watch;
+    self.cpu = cpu;
+    self.cpu.bus.watchpoints = watchpoints;
+    self.cpu.bus.next_watch = next_watch;
+    self.running = false;
+  }
+
+  /// Creates an independent machine in the current state, attached to new
+  /// I/O streams.
+  pub fn fork<O, I>(&self, stdout: O, stdin: I) -> Sys
+  where
+    O: 'static + Write,
+    I: 'static + Read,
+  {
+    let mut sys = Sys::new(self.cpu.clone(), stdout, stdin);
+    sys.brk = self.brk;
+    sys.stack = self.stack;
+    sys.stack_checked = self.stack_checked;
+    sys.syscalls = self.syscalls;
+    sys.limits = self.limits;
+    sys.retired = self.retired;
+    sys.output = self.output;
+    sys.map_data();
+    sys.vfs = self.vfs.clone();
+    sys.exit_code = self.exit_code;
+    sys
   }
 
   fn write<S>(&mut self, str: S) -> Result<()>
   where
     S: Display,
   {
-    write!(self.stdout, "{}", str)
-      .map_err(|e| Interrupt::Software(SoftwareInterrupt::STDOUT(e.to_string())))
+    self.write_bytes(str.to_string().as_bytes())
+  }
+
+  /// Writes guest output, cutting it short at the output limit.
+  fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+    let allowed = match self.limits.output {
+      Some(limit) => bytes.len().min(limit.saturating_sub(self.output) as usize),
+      None => bytes.len(),
+    };
+    self
+      .stdout
+      .write_all(&bytes[..allowed])
+      .map_err(|e| Interrupt::Software(SoftwareInterrupt::STDOUT(e.to_string())))?;
+    self.output += allowed as u64;
+
+    match self.limits.output {
+      Some(limit) if allowed < bytes.len() => self.limit_exceeded(LimitKind::Output(limit)),
+      _ => Ok(()),
+    }
+  }
+
+  fn read_bytes(&mut self, max: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0; max];
+    let n = self
+      .stdin
+      .read(&mut buf)
+      .map_err(|e| Interrupt::Software(SoftwareInterrupt::STDIN(e.to_string())))?;
+    buf.truncate(n);
+    Ok(buf)
+  }
+
+  /// Reads up to and including the next newline, but no more than `max`
+  /// bytes, so input after the line is left for the next read.
+  fn read_line(&mut self, max: usize) -> Result<Vec<u8>> {
+    let mut line = Vec::new();
+    while line.len() < max && line.last() != Some(&b'\n') {
+      match self.read_bytes(1)?.first() {
+        Some(&byte) => line.push(byte),
+        None => break,
+      }
+    }
+    Ok(line)
   }
 
+  /// Reads a NUL-terminated string from guest memory.
+  fn load_cstring(&self, addr: u32) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    loop {
+      let byte = self
+        .cpu
+        .bus
+        .load(addr.wrapping_add(bytes.len() as u32), 8)? as u8;
+      if byte == 0 {
+        return Ok(bytes);
+      }
+      bytes.push(byte);
+    }
+  }
+
+  fn load_block(&self, addr: u32, len: u32) -> Result<Vec<u8>> {
+    (0..len)
+      .map(|i| self.cpu.bus.load(addr.wrapping_add(i), 8).map(|b| b as u8))
+      .collect()
+  }
+
+  fn store_block(&mut self, addr: u32, bytes: &[u8]) -> Result<()> {
+    for (i, &byte) in bytes.iter().enumerate() {
+      self
+        .cpu
+        .bus
+        .store(addr.wrapping_add(i as u32), 8, byte as u32)?;
+    }
+    Ok(())
+  }
+
+  /// Runs the program to completion. The exit status is available from
+  /// [`Sys::exit_code`] afterwards.
   pub fn run(&mut self) -> Result<()> {
+    self.start_clock();
+    let res = loop {
+      match self.tick() {
+        Ok(true) => break Ok(()),
+        Ok(false) => {}
+        Err(err) => break Err(err),
+      }
+    };
+    self.stop_clock();
+    res
+  }
+
+  /// Starts the wall-clock limit over for a new run.
+  pub(crate) fn start_clock(&mut self) {
+    self.deadline = self.limits.time.map(|time| Instant::now() + time);
+  }
+
+  pub(crate) fn clock_running(&self) -> bool {
+    self.deadline.is_some()
+  }
+
+  /// Ends the current run for the purpose of the time limit, so the next
+  /// one gets the full time again. Only needed after [`Sys::cont_for`],
+  /// which leaves the clock running between polls.
+  pub fn stop_clock(&mut self) {
+    self.deadline = None;
+  }
+
+  fn limit_exceeded(&self, kind: LimitKind) -> Result<()> {
+    interrupt_software!(LIMIT(LimitExceeded {
+      kind,
+      pc: self.cpu.pc,
+    }))
+  }
+
+  /// Stops the machine if it has retired its budget of instructions or,
+  /// every [`CLOCK_INTERVAL`] instructions, if its time is up.
+  fn check_limits(&self) -> Result<()> {
+    if let Some(n) = self.limits.instructions {
+      if self.retired >= n {
+        return self.limit_exceeded(LimitKind::Instructions(n));
+      }
+    }
+    if let (Some(time), Some(deadline)) = (self.limits.time, self.deadline) {
+      if self.retired.is_multiple_of(CLOCK_INTERVAL) && Instant::now() >= deadline {
+        return self.limit_exceeded(LimitKind::Time(time));
+      }
+    }
+    Ok(())
+  }
+
+  /// Executes one instruction, servicing any system call it makes.
+  /// Returns whether the program has finished.
+  pub(crate) fn tick(&mut self) -> Result<bool> {
     self.running = true;
 
-    while self.running {
-      match self.cpu.step() {
-        Ok(done) => self.running = !done,
-        Err(err) => match err {
-          Interrupt::Software(SoftwareInterrupt::SYSCALL) => self.handle_syscall()?,
-          _ => return Err(err),
-        },
+    let pc = self.cpu.pc;
+    let sp = self.cpu.regs[Register::SP];
+    let watched = match self.cpu.step() {
+      StepOutcome::Retired => None,
+      StepOutcome::Halted => {
+        self.running = false;
+        return Ok(true);
+      }
+      StepOutcome::Syscall => {
+        self.handle_syscall()?;
+        self.cpu.bus.take_watch_hit(pc)
       }
+      StepOutcome::Break(code) => interrupt_exception!(BREAK(code)),
+      StepOutcome::Trap => interrupt_exception!(TRAP),
+      StepOutcome::Exception { kind, .. } => return Err(Interrupt::Exception(kind)),
+      StepOutcome::Watch(hit) => Some(hit),
+      StepOutcome::Interrupted { err, .. } => return Err(err),
+    };
+
+    /* A stack fault outranks the watchpoint on the same instruction */
+    self.check_stack(pc, sp)?;
+    if let Some(hit) = watched {
+      interrupt_software!(WATCH(hit))
     }
+    self.retired += 1;
 
-    self.write(format!(
-      "\nProcess exited with code {}\n",
-      self.exit_code.unwrap_or(0)
-    ))?;
+    /* Limits only stop a program that has more instructions to run */
+    if self.running && !self.cpu.halts_at(self.cpu.pc) {
+      self.check_limits()?;
+      if self.stop.take() {
+        interrupt_software!(STOPPED(self.cpu.pc))
+      }
+    }
 
-    Ok(())
+    Ok(!self.running)
+  }
+
+  /// Stops the machine if the instruction at `pc` moved `$sp` below the
+  /// stack limit or into the heap. An instruction that points `$sp` outside
+  /// the stack without reading it hands the stack over to the program,
+  /// which turns the check off.
+  fn check_stack(&mut self, pc: u32, old_sp: u32) -> Result<()> {
+    if !self.stack_checked {
+      return Ok(());
+    }
+
+    let sp = self.cpu.regs[Register::SP];
+    let inst = self.cpu.bus.load(pc, 32).unwrap_or(0);
+    if sp != old_sp
+      && !self.stack.holds(sp)
+      && !decode::src_regs(inst).contains(&Some(Register::SP))
+    {
+      self.stack_checked = false;
+      return Ok(());
+    }
+
+    let kind = if sp < self.brk {
+      StackFaultKind::HeapCollision
+    } else if sp < self.stack.limit {
+      StackFaultKind::Overflow
+    } else {
+      return Ok(());
+    };
+
+    interrupt_software!(STACK(StackFault {
+      kind,
+      sp,
+      depth: self.stack.top.wrapping_sub(sp),
+      pc,
+      inst,
+    }))
   }
 
   pub fn handle_syscall(&mut self) -> Result<()> {
     let r = &mut self.cpu.regs;
+    if !self.syscalls.supports(r[Register::V0]) {
+      interrupt_software!(UNSUPPORTED(r[Register::V0]))
+    }
+
     match r[Register::V0] {
       /* Print Integer */
       0x01 => {
@@ -92,7 +590,14 @@ impl Sys {
       }
 
       /* Read Integer */
-      0x05 => {}
+      0x05 => {
+        let line = self.read_line(usize::MAX)?;
+        let text = String::from_utf8_lossy(&line);
+        let Ok(n) = text.trim().parse::<i32>() else {
+          interrupt_software!(STDIN(format!("Not an integer: {:?}", text.trim_end())))
+        };
+        self.cpu.regs[Register::V0] = n as u32;
+      }
 
       /* Read Float */
       0x06 => {}
@@ -101,10 +606,29 @@ impl Sys {
       0x07 => {}
 
       /* Read String */
-      0x08 => {}
+      0x08 => {
+        /* At most $a1 - 1 bytes, keeping the newline if it fits */
+        let (buf, max) = (r[Register::A0], r[Register::A1] as i32);
+        if max > 0 {
+          let mut line = self.read_line(max as usize - 1)?;
+          line.push(0);
+          self.store_block(buf, &line)?;
+        }
+      }
 
       /* SBRK (allocate heap memory) */
-      0x09 => {}
+      0x09 => {
+        let bytes = r[Register::A0];
+        let size = bytes.wrapping_add(3) & !3;
+        /* The heap may grow up to, but not into, the live stack */
+        let end = r[Register::SP].min(MemMap::HIGHMEM.base + self.cpu.bus.dram.size());
+        if (bytes as i32) < 0 || self.brk as u64 + size as u64 > end as u64 {
+          interrupt_software!(HEAP(bytes))
+        }
+
+        r[Register::V0] = self.brk;
+        self.brk += size;
+      }
 
       /* Exit (terminate execution) */
       0x0A => self.running = false,
@@ -113,19 +637,57 @@ impl Sys {
       0x0B => {}
 
       /* Read Character */
-      0x0C => {}
+      0x0C => {
+        let Some(&c) = self.read_bytes(1)?.first() else {
+          interrupt_software!(STDIN("End of input".to_string()))
+        };
+        self.cpu.regs[Register::V0] = c as u32;
+      }
 
       /* Open File */
-      0x0D => {}
+      0x0D => {
+        let (addr, flags) = (r[Register::A0], r[Register::A1]);
+        let path = String::from_utf8_lossy(&self.load_cstring(addr)?).into_owned();
+        let fd = OpenMode::from_flags(flags).and_then(|mode| self.vfs.open(&path, mode));
+        self.cpu.regs[Register::V0] = fd.map_or(-1, |fd| fd as i32) as u32;
+      }
 
       /* Read From File */
-      0x0E => {}
+      0x0E => {
+        let (fd, buf, max) = (r[Register::A0], r[Register::A1], r[Register::A2]);
+        let data = match fd {
+          0 => Some(self.read_bytes(max as usize)?),
+          _ => self.vfs.read(fd, max as usize),
+        };
+        let n = match data {
+          Some(data) => {
+            self.store_block(buf, &data)?;
+            data.len() as i32
+          }
+          None => -1,
+        };
+        self.cpu.regs[Register::V0] = n as u32;
+      }
 
       /* Write To File */
-      0x0F => {}
+      0x0F => {
+        let (fd, buf, len) = (r[Register::A0], r[Register::A1], r[Register::A2]);
+        let data = self.load_block(buf, len)?;
+        let n = match fd {
+          1 | 2 => {
+            self.write_bytes(&data)?;
+            Some(data.len())
+          }
+          _ => self.vfs.write(fd, &data),
+        };
+        self.cpu.regs[Register::V0] = n.map_or(-1, |n| n as i32) as u32;
+      }
 
       /* Close File */
-      0x10 => {}
+      0x10 => {
+        let fd = r[Register::A0];
+        self.vfs.close(fd);
+      }
 
       /* Exit2 (terminate with value) */
       0x11 => {
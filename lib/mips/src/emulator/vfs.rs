@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+/// Descriptors below this are reserved for stdin, stdout and stderr.
+pub const FIRST_FD: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpenMode {
+  Read,
+  Write,
+  Append,
+}
+
+impl OpenMode {
+  /// Decodes the MARS `open` flags (0 = read, 1 = write, 9 = append).
+  pub fn from_flags(flags: u32) -> Option<OpenMode> {
+    match flags {
+      0 => Some(OpenMode::Read),
+      1 => Some(OpenMode::Write),
+      9 => Some(OpenMode::Append),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Handle {
+  path: String,
+  mode: OpenMode,
+  pos: usize,
+}
+
+/// In-memory file system backing the guest's file syscalls, so programs
+/// never touch the host file system.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vfs {
+  files: BTreeMap<String, Vec<u8>>,
+  handles: BTreeMap<u32, Handle>,
+}
+
+impl Vfs {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn insert<P, C>(&mut self, path: P, contents: C)
+  where
+    P: Into<String>,
+    C: Into<Vec<u8>>,
+  {
+    self.files.insert(path.into(), contents.into());
+  }
+
+  pub fn get(&self, path: &str) -> Option<&[u8]> {
+    self.files.get(path).map(Vec::as_slice)
+  }
+
+  pub fn open(&mut self, path: &str, mode: OpenMode) -> Option<u32> {
+    match mode {
+      OpenMode::Read if !self.files.contains_key(path) => return None,
+      OpenMode::Read => {}
+      OpenMode::Write => {
+        self.files.insert(path.to_string(), Vec::new());
+      }
+      OpenMode::Append => {
+        self.files.entry(path.to_string()).or_default();
+      }
+    }
+
+    let fd = (FIRST_FD..).find(|fd| !self.handles.contains_key(fd))?;
+    self.handles.insert(
+      fd,
+      Handle {
+        path: path.to_string(),
+        mode,
+        pos: 0,
+      },
+    );
+
+    Some(fd)
+  }
+
+  pub fn read(&mut self, fd: u32, max: usize) -> Option<Vec<u8>> {
+    let handle = self.handles.get_mut(&fd)?;
+    if handle.mode != OpenMode::Read {
+      return None;
+    }
+
+    let file = self.files.get(&handle.path)?;
+    let start = handle.pos.min(file.len());
+    let end = (start + max).min(file.len());
+    handle.pos = end;
+
+    Some(file[start..end].to_vec())
+  }
+
+  pub fn write(&mut self, fd: u32, data: &[u8]) -> Option<usize> {
+    let handle = self.handles.get(&fd)?;
+    if handle.mode == OpenMode::Read {
+      return None;
+    }
+
+    self.files.get_mut(&handle.path)?.extend_from_slice(data);
+    Some(data.len())
+  }
+
+  pub fn close(&mut self, fd: u32) -> bool {
+    self.handles.remove(&fd).is_some()
+  }
+
+  pub fn open_handles(&self) -> usize {
+    self.handles.len()
+  }
+}
@@ -17,7 +17,79 @@ impl MemMap {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemRegion {
   pub base: u32,
   pub size: u32,
 }
+
+impl MemRegion {
+  pub fn contains(&self, addr: u32) -> bool {
+    addr.wrapping_sub(self.base) < self.size
+  }
+
+  pub fn end(&self) -> u32 {
+    self.base.wrapping_add(self.size)
+  }
+}
+
+/// Access rights on a range of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Perm(u8);
+
+impl Perm {
+  pub const NONE: Perm = Perm(0);
+  pub const R: Perm = Perm(0b100);
+  pub const W: Perm = Perm(0b010);
+  pub const X: Perm = Perm(0b001);
+  pub const RW: Perm = Perm(0b110);
+  pub const RX: Perm = Perm(0b101);
+  pub const RWX: Perm = Perm(0b111);
+
+  pub fn allows(self, access: Access) -> bool {
+    let needed = match access {
+      Access::Read => Perm::R,
+      Access::Write => Perm::W,
+      Access::Execute => Perm::X,
+    };
+    self.0 & needed.0 != 0
+  }
+}
+
+impl std::ops::BitOr for Perm {
+  type Output = Perm;
+
+  fn bitor(self, rhs: Perm) -> Perm {
+    Perm(self.0 | rhs.0)
+  }
+}
+
+impl std::fmt::Display for Perm {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let flag = |perm: Perm, c: char| if self.0 & perm.0 != 0 { c } else { '-' };
+    write!(
+      f,
+      "{}{}{}",
+      flag(Perm::R, 'r'),
+      flag(Perm::W, 'w'),
+      flag(Perm::X, 'x')
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+  Read,
+  Write,
+  Execute,
+}
+
+/// A named range of memory with its own access rights.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mapping {
+  pub name: String,
+  pub region: MemRegion,
+  pub perm: Perm,
+}
@@ -0,0 +1,96 @@
+use std::{fmt::Display, sync::Arc};
+
+use super::virt::{Access, MemRegion};
+
+/// Which accesses trigger a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+  Read,
+  Write,
+  ReadWrite,
+}
+
+impl WatchKind {
+  pub fn matches(self, access: Access) -> bool {
+    matches!(
+      (self, access),
+      (WatchKind::Read | WatchKind::ReadWrite, Access::Read)
+        | (WatchKind::Write | WatchKind::ReadWrite, Access::Write)
+    )
+  }
+}
+
+pub type WatchCallback = Arc<dyn Fn(&WatchHit) + Send + Sync>;
+
+/// What happens when a watchpoint triggers.
+#[derive(Clone)]
+pub enum WatchAction {
+  /// Stop execution after the accessing instruction completes
+  Pause,
+  /// Report the hit and keep running
+  Callback(WatchCallback),
+}
+
+impl std::fmt::Debug for WatchAction {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      WatchAction::Pause => write!(f, "Pause"),
+      WatchAction::Callback(_) => write!(f, "Callback(..)"),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+  pub id: usize,
+  pub region: MemRegion,
+  pub kind: WatchKind,
+  pub action: WatchAction,
+}
+
+impl Watchpoint {
+  /// Whether a `size`-bit `access` at `addr` touches any watched byte.
+  pub fn triggers(&self, addr: u32, size: u32, access: Access) -> bool {
+    let bytes = (size / 8).max(1) as u64;
+    let (start, end) = (
+      self.region.base as u64,
+      self.region.base as u64 + self.region.size as u64,
+    );
+    self.kind.matches(access) && (addr as u64) < end && addr as u64 + bytes > start
+  }
+}
+
+/// A single access that triggered a watchpoint. For reads, `old` and `new`
+/// are both the value read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchHit {
+  pub id: usize,
+  /// Address of the accessing instruction
+  pub pc: u32,
+  pub addr: u32,
+  /// Access size in bits
+  pub size: u32,
+  pub access: Access,
+  pub old: u32,
+  pub new: u32,
+}
+
+impl Display for WatchHit {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let what = match self.access {
+      Access::Write => "Write",
+      _ => "Read",
+    };
+    write!(
+      f,
+      "Watchpoint {}: {} of {} bytes at {:#010X} by {:#010X} ({:#X} -> {:#X})",
+      self.id,
+      what,
+      self.size / 8,
+      self.addr,
+      self.pc,
+      self.old,
+      self.new
+    )
+  }
+}
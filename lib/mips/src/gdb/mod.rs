@@ -0,0 +1,409 @@
+//! A GDB Remote Serial Protocol stub for debugging guest programs with
+//! `gdb-multiarch` or an IDE frontend.
+
+pub mod packet;
+pub mod target;
+
+use std::{
+  collections::HashMap,
+  io,
+  net::{TcpListener, ToSocketAddrs},
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixListener, path::Path};
+
+use crate::emulator::{
+  arch::Endian,
+  debug::StopReason,
+  interrupt::{ExceptionInterrupt, Interrupt, SoftwareInterrupt},
+  sys::Sys,
+  virt::MemRegion,
+  watch::WatchKind,
+};
+
+pub use packet::Connection;
+use packet::{from_hex, parse_hex, to_hex, Channel, Incoming};
+use target::{read_reg, target_xml, write_reg, NUM_REGS};
+
+/// Instructions executed between checks for an interrupt from gdb.
+const POLL_INTERVAL: u64 = 4096;
+
+/* Signal numbers as gdb defines them */
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGABRT: u8 = 6;
+const SIGFPE: u8 = 8;
+const SIGBUS: u8 = 10;
+const SIGSEGV: u8 = 11;
+
+enum Action {
+  Reply(String),
+  ReplyAndClose(String),
+  Close,
+}
+
+/// Serves one gdb session over `C`, driving the machine through the
+/// breakpoint and stepping API of [`Sys`].
+pub struct GdbStub<C> {
+  chan: Channel<C>,
+  sys: Sys,
+  /// Watchpoints inserted by gdb, keyed by packet type, address and length
+  watches: HashMap<(u8, u32, u32), usize>,
+  /// Breakpoints inserted by gdb, with their packet type
+  breaks: HashMap<u32, u8>,
+  swbreak: bool,
+}
+
+/// Waits for gdb to connect on `addr`, then serves it until it detaches.
+pub fn serve_tcp<A: ToSocketAddrs>(addr: A, sys: Sys) -> io::Result<Sys> {
+  let (stream, _) = TcpListener::bind(addr)?.accept()?;
+  stream.set_nodelay(true)?;
+  GdbStub::new(stream, sys).serve()
+}
+
+/// Waits for gdb to connect on the socket at `path`, then serves it until
+/// it detaches.
+#[cfg(unix)]
+pub fn serve_unix<P: AsRef<Path>>(path: P, sys: Sys) -> io::Result<Sys> {
+  let (stream, _) = UnixListener::bind(path)?.accept()?;
+  GdbStub::new(stream, sys).serve()
+}
+
+impl<C: Connection> GdbStub<C> {
+  pub fn new(conn: C, sys: Sys) -> Self {
+    GdbStub {
+      chan: Channel::new(conn),
+      sys,
+      watches: HashMap::new(),
+      breaks: HashMap::new(),
+      swbreak: false,
+    }
+  }
+
+  /// Serves requests until gdb detaches, kills the target or disconnects,
+  /// then hands the machine back.
+  pub fn serve(mut self) -> io::Result<Sys> {
+    while let Some(incoming) = self.chan.recv()? {
+      /* An interrupt while stopped has nothing to stop */
+      let Incoming::Packet(packet) = incoming else {
+        continue;
+      };
+
+      match self.handle(&packet)? {
+        Action::Reply(reply) => self.chan.send(reply.as_bytes())?,
+        Action::ReplyAndClose(reply) => {
+          self.chan.send(reply.as_bytes())?;
+          break;
+        }
+        Action::Close => break,
+      }
+
+      /* Acknowledgements stop after the reply to this packet */
+      if packet == b"QStartNoAckMode" {
+        self.chan.ack = false;
+      }
+    }
+
+    for id in self.watches.drain().map(|(_, id)| id) {
+      self.sys.cpu_mut().bus.unwatch(id);
+    }
+    for addr in self.breaks.drain().map(|(addr, _)| addr) {
+      self.sys.clear_breakpoint(addr);
+    }
+    Ok(self.sys)
+  }
+
+  fn handle(&mut self, packet: &[u8]) -> io::Result<Action> {
+    let Ok(packet) = std::str::from_utf8(packet) else {
+      return Ok(Action::Reply(String::new()));
+    };
+    let (cmd, args) = packet.split_at(packet.len().min(1));
+
+    let reply = match cmd {
+      "?" => format!("S{:02x}", SIGTRAP),
+      "g" => self.read_regs(),
+      "G" => self.write_regs(args),
+      "p" => self.read_one_reg(args),
+      "P" => self.write_one_reg(args),
+      "m" => self.read_mem(args),
+      "M" => self.write_mem(args),
+      "c" | "s" => {
+        self.set_resume_addr(args);
+        self.resume(cmd == "s")?
+      }
+      /* The signal to deliver is ignored */
+      "C" | "S" => {
+        self.set_resume_addr(args.split_once(';').map_or("", |(_, addr)| addr));
+        self.resume(cmd == "S")?
+      }
+      "Z" | "z" => self.toggle_point(cmd == "Z", args),
+      "H" | "T" => "OK".into(),
+      "D" => return Ok(Action::ReplyAndClose("OK".into())),
+      "k" => return Ok(Action::Close),
+      "q" | "Q" | "v" => return self.handle_query(packet),
+      _ => String::new(),
+    };
+
+    Ok(Action::Reply(reply))
+  }
+
+  fn handle_query(&mut self, packet: &str) -> io::Result<Action> {
+    const TARGET_XML: &str = "qXfer:features:read:target.xml:";
+
+    let reply = match packet {
+      _ if packet.starts_with("qSupported") => {
+        self.swbreak = packet.contains("swbreak+");
+        "PacketSize=4000;qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+".into()
+      }
+      _ if packet.starts_with(TARGET_XML) => read_xfer(&target_xml(), &packet[TARGET_XML.len()..]),
+      "QStartNoAckMode" => "OK".into(),
+      "qAttached" => "1".into(),
+      "qC" => "QC1".into(),
+      "qfThreadInfo" => "m1".into(),
+      "qsThreadInfo" => "l".into(),
+      "qSymbol::" => "OK".into(),
+      "vCont?" => "vCont;c;C;s;S".into(),
+      _ if packet.starts_with("vCont;") => {
+        let step = packet["vCont;".len()..].starts_with(['s', 'S']);
+        self.resume(step)?
+      }
+      _ if packet.starts_with("vKill") => return Ok(Action::ReplyAndClose("OK".into())),
+      _ => String::new(),
+    };
+
+    Ok(Action::Reply(reply))
+  }
+
+  /// Encodes a register in the byte order of the target.
+  fn encode_reg(&self, value: u32) -> String {
+    match self.sys.cpu().bus.dram.endian {
+      Endian::Little => to_hex(&value.to_le_bytes()),
+      Endian::Big => to_hex(&value.to_be_bytes()),
+    }
+  }
+
+  fn decode_reg(&self, hex: &[u8]) -> Option<u32> {
+    let bytes: [u8; 4] = from_hex(hex)?.try_into().ok()?;
+    match self.sys.cpu().bus.dram.endian {
+      Endian::Little => Some(u32::from_le_bytes(bytes)),
+      Endian::Big => Some(u32::from_be_bytes(bytes)),
+    }
+  }
+
+  fn read_regs(&self) -> String {
+    (0..NUM_REGS)
+      .map(|n| self.encode_reg(read_reg(self.sys.cpu(), n).unwrap_or(0)))
+      .collect()
+  }
+
+  fn write_regs(&mut self, args: &str) -> String {
+    for (n, hex) in args.as_bytes().chunks(8).enumerate().take(NUM_REGS) {
+      match self.decode_reg(hex) {
+        Some(value) => write_reg(self.sys.cpu_mut(), n, value),
+        None => return "E01".into(),
+      };
+    }
+    "OK".into()
+  }
+
+  fn read_one_reg(&self, args: &str) -> String {
+    parse_hex(args.as_bytes())
+      .and_then(|n| read_reg(self.sys.cpu(), n as usize))
+      .map_or("E01".into(), |value| self.encode_reg(value))
+  }
+
+  fn write_one_reg(&mut self, args: &str) -> String {
+    let written = args.split_once('=').and_then(|(n, value)| {
+      let n = parse_hex(n.as_bytes())? as usize;
+      let value = self.decode_reg(value.as_bytes())?;
+      Some(write_reg(self.sys.cpu_mut(), n, value))
+    });
+
+    match written {
+      Some(true) => "OK".into(),
+      _ => "E01".into(),
+    }
+  }
+
+  fn read_mem(&self, args: &str) -> String {
+    let Some((addr, len)) = parse_addr_len(args) else {
+      return "E01".into();
+    };
+
+    /* Stop at the first unreadable byte; gdb accepts a short read */
+    let bytes: Vec<u8> = (0..len)
+      .map_while(|i| {
+        let value = self.sys.cpu().bus.load(addr.wrapping_add(i), 8).ok()?;
+        Some(value as u8)
+      })
+      .collect();
+
+    match bytes.is_empty() && len > 0 {
+      true => "E14".into(),
+      false => to_hex(&bytes),
+    }
+  }
+
+  fn write_mem(&mut self, args: &str) -> String {
+    let parsed = args.split_once(':').and_then(|(range, data)| {
+      let (addr, len) = parse_addr_len(range)?;
+      let bytes = from_hex(data.as_bytes())?;
+      (bytes.len() == len as usize).then_some((addr, bytes))
+    });
+    let Some((addr, bytes)) = parsed else {
+      return "E01".into();
+    };
+
+    match self.sys.cpu_mut().bus.write_bytes(addr, &bytes) {
+      Ok(()) => "OK".into(),
+      Err(_) => "E14".into(),
+    }
+  }
+
+  fn set_resume_addr(&mut self, args: &str) {
+    if let Some(addr) = parse_hex(args.as_bytes()) {
+      self.sys.cpu_mut().pc = addr;
+    }
+  }
+
+  /// Steps or continues, returning the stop reply. A continue checks for
+  /// an interrupt from gdb every [`POLL_INTERVAL`] instructions.
+  fn resume(&mut self, step: bool) -> io::Result<String> {
+    let res = loop {
+      if step {
+        break self.sys.step();
+      }
+
+      match self.sys.cont_for(POLL_INTERVAL) {
+        Ok(StopReason::Step) if self.chan.interrupted()? => {
+          self.sys.stop_clock();
+          return Ok(format!("S{:02x}", SIGINT));
+        }
+        Ok(StopReason::Step) => continue,
+        res => break res,
+      }
+    };
+
+    match res {
+      Ok(reason) => Ok(self.stop_reply(reason)),
+      Err(err) => {
+        /* Show the reason in the gdb console before reporting the signal */
+        let message = format!("{}\n", err);
+        self
+          .chan
+          .send(format!("O{}", to_hex(message.as_bytes())).as_bytes())?;
+        Ok(format!("S{:02x}", signal(&err)))
+      }
+    }
+  }
+
+  fn stop_reply(&self, reason: StopReason) -> String {
+    match reason {
+      StopReason::Exited(code) => format!("W{:02x}", code as u8),
+      StopReason::Breakpoint(addr) if self.breaks.get(&addr) == Some(&1) => {
+        format!("T{:02x}hwbreak:;", SIGTRAP)
+      }
+      StopReason::Breakpoint(_) if self.swbreak => format!("T{:02x}swbreak:;", SIGTRAP),
+      StopReason::Watchpoint(hit) => {
+        let kind =
+          self
+            .watches
+            .iter()
+            .find(|(_, &id)| id == hit.id)
+            .map_or("awatch", |(&(kind, ..), _)| match kind {
+              2 => "watch",
+              3 => "rwatch",
+              _ => "awatch",
+            });
+        format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.addr)
+      }
+      _ => format!("S{:02x}", SIGTRAP),
+    }
+  }
+
+  /// Handles `Z`/`z` packets: breakpoints of type 0 and 1, and write, read
+  /// and access watchpoints of types 2 to 4.
+  fn toggle_point(&mut self, insert: bool, args: &str) -> String {
+    let mut fields = args.split(',');
+    let (Some(kind), Some(addr), Some(len)) = (
+      fields.next().and_then(|k| k.parse::<u8>().ok()),
+      fields.next().and_then(|a| parse_hex(a.as_bytes())),
+      fields.next().and_then(|l| parse_hex(l.as_bytes())),
+    ) else {
+      return "E01".into();
+    };
+
+    let watch = match kind {
+      0 | 1 => {
+        if insert {
+          self.sys.set_breakpoint(addr);
+          self.breaks.insert(addr, kind);
+        } else if self.breaks.remove(&addr).is_some() {
+          self.sys.clear_breakpoint(addr);
+        }
+        return "OK".into();
+      }
+      2 => WatchKind::Write,
+      3 => WatchKind::Read,
+      4 => WatchKind::ReadWrite,
+      _ => return String::new(),
+    };
+
+    let key = (kind, addr, len);
+    let bus = &mut self.sys.cpu_mut().bus;
+    if insert {
+      let id = bus.watch(
+        MemRegion {
+          base: addr,
+          size: len,
+        },
+        watch,
+      );
+      if let Some(old) = self.watches.insert(key, id) {
+        bus.unwatch(old);
+      }
+    } else if let Some(id) = self.watches.remove(&key) {
+      bus.unwatch(id);
+    }
+    "OK".into()
+  }
+}
+
+/// Replies to a `qXfer` read of `offset,length` within `data`.
+fn read_xfer(data: &str, range: &str) -> String {
+  let Some((offset, len)) = parse_addr_len(range) else {
+    return "E01".into();
+  };
+
+  let start = (offset as usize).min(data.len());
+  let end = start.saturating_add(len as usize).min(data.len());
+  let more = if end < data.len() { 'm' } else { 'l' };
+  format!("{}{}", more, &data[start..end])
+}
+
+fn parse_addr_len(args: &str) -> Option<(u32, u32)> {
+  let (addr, len) = args.split_once(',')?;
+  Some((parse_hex(addr.as_bytes())?, parse_hex(len.as_bytes())?))
+}
+
+/// The signal reported to gdb for an error that stopped the machine.
+fn signal(err: &Interrupt) -> u8 {
+  match err {
+    Interrupt::Exception(exception) => match exception {
+      ExceptionInterrupt::ADDRL(_)
+      | ExceptionInterrupt::ADDRS(_)
+      | ExceptionInterrupt::ALIGNMENT(_)
+      | ExceptionInterrupt::PROTECTION(_) => SIGSEGV,
+      ExceptionInterrupt::IBUS(_) | ExceptionInterrupt::DBUS(_) => SIGBUS,
+      ExceptionInterrupt::OVF => SIGFPE,
+      ExceptionInterrupt::BREAK(_) | ExceptionInterrupt::TRAP => SIGTRAP,
+      ExceptionInterrupt::UNDEFINED
+      | ExceptionInterrupt::UNSUPPORTED(_)
+      | ExceptionInterrupt::FORBIDDEN(_) => SIGILL,
+    },
+    Interrupt::Software(SoftwareInterrupt::STACK(_)) => SIGSEGV,
+    _ => SIGABRT,
+  }
+}
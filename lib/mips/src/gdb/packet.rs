@@ -0,0 +1,185 @@
+use std::{
+  io::{self, ErrorKind, Read, Write},
+  net::TcpStream,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+/// A byte stream to a debugger.
+pub trait Connection: Read + Write {
+  /// Reads a byte if one has arrived, without blocking.
+  fn poll_byte(&mut self) -> io::Result<Option<u8>>;
+}
+
+impl Connection for TcpStream {
+  fn poll_byte(&mut self) -> io::Result<Option<u8>> {
+    poll_nonblocking(self, |s, on| s.set_nonblocking(on))
+  }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+  fn poll_byte(&mut self) -> io::Result<Option<u8>> {
+    poll_nonblocking(self, |s, on| s.set_nonblocking(on))
+  }
+}
+
+fn poll_nonblocking<S, F>(stream: &mut S, set_nonblocking: F) -> io::Result<Option<u8>>
+where
+  S: Read,
+  F: Fn(&S, bool) -> io::Result<()>,
+{
+  set_nonblocking(stream, true)?;
+  let mut byte = [0];
+  let res = match stream.read(&mut byte) {
+    Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+    Ok(_) => Ok(Some(byte[0])),
+    Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+    Err(e) => Err(e),
+  };
+  set_nonblocking(stream, false)?;
+  res
+}
+
+/// The break character gdb sends to interrupt a running target.
+pub(crate) const INTERRUPT: u8 = 0x03;
+
+pub(crate) enum Incoming {
+  Packet(Vec<u8>),
+  Interrupt,
+}
+
+/// Frames packets as `$data#checksum`, acknowledging each one until the
+/// debugger switches acknowledgements off.
+pub(crate) struct Channel<C> {
+  conn: C,
+  pub(crate) ack: bool,
+}
+
+impl<C: Connection> Channel<C> {
+  pub(crate) fn new(conn: C) -> Self {
+    Channel { conn, ack: true }
+  }
+
+  fn read_byte(&mut self) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match self.conn.read(&mut byte)? {
+      0 => Ok(None),
+      _ => Ok(Some(byte[0])),
+    }
+  }
+
+  /// Receives the next packet, or `None` once the debugger disconnects.
+  pub(crate) fn recv(&mut self) -> io::Result<Option<Incoming>> {
+    loop {
+      match self.read_byte()? {
+        None => return Ok(None),
+        Some(INTERRUPT) => return Ok(Some(Incoming::Interrupt)),
+        Some(b'$') => {}
+        /* Stray acknowledgements and line noise */
+        Some(_) => continue,
+      }
+
+      let mut data = Vec::new();
+      let mut sum = 0u8;
+      loop {
+        match self.read_byte()? {
+          None => return Ok(None),
+          Some(b'#') => break,
+          Some(byte) => {
+            sum = sum.wrapping_add(byte);
+            data.push(byte);
+          }
+        }
+      }
+
+      let mut checksum = [0; 2];
+      self.conn.read_exact(&mut checksum)?;
+      let valid = parse_hex(&checksum) == Some(sum as u32);
+
+      if self.ack {
+        self.conn.write_all(if valid { b"+" } else { b"-" })?;
+        self.conn.flush()?;
+      }
+      if valid || !self.ack {
+        return Ok(Some(Incoming::Packet(unescape(&data))));
+      }
+    }
+  }
+
+  pub(crate) fn send(&mut self, data: &[u8]) -> io::Result<()> {
+    let escaped = escape(data);
+    let sum = escaped.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+
+    let mut frame = Vec::with_capacity(escaped.len() + 4);
+    frame.push(b'$');
+    frame.extend_from_slice(&escaped);
+    frame.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+
+    loop {
+      self.conn.write_all(&frame)?;
+      self.conn.flush()?;
+      if !self.ack {
+        return Ok(());
+      }
+
+      /* Retransmit until the debugger acknowledges the packet */
+      loop {
+        match self.read_byte()? {
+          None => return Err(ErrorKind::UnexpectedEof.into()),
+          Some(b'+') => return Ok(()),
+          Some(b'-') => break,
+          Some(_) => continue,
+        }
+      }
+    }
+  }
+
+  /// Whether the debugger has asked the running target to stop.
+  pub(crate) fn interrupted(&mut self) -> io::Result<bool> {
+    Ok(self.conn.poll_byte()? == Some(INTERRUPT))
+  }
+}
+
+fn escape(data: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(data.len());
+  for &byte in data {
+    match byte {
+      b'$' | b'#' | b'}' | b'*' => out.extend_from_slice(&[b'}', byte ^ 0x20]),
+      _ => out.push(byte),
+    }
+  }
+  out
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+  let mut out = Vec::with_capacity(data.len());
+  let mut bytes = data.iter();
+  while let Some(&byte) = bytes.next() {
+    match byte {
+      b'}' => out.extend(bytes.next().map(|b| b ^ 0x20)),
+      _ => out.push(byte),
+    }
+  }
+  out
+}
+
+pub(crate) fn parse_hex(hex: &[u8]) -> Option<u32> {
+  let str = std::str::from_utf8(hex).ok()?;
+  u32::from_str_radix(str, 16).ok()
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(hex: &[u8]) -> Option<Vec<u8>> {
+  if !hex.len().is_multiple_of(2) {
+    return None;
+  }
+  hex
+    .chunks(2)
+    .map(|pair| parse_hex(pair).map(|b| b as u8))
+    .collect()
+}
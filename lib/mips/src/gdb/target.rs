@@ -0,0 +1,94 @@
+use std::fmt::Write;
+
+use crate::emulator::cpu::Cpu;
+
+/// Number of registers in gdb's MIPS layout: 32 GPRs, status, lo, hi,
+/// badvaddr, cause, pc, 32 FPRs, fcsr and fir.
+pub const NUM_REGS: usize = 72;
+
+pub const REG_STATUS: usize = 32;
+pub const REG_LO: usize = 33;
+pub const REG_HI: usize = 34;
+pub const REG_BADVADDR: usize = 35;
+pub const REG_CAUSE: usize = 36;
+pub const REG_PC: usize = 37;
+pub const REG_F0: usize = 38;
+
+/// Reads register `n` in gdb's numbering. The machine has no FPU or
+/// status/cause registers, so those read as zero.
+pub fn read_reg(cpu: &Cpu, n: usize) -> Option<u32> {
+  match n {
+    0..=31 => Some(cpu.regs[n]),
+    REG_LO => Some(cpu.lo),
+    REG_HI => Some(cpu.hi),
+    REG_BADVADDR => Some(cpu.badvaddr),
+    REG_PC => Some(cpu.pc),
+    REG_STATUS | REG_CAUSE | REG_F0..NUM_REGS => Some(0),
+    _ => None,
+  }
+}
+
+/// Writes register `n` in gdb's numbering. Writes to registers the machine
+/// does not model are ignored. Returns `false` for an unknown register.
+pub fn write_reg(cpu: &mut Cpu, n: usize, value: u32) -> bool {
+  match n {
+    /* $zero is hardwired */
+    0 => {}
+    1..=31 => cpu.regs[n] = value,
+    REG_LO => cpu.lo = value,
+    REG_HI => cpu.hi = value,
+    REG_BADVADDR => cpu.badvaddr = value,
+    REG_PC => cpu.pc = value,
+    REG_STATUS | REG_CAUSE | REG_F0..NUM_REGS => {}
+    _ => return false,
+  }
+  true
+}
+
+/// The `target.xml` description of the register layout above, using the
+/// feature names gdb's MIPS support requires.
+pub fn target_xml() -> String {
+  let mut xml = String::from(concat!(
+    "<?xml version=\"1.0\"?>\n",
+    "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n",
+    "<target version=\"1.0\">\n",
+    "  <architecture>mips</architecture>\n",
+    "  <feature name=\"org.gnu.gdb.mips.cpu\">\n",
+  ));
+
+  let reg = |xml: &mut String, name: &str, n: usize, extra: &str| {
+    writeln!(
+      xml,
+      "    <reg name=\"{}\" bitsize=\"32\" regnum=\"{}\"{}/>",
+      name, n, extra
+    )
+    .unwrap();
+  };
+
+  for n in 0..32 {
+    reg(&mut xml, &format!("r{}", n), n, "");
+  }
+  reg(&mut xml, "lo", REG_LO, "");
+  reg(&mut xml, "hi", REG_HI, "");
+  reg(&mut xml, "pc", REG_PC, " type=\"code_ptr\"");
+
+  xml.push_str("  </feature>\n  <feature name=\"org.gnu.gdb.mips.cp0\">\n");
+  reg(&mut xml, "status", REG_STATUS, "");
+  reg(&mut xml, "badvaddr", REG_BADVADDR, "");
+  reg(&mut xml, "cause", REG_CAUSE, "");
+
+  xml.push_str("  </feature>\n  <feature name=\"org.gnu.gdb.mips.fpu\">\n");
+  for n in 0..32 {
+    reg(
+      &mut xml,
+      &format!("f{}", n),
+      REG_F0 + n,
+      " type=\"ieee_single\"",
+    );
+  }
+  reg(&mut xml, "fcsr", REG_F0 + 32, " group=\"float\"");
+  reg(&mut xml, "fir", REG_F0 + 33, " group=\"float\"");
+
+  xml.push_str("  </feature>\n</target>\n");
+  xml
+}
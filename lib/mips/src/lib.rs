@@ -1 +1,50 @@
-pub mod emulator;
\ No newline at end of file
+//! A MIPS32 emulator with an assembler and debugging front ends.
+//!
+//! Most programs only need [`Machine::builder`], which loads a program and
+//! returns a machine ready to run:
+//!
+//! ```
+//! use mips::{Console, Machine};
+//!
+//! let console = Console::new();
+//! let mut machine = Machine::builder()
+//!   .source("li $a0, 42\nli $v0, 1\nsyscall\nli $a0, 7\nli $v0, 17\nsyscall")
+//!   .console(&console)
+//!   .build()
+//!   .unwrap();
+//! assert_eq!(machine.run().unwrap(), 7);
+//! assert_eq!(console.output(), "42");
+//! ```
+//!
+//! A [`Console`] captures output and scripts input for tests, and a
+//! [`Worker`] runs a machine on a background thread that others can pause
+//! and inspect.
+//!
+//! The modules underneath are public for finer control:
+//!
+//! - [`emulator`]: the processor ([`Cpu`]), its memory bus ([`Bus`]), and
+//!   the system call layer and debugger hooks ([`Sys`]). The
+//!   [`interrupt_software!`], [`interrupt_hardware!`] and
+//!   [`interrupt_exception!`] macros return an [`Interrupt`] from the
+//!   enclosing function.
+//! - [`asm`]: a MARS/SPIM-style assembler producing a [`Program`].
+//! - [`gdb`]: a GDB remote protocol stub.
+
+pub mod asm;
+pub mod emulator;
+pub mod gdb;
+pub mod machine;
+
+pub use asm::{assemble, AsmError, Program};
+pub use emulator::{
+  arch::{Endian, Isa, Register},
+  bus::Bus,
+  console::Console,
+  control::{Event, StopFlag, Worker},
+  cpu::Cpu,
+  debug::StopReason,
+  interrupt::Interrupt,
+  limits::{LimitExceeded, LimitKind, Limits},
+  sys::{Sys, Syscalls},
+};
+pub use machine::{Machine, MachineBuilder, MachineError};
@@ -0,0 +1,303 @@
+//! One-stop construction of a runnable machine.
+//!
+//! [`Machine::builder`] collects the memory layout, processor options,
+//! system call services and I/O streams, loads a program and wires the
+//! [`Cpu`] into a [`Sys`] ready to run.
+
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use crate::{
+  asm::{assemble_with, AsmError, Program},
+  emulator::{
+    arch::{Endian, Isa},
+    console::Console,
+    cpu::Cpu,
+    dram::{Dram, DRAM_SIZE},
+    elf::ElfError,
+    interrupt::Interrupt,
+    limits::Limits,
+    memory::{FlatMemory, SparseMemory},
+    sys::{Stack, Sys, Syscalls, HEAP_BASE, STACK_SIZE},
+    vfs::Vfs,
+    virt::{MemMap, MemRegion, Perm},
+  },
+};
+
+#[derive(Debug, Error)]
+pub enum MachineError {
+  #[error("{0}")]
+  ASM(#[from] AsmError),
+
+  #[error("{0}")]
+  ELF(#[from] ElfError),
+
+  #[error("Cannot load program: {0}")]
+  LOAD(#[from] Interrupt),
+
+  #[error("{0:#x} bytes of memory leave no room for the heap and a {1:#x} byte stack")]
+  LAYOUT(u32, u32),
+}
+
+/// The program a [`MachineBuilder`] loads.
+enum Image {
+  Source(String),
+  Program(Program),
+  Elf(Vec<u8>),
+  Raw(Vec<u8>),
+}
+
+/// A [`Sys`] assembled by [`MachineBuilder`], together with the program
+/// it was loaded with.
+pub struct Machine {
+  sys: Sys,
+  program: Option<Program>,
+}
+
+impl Machine {
+  pub fn builder() -> MachineBuilder {
+    MachineBuilder::default()
+  }
+
+  pub fn sys(&self) -> &Sys {
+    &self.sys
+  }
+
+  pub fn sys_mut(&mut self) -> &mut Sys {
+    &mut self.sys
+  }
+
+  pub fn into_sys(self) -> Sys {
+    self.sys
+  }
+
+  /// Labels and line information, for programs loaded from source or a
+  /// [`Program`].
+  pub fn program(&self) -> Option<&Program> {
+    self.program.as_ref()
+  }
+
+  /// Runs the program to completion and returns its exit code.
+  pub fn run(&mut self) -> Result<i32, Interrupt> {
+    self.sys.run()?;
+    Ok(self.sys.exit_code().unwrap_or(0))
+  }
+}
+
+/// Configures and loads a [`Machine`].
+///
+/// DRAM is mapped from `0x80000000`. The heap starts halfway through the
+/// default 128 MiB and the stack sits at the top of DRAM, so memory sizes
+/// must leave room for both.
+pub struct MachineBuilder {
+  memory: u32,
+  flat: bool,
+  stack_size: u32,
+  isa: Isa,
+  endian: Endian,
+  delay_slots: bool,
+  load_delay: bool,
+  syscalls: Syscalls,
+  limits: Limits,
+  stdout: Box<dyn Write>,
+  stdin: Box<dyn Read>,
+  vfs: Vfs,
+  mappings: Vec<(String, MemRegion, Perm)>,
+  args: Vec<String>,
+  image: Option<Image>,
+}
+
+impl Default for MachineBuilder {
+  fn default() -> Self {
+    MachineBuilder {
+      memory: DRAM_SIZE as u32,
+      flat: false,
+      stack_size: STACK_SIZE,
+      isa: Isa::default(),
+      endian: Endian::default(),
+      delay_slots: false,
+      load_delay: false,
+      syscalls: Syscalls::default(),
+      limits: Limits::default(),
+      stdout: Box::new(io::sink()),
+      stdin: Box::new(io::empty()),
+      vfs: Vfs::new(),
+      mappings: Vec::new(),
+      args: Vec::new(),
+      image: None,
+    }
+  }
+}
+
+impl MachineBuilder {
+  /// Size of DRAM in bytes.
+  pub fn memory(mut self, bytes: u32) -> Self {
+    self.memory = bytes;
+    self
+  }
+
+  /// Allocates all of DRAM up front instead of page by page on first touch.
+  pub fn flat_memory(mut self, flat: bool) -> Self {
+    self.flat = flat;
+    self
+  }
+
+  /// Maximum depth of the stack in bytes.
+  pub fn stack_size(mut self, bytes: u32) -> Self {
+    self.stack_size = bytes;
+    self
+  }
+
+  pub fn isa(mut self, isa: Isa) -> Self {
+    self.isa = isa;
+    self
+  }
+
+  /// Byte order of memory, and of programs assembled from source. ELF
+  /// executables use the byte order they declare.
+  pub fn endian(mut self, endian: Endian) -> Self {
+    self.endian = endian;
+    self
+  }
+
+  pub fn delay_slots(mut self, enabled: bool) -> Self {
+    self.delay_slots = enabled;
+    self
+  }
+
+  pub fn load_delay(mut self, enabled: bool) -> Self {
+    self.load_delay = enabled;
+    self
+  }
+
+  pub fn syscalls(mut self, syscalls: Syscalls) -> Self {
+    self.syscalls = syscalls;
+    self
+  }
+
+  /// Bounds on instructions, time and output. Unlimited by default.
+  pub fn limits(mut self, limits: Limits) -> Self {
+    self.limits = limits;
+    self
+  }
+
+  /// Where the guest's console output goes. Discarded by default.
+  pub fn stdout<O: 'static + Write>(mut self, stdout: O) -> Self {
+    self.stdout = Box::new(stdout);
+    self
+  }
+
+  /// Where the guest's console input comes from. Empty by default.
+  pub fn stdin<I: 'static + Read>(mut self, stdin: I) -> Self {
+    self.stdin = Box::new(stdin);
+    self
+  }
+
+  /// Attaches the guest's console input and output to `console`.
+  pub fn console(self, console: &Console) -> Self {
+    self.stdout(console.stdout()).stdin(console.stdin())
+  }
+
+  /// Adds a file the guest can open.
+  pub fn file<P: Into<String>, C: Into<Vec<u8>>>(mut self, path: P, contents: C) -> Self {
+    self.vfs.insert(path, contents);
+    self
+  }
+
+  /// Maps `region` with the given permissions, overriding the mappings
+  /// the program's sections, heap and stack get.
+  pub fn map<S: Into<String>>(mut self, name: S, region: MemRegion, perm: Perm) -> Self {
+    self.mappings.push((name.into(), region, perm));
+    self
+  }
+
+  /// Program arguments, passed in `$a0` and `$a1` as by [`Sys::set_args`].
+  pub fn args<I, S>(mut self, args: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    self.args = args.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Assembles and loads MIPS source.
+  pub fn source<S: Into<String>>(mut self, src: S) -> Self {
+    self.image = Some(Image::Source(src.into()));
+    self
+  }
+
+  /// Loads an assembled program.
+  pub fn program(mut self, program: Program) -> Self {
+    self.image = Some(Image::Program(program));
+    self
+  }
+
+  /// Loads an ELF executable.
+  pub fn elf<B: Into<Vec<u8>>>(mut self, bytes: B) -> Self {
+    self.image = Some(Image::Elf(bytes.into()));
+    self
+  }
+
+  /// Loads machine code at the start of DRAM and runs it from there.
+  pub fn raw<B: Into<Vec<u8>>>(mut self, bytes: B) -> Self {
+    self.image = Some(Image::Raw(bytes.into()));
+    self
+  }
+
+  fn stack(&self) -> Result<Stack, MachineError> {
+    let layout = || MachineError::LAYOUT(self.memory, self.stack_size);
+    let end = MemMap::HIGHMEM
+      .base
+      .checked_add(self.memory)
+      .ok_or_else(layout)?;
+    match end.checked_sub(self.stack_size) {
+      Some(limit) if limit > HEAP_BASE => Ok(Stack::below(end, self.stack_size)),
+      _ => Err(layout()),
+    }
+  }
+
+  pub fn build(self) -> Result<Machine, MachineError> {
+    let stack = self.stack()?;
+
+    let mut cpu = Cpu::new();
+    cpu.bus.dram = match self.flat {
+      true => Dram::with_memory(Box::new(FlatMemory::new(self.memory))),
+      false => Dram::with_memory(Box::new(SparseMemory::new(self.memory))),
+    };
+    cpu.bus.dram.endian = self.endian;
+    cpu.isa = self.isa;
+    cpu.delay_slots = self.delay_slots;
+    cpu.load_delay = self.load_delay;
+
+    let program = match self.image {
+      Some(Image::Source(src)) => Some(assemble_with(&src, self.endian)?),
+      Some(Image::Program(program)) => Some(program),
+      Some(Image::Elf(bytes)) => {
+        cpu.load_elf(&bytes)?;
+        None
+      }
+      Some(Image::Raw(bytes)) => {
+        cpu.load(bytes)?;
+        None
+      }
+      None => None,
+    };
+    if let Some(program) = &program {
+      cpu.load_program(program)?;
+    }
+
+    let mut sys = Sys::new(cpu, self.stdout, self.stdin);
+    sys.set_stack(stack);
+    sys.set_args(&self.args)?;
+    sys.set_syscalls(self.syscalls);
+    sys.set_limits(self.limits);
+    *sys.vfs_mut() = self.vfs;
+    for (name, region, perm) in self.mappings {
+      sys.cpu_mut().bus.map(name, region, perm);
+    }
+
+    Ok(Machine { sys, program })
+  }
+}
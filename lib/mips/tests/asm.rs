@@ -0,0 +1,135 @@
+use std::{
+  cell::RefCell,
+  io::{empty, Write},
+  rc::Rc,
+};
+
+use mips::{
+  asm::{assemble, AsmError, AsmErrorKind, DATA_BASE, TEXT_BASE},
+  emulator::{arch::Register, cpu::Cpu, sys::Sys},
+};
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+const HELLO: &str = r#"
+        .data
+msg:    .asciiz "hello, world\n"
+nums:   .word 1, 2, 3, msg
+
+        .text
+main:   la    $a0, msg          # print the greeting
+        li    $v0, 4
+        syscall
+
+        li    $t0, 0
+        la    $t1, nums
+        li    $t2, 3
+loop:   lw    $t3, 0($t1)
+        add   $t0, $t0, $t3
+        addi  $t1, $t1, 4
+        subi  $t2, $t2, 1
+        bgtz  $t2, loop
+
+        li    $v0, 10
+        syscall
+"#;
+
+fn run(src: &str) -> (Cpu, String) {
+  let program = assemble(src).unwrap();
+  let mut cpu = Cpu::new();
+  cpu.load_program(&program).unwrap();
+
+  let out = Output::default();
+  let mut sys = Sys::new(cpu, out.clone(), empty());
+  sys.run().unwrap();
+  let text = String::from_utf8(out.0.borrow().clone()).unwrap();
+  (sys.cpu().clone(), text)
+}
+
+#[test]
+fn assembles_and_runs_a_program() {
+  let (cpu, out) = run(HELLO);
+  assert!(out.starts_with("hello, world\n"));
+  assert_eq!(cpu.regs[Register::T0], 6);
+}
+
+#[test]
+fn records_labels_and_lines() {
+  let program = assemble(HELLO).unwrap();
+  assert_eq!(program.symbols["msg"], DATA_BASE);
+  assert_eq!(program.symbols["nums"], DATA_BASE + 16);
+  assert_eq!(program.entry, TEXT_BASE);
+  assert_eq!(&program.data[28..32], &DATA_BASE.to_le_bytes());
+
+  /* `la` expands to two instructions on the same line */
+  assert_eq!(program.line_at(TEXT_BASE), Some(7));
+  assert_eq!(program.line_at(TEXT_BASE + 4), Some(7));
+  assert_eq!(program.line_at(TEXT_BASE + 8), Some(8));
+
+  /* Breakpoints on blank lines move to the next statement */
+  assert_eq!(program.resolve_line(10), Some((11, TEXT_BASE + 16)));
+  assert_eq!(program.resolve_line(100), None);
+}
+
+#[test]
+fn expands_pseudo_instructions() {
+  let (cpu, _) = run(
+    "
+    li   $t0, 0x12345678
+    li   $t1, -7
+    mul  $t2, $t1, 3
+    rem  $t3, $t0, 16
+    move $t4, $t2
+    neg  $t5, $t4
+    addiu $t6, $zero, 0x10000
+    blt  $t1, $zero, skip
+    li   $t7, 1
+skip:
+    ",
+  );
+  assert_eq!(cpu.regs[Register::T0], 0x1234_5678);
+  assert_eq!(cpu.regs[Register::T1], -7i32 as u32);
+  assert_eq!(cpu.regs[Register::T2], -21i32 as u32);
+  assert_eq!(cpu.regs[Register::T3], 8);
+  assert_eq!(cpu.regs[Register::T5], 21);
+  assert_eq!(cpu.regs[Register::T6], 0x10000);
+  assert_eq!(cpu.regs[Register::T7], 0);
+}
+
+#[test]
+fn reports_errors_with_line_numbers() {
+  let err = |src| assemble(src).unwrap_err();
+
+  assert_eq!(
+    err("nop\nfoo $t0"),
+    AsmError {
+      line: 2,
+      kind: AsmErrorKind::INSTRUCTION("foo".into())
+    }
+  );
+  assert_eq!(
+    err("j nowhere").kind,
+    AsmErrorKind::UNDEFINED("nowhere".into())
+  );
+  assert_eq!(
+    err("a: nop\na: nop").kind,
+    AsmErrorKind::DUPLICATE("a".into())
+  );
+  assert_eq!(
+    err("addu $t0, $t1").kind,
+    AsmErrorKind::ARITY("addu".into(), 3)
+  );
+  assert_eq!(err("sll $t0, $t0, 32").kind, AsmErrorKind::RANGE(32));
+}
@@ -0,0 +1,180 @@
+mod common;
+
+use common::{i_type, retired, run, BASE};
+use mips::emulator::{
+  arch::Register,
+  cpu::{Cpu, StepOutcome},
+};
+
+const T0: usize = Register::T0;
+const T1: usize = Register::T1;
+const T2: usize = Register::T2;
+const RA: usize = Register::RA;
+
+fn addiu(rt: usize, imm: u16) -> u32 {
+  i_type(0x09, rt, rt, imm)
+}
+
+/// Loads `code` at the reset PC, with or without delay slots.
+fn setup(code: &[u32], delay_slots: bool) -> Cpu {
+  let mut cpu = common::setup(code, &[], &[]);
+  cpu.delay_slots = delay_slots;
+  cpu
+}
+
+/// beq $zero, $zero, +2; addiu $t0, 1; addiu $t1, 1; addiu $t2, 1
+fn taken_branch() -> [u32; 4] {
+  [
+    i_type(0x04, 0, 0, 2),
+    addiu(T0, 1),
+    addiu(T1, 1),
+    addiu(T2, 1),
+  ]
+}
+
+#[test]
+fn delay_slot_runs_before_a_taken_branch() {
+  let mut cpu = setup(&taken_branch(), true);
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 4);
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 12);
+
+  assert_eq!(run(&mut cpu), 1);
+  assert_eq!([cpu.regs[T0], cpu.regs[T1], cpu.regs[T2]], [1, 0, 1]);
+}
+
+#[test]
+fn branches_are_immediate_without_delay_slots() {
+  let mut cpu = setup(&taken_branch(), false);
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 12);
+
+  assert_eq!(run(&mut cpu), 1);
+  assert_eq!([cpu.regs[T0], cpu.regs[T1], cpu.regs[T2]], [0, 0, 1]);
+}
+
+#[test]
+fn jumps_honour_the_delay_slot() {
+  /* j BASE + 12; addiu $t0, 1; addiu $t1, 1; addiu $t2, 1 */
+  let j = (0x02 << 26) | (((BASE + 12) >> 2) & 0x3ff_ffff);
+  let code = [j, addiu(T0, 1), addiu(T1, 1), addiu(T2, 1)];
+
+  let mut cpu = setup(&code, true);
+  assert_eq!(run(&mut cpu), 3);
+  assert_eq!([cpu.regs[T0], cpu.regs[T1], cpu.regs[T2]], [1, 0, 1]);
+
+  let mut cpu = setup(&code, false);
+  assert_eq!(run(&mut cpu), 2);
+  assert_eq!([cpu.regs[T0], cpu.regs[T1], cpu.regs[T2]], [0, 0, 1]);
+}
+
+#[test]
+fn a_fault_in_the_delay_slot_keeps_the_branch() {
+  /* beq $zero, $zero, +2; lw $t1, 0($t0); addiu $t1, 1; addiu $t2, 1 */
+  let code = [
+    i_type(0x04, 0, 0, 2),
+    i_type(0x23, T0, T1, 0),
+    addiu(T1, 1),
+    addiu(T2, 1),
+  ];
+  let mut cpu = setup(&code, true);
+  cpu.regs[T0] = BASE + 1;
+
+  retired(cpu.step());
+  let res = cpu.step();
+  assert!(
+    matches!(res, StepOutcome::Exception { pc, .. } if pc == BASE + 4),
+    "{res:?}"
+  );
+  assert_eq!(cpu.pc, BASE + 4);
+
+  /* Resuming re-runs the slot and then takes the branch */
+  cpu.regs[T0] = BASE;
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 12);
+  assert_eq!(cpu.regs[T1], code[0]);
+  assert_eq!(run(&mut cpu), 1);
+  assert_eq!([cpu.regs[T1], cpu.regs[T2]], [code[0], 1]);
+}
+
+#[test]
+fn links_skip_the_delay_slot() {
+  let jal = (0x03 << 26) | (((BASE + 0x40) >> 2) & 0x3ff_ffff);
+  let jalr = ((T0 as u32) << 21) | ((T1 as u32) << 11) | 0x09;
+  let bgezal = i_type(0x01, 0, 0x11, 4);
+
+  for (inst, link) in [(jal, RA), (jalr, T1), (bgezal, RA)] {
+    let mut cpu = setup(&[inst, 0], true);
+    cpu.regs[T0] = BASE + 0x40;
+    retired(cpu.step());
+    assert_eq!(cpu.regs[link], BASE + 8, "{inst:#010x}");
+
+    let mut cpu = setup(&[inst, 0], false);
+    cpu.regs[T0] = BASE + 0x40;
+    retired(cpu.step());
+    assert_eq!(cpu.regs[link], BASE + 4, "{inst:#010x}");
+  }
+}
+
+#[test]
+fn branch_likely_annuls_the_slot_only_when_not_taken() {
+  /* beql $t0, $zero, +2; addiu $t1, 1; nop; addiu $t2, 1 */
+  let code = [i_type(0x14, T0, 0, 2), addiu(T1, 1), 0, addiu(T2, 1)];
+
+  /* Taken: the slot runs, then the target */
+  let mut cpu = setup(&code, true);
+  assert_eq!(run(&mut cpu), 3);
+  assert_eq!([cpu.regs[T1], cpu.regs[T2]], [1, 1]);
+
+  /* Not taken: the slot is skipped */
+  let mut cpu = setup(&code, true);
+  cpu.regs[T0] = 1;
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 8);
+  assert_eq!(run(&mut cpu), 2);
+  assert_eq!([cpu.regs[T1], cpu.regs[T2]], [0, 1]);
+}
+
+#[test]
+fn branch_likely_falls_through_without_delay_slots() {
+  let code = [i_type(0x14, T0, 0, 2), addiu(T1, 1), 0, addiu(T2, 1)];
+
+  let mut cpu = setup(&code, false);
+  assert_eq!(run(&mut cpu), 2);
+  assert_eq!([cpu.regs[T1], cpu.regs[T2]], [0, 1]);
+
+  /* Not taken: nothing is annulled */
+  let mut cpu = setup(&code, false);
+  cpu.regs[T0] = 1;
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 4);
+  assert_eq!(run(&mut cpu), 3);
+  assert_eq!([cpu.regs[T1], cpu.regs[T2]], [1, 1]);
+}
+
+#[test]
+fn branch_likely_and_link_links_either_way() {
+  /* bltzall $t0, +4 */
+  let bltzall = i_type(0x01, T0, 0x12, 4);
+
+  let mut cpu = setup(&[bltzall, 0, 0], true);
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 8);
+  assert_eq!(cpu.regs[RA], BASE + 8);
+
+  let mut cpu = setup(&[bltzall, 0, 0], false);
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 4);
+  assert_eq!(cpu.regs[RA], BASE + 4);
+}
+
+#[test]
+fn skipping_the_slot_past_the_end_halts() {
+  /* beql $t0, $zero, +2 as the last instruction, not taken */
+  let mut cpu = setup(&[addiu(T0, 1), i_type(0x14, T0, 0, 2)], true);
+  retired(cpu.step());
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 12);
+  assert!(matches!(cpu.step(), StepOutcome::Halted));
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "serde")]
+
+use std::io::{empty, sink};
+
+use mips::emulator::{
+  arch::Register,
+  checkpoint::CheckpointError,
+  cpu::{Cpu, StepOutcome},
+  debug::StopReason,
+  sys::{Stack, Sys},
+  vfs::OpenMode,
+  virt::{MemMap, MemRegion},
+  watch::WatchKind,
+};
+
+const DATA: u32 = MemMap::HIGHMEM.base + 0x100;
+
+fn program() -> Cpu {
+  let code: Vec<u8> = [
+    0x3c09_8000u32, // lui $t1, 0x8000
+    0x2508_0007,    // addiu $t0, $t0, 7
+    0xad28_0100,    // sw $t0, 0x100($t1)
+    0x2508_0001,    // addiu $t0, $t0, 1
+    0xad28_0104,    // sw $t0, 0x104($t1)
+  ]
+  .iter()
+  .flat_map(|w| w.to_le_bytes())
+  .collect();
+
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+  cpu
+}
+
+#[test]
+fn checkpoint_resumes_mid_run() {
+  let mut sys = Sys::new(program(), sink(), empty());
+  for _ in 0..3 {
+    assert!(matches!(sys.cpu_mut().step(), StepOutcome::Retired));
+  }
+  sys.vfs_mut().insert("input.txt", "hello");
+  let fd = sys.vfs_mut().open("input.txt", OpenMode::Read).unwrap();
+
+  let mut file = Vec::new();
+  sys.save_checkpoint(&mut file).unwrap();
+
+  let mut resumed = Sys::new(Cpu::new(), sink(), empty());
+  resumed.load_checkpoint(file.as_slice()).unwrap();
+
+  assert_eq!(resumed.cpu().pc, sys.cpu().pc);
+  assert_eq!(resumed.cpu().regs, sys.cpu().regs);
+  assert_eq!(resumed.cpu().bus.load(DATA, 32).unwrap(), 7);
+  assert_eq!(resumed.vfs_mut().read(fd, 16).unwrap(), b"hello");
+
+  resumed.run().unwrap();
+  assert_eq!(resumed.cpu().regs[Register::T0], 8);
+  assert_eq!(resumed.cpu().bus.load(DATA + 4, 32).unwrap(), 8);
+}
+
+#[test]
+fn checkpoint_restores_run_state_and_keeps_watchpoints() {
+  let mut sys = Sys::new(program(), sink(), empty());
+  let stack = Stack::below(MemMap::HIGHMEM.base + 0x700_0000, 0x1_0000);
+  sys.set_stack(stack);
+  sys.cpu_mut().load_hazards.enabled = true;
+  for _ in 0..3 {
+    assert_eq!(sys.step().unwrap(), StopReason::Step);
+  }
+
+  let mut file = Vec::new();
+  sys.save_checkpoint(&mut file).unwrap();
+
+  let mut resumed = Sys::new(Cpu::new(), sink(), empty());
+  let watched = MemRegion {
+    base: DATA + 4,
+    size: 4,
+  };
+  let id = resumed.cpu_mut().bus.watch(watched, WatchKind::Write);
+  resumed.load_checkpoint(file.as_slice()).unwrap();
+
+  assert_eq!(resumed.stack(), stack);
+  assert_eq!(resumed.retired(), 3);
+  assert!(resumed.cpu().load_hazards.enabled);
+  assert!(matches!(
+    resumed.cont().unwrap(),
+    StopReason::Watchpoint(hit) if hit.id == id
+  ));
+}
+
+#[test]
+fn checkpoint_is_sparse() {
+  let sys = Sys::new(program(), sink(), empty());
+  let mut file = Vec::new();
+  sys.save_checkpoint(&mut file).unwrap();
+
+  assert!(file.len() < 1024);
+}
+
+#[test]
+fn rejects_foreign_files() {
+  let mut sys = Sys::new(Cpu::new(), sink(), empty());
+  let err = sys.load_checkpoint(&b"\x7fELF\x01\x01\x01\x00\x00\x00\x00\x00"[..]);
+  assert!(matches!(err, Err(CheckpointError::MAGIC)));
+}
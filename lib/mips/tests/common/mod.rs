@@ -0,0 +1,53 @@
+//! Instruction encoders and CPU setup shared by the instruction-level test
+//! suites. Each suite uses a different subset.
+#![allow(dead_code)]
+
+use mips::emulator::{
+  cpu::{Cpu, StepOutcome},
+  virt::MemMap,
+};
+
+pub const BASE: u32 = MemMap::HIGHMEM.base;
+pub const DATA: u32 = BASE + 0x100;
+
+pub fn r_type(funct: u32, rs: usize, rt: usize, rd: usize, shamt: u32) -> u32 {
+  ((rs as u32) << 21) | ((rt as u32) << 16) | ((rd as u32) << 11) | (shamt << 6) | funct
+}
+
+pub fn i_type(opcode: u32, rs: usize, rt: usize, imm: u16) -> u32 {
+  (opcode << 26) | ((rs as u32) << 21) | ((rt as u32) << 16) | imm as u32
+}
+
+/// Loads `code` at the reset PC, followed by `data` at `DATA` when there is
+/// any, with `regs` preloaded.
+pub fn setup(code: &[u32], data: &[u8], regs: &[(usize, u32)]) -> Cpu {
+  let mut image: Vec<u8> = code.iter().flat_map(|w| w.to_le_bytes()).collect();
+  if !data.is_empty() {
+    image.resize((DATA - BASE) as usize, 0);
+    image.extend_from_slice(data);
+  }
+
+  let mut cpu = Cpu::new();
+  cpu.load(image).unwrap();
+  for &(reg, value) in regs {
+    cpu.regs[reg] = value;
+  }
+  cpu
+}
+
+pub fn retired(res: StepOutcome) {
+  assert!(matches!(res, StepOutcome::Retired), "{res:?}");
+}
+
+/// Steps until the CPU halts, returning the number of instructions run and
+/// failing on anything but a retired instruction.
+pub fn run(cpu: &mut Cpu) -> usize {
+  let mut steps = 0;
+  loop {
+    match cpu.step() {
+      StepOutcome::Retired => steps += 1,
+      StepOutcome::Halted => return steps,
+      other => panic!("unexpected {other:?}"),
+    }
+  }
+}
@@ -0,0 +1,530 @@
+mod common;
+
+use common::{i_type, r_type, retired, setup, DATA};
+use mips::emulator::{
+  arch::Register,
+  cpu::{Cpu, StepOutcome},
+  interrupt::ExceptionInterrupt,
+  virt::{MemMap, MemRegion, Perm},
+};
+
+/// Runs a single instruction with the given registers preloaded.
+fn exec_with(inst: u32, regs: &[(usize, u32)], data: &[u8]) -> (Cpu, StepOutcome) {
+  let mut cpu = setup(&[inst], data, regs);
+  let res = cpu.step();
+  (cpu, res)
+}
+
+fn exec(inst: u32, regs: &[(usize, u32)]) -> Cpu {
+  let (cpu, res) = exec_with(inst, regs, &[]);
+  retired(res);
+  cpu
+}
+
+fn is_overflow(res: StepOutcome) -> bool {
+  matches!(
+    res,
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::OVF,
+      ..
+    }
+  )
+}
+
+const T0: usize = Register::T0;
+const T1: usize = Register::T1;
+const T2: usize = Register::T2;
+
+/* ----- $zero ----- */
+
+#[test]
+fn zero_ignores_alu_writes() {
+  let cpu = exec(i_type(0x09, T0, Register::ZERO, 5), &[(T0, 1)]);
+  assert_eq!(cpu.regs[Register::ZERO], 0);
+}
+
+#[test]
+fn zero_ignores_loads() {
+  let (cpu, res) = exec_with(
+    i_type(0x23, T0, Register::ZERO, 0),
+    &[(T0, DATA)],
+    &[1, 2, 3, 4],
+  );
+  retired(res);
+  assert_eq!(cpu.regs[Register::ZERO], 0);
+}
+
+/* ----- ADD / ADDU / SUB / SUBU ----- */
+
+#[test]
+fn addu_wraps_without_trapping() {
+  let cpu = exec(r_type(0x21, T0, T1, T2, 0), &[(T0, u32::MAX), (T1, 1)]);
+  assert_eq!(cpu.regs[T2], 0);
+
+  let cpu = exec(
+    r_type(0x21, T0, T1, T2, 0),
+    &[(T0, i32::MAX as u32), (T1, 1)],
+  );
+  assert_eq!(cpu.regs[T2], i32::MIN as u32);
+}
+
+#[test]
+fn add_traps_on_signed_overflow() {
+  let (cpu, res) = exec_with(
+    r_type(0x20, T0, T1, T2, 0),
+    &[(T0, i32::MAX as u32), (T1, 1), (T2, 7)],
+    &[],
+  );
+  assert!(is_overflow(res));
+  assert_eq!(cpu.regs[T2], 7);
+
+  let (_, res) = exec_with(
+    r_type(0x20, T0, T1, T2, 0),
+    &[(T0, i32::MIN as u32), (T1, -1i32 as u32)],
+    &[],
+  );
+  assert!(is_overflow(res));
+}
+
+#[test]
+fn add_ignores_unsigned_carry() {
+  let cpu = exec(r_type(0x20, T0, T1, T2, 0), &[(T0, u32::MAX), (T1, 1)]);
+  assert_eq!(cpu.regs[T2], 0);
+}
+
+#[test]
+fn subu_wraps_without_trapping() {
+  let cpu = exec(r_type(0x23, T0, T1, T2, 0), &[(T0, 0), (T1, 1)]);
+  assert_eq!(cpu.regs[T2], u32::MAX);
+
+  let cpu = exec(
+    r_type(0x23, T0, T1, T2, 0),
+    &[(T0, i32::MIN as u32), (T1, 1)],
+  );
+  assert_eq!(cpu.regs[T2], i32::MAX as u32);
+}
+
+#[test]
+fn sub_traps_on_signed_overflow() {
+  let (_, res) = exec_with(
+    r_type(0x22, T0, T1, T2, 0),
+    &[(T0, i32::MIN as u32), (T1, 1)],
+    &[],
+  );
+  assert!(is_overflow(res));
+
+  let (_, res) = exec_with(
+    r_type(0x22, T0, T1, T2, 0),
+    &[(T0, 0), (T1, i32::MIN as u32)],
+    &[],
+  );
+  assert!(is_overflow(res));
+
+  let cpu = exec(
+    r_type(0x22, T0, T1, T2, 0),
+    &[(T0, -1i32 as u32), (T1, i32::MAX as u32)],
+  );
+  assert_eq!(cpu.regs[T2], i32::MIN as u32);
+}
+
+/* ----- ADDI / ADDIU ----- */
+
+#[test]
+fn addi_traps_on_signed_overflow() {
+  let (cpu, res) = exec_with(
+    i_type(0x08, T0, T1, 1),
+    &[(T0, i32::MAX as u32), (T1, 7)],
+    &[],
+  );
+  assert!(is_overflow(res));
+  assert_eq!(cpu.regs[T1], 7);
+
+  let (_, res) = exec_with(i_type(0x08, T0, T1, 0xffff), &[(T0, i32::MIN as u32)], &[]);
+  assert!(is_overflow(res));
+}
+
+#[test]
+fn addi_sign_extends_immediate() {
+  let cpu = exec(i_type(0x08, T0, T1, 0x8000), &[(T0, 0)]);
+  assert_eq!(cpu.regs[T1], -32768i32 as u32);
+}
+
+#[test]
+fn addiu_wraps_without_trapping() {
+  let cpu = exec(i_type(0x09, T0, T1, 1), &[(T0, i32::MAX as u32)]);
+  assert_eq!(cpu.regs[T1], i32::MIN as u32);
+
+  let cpu = exec(i_type(0x09, T0, T1, 0xffff), &[(T0, 0)]);
+  assert_eq!(cpu.regs[T1], u32::MAX);
+}
+
+/* ----- SLT / SLTU / SLTI / SLTIU ----- */
+
+#[test]
+fn slt_compares_signed() {
+  let cpu = exec(r_type(0x2A, T0, T1, T2, 0), &[(T0, -1i32 as u32), (T1, 0)]);
+  assert_eq!(cpu.regs[T2], 1);
+
+  let cpu = exec(
+    r_type(0x2A, T0, T1, T2, 0),
+    &[(T0, i32::MAX as u32), (T1, i32::MIN as u32)],
+  );
+  assert_eq!(cpu.regs[T2], 0);
+}
+
+#[test]
+fn sltu_compares_unsigned() {
+  let cpu = exec(r_type(0x2B, T0, T1, T2, 0), &[(T0, -1i32 as u32), (T1, 0)]);
+  assert_eq!(cpu.regs[T2], 0);
+
+  let cpu = exec(r_type(0x2B, T0, T1, T2, 0), &[(T0, 0), (T1, u32::MAX)]);
+  assert_eq!(cpu.regs[T2], 1);
+}
+
+#[test]
+fn slti_sign_extends_immediate() {
+  let cpu = exec(i_type(0x0A, T0, T1, 0xffff), &[(T0, -2i32 as u32)]);
+  assert_eq!(cpu.regs[T1], 1);
+
+  let cpu = exec(i_type(0x0A, T0, T1, 0x8000), &[(T0, 0)]);
+  assert_eq!(cpu.regs[T1], 0);
+}
+
+#[test]
+fn sltiu_sign_extends_then_compares_unsigned() {
+  let cpu = exec(i_type(0x0B, T0, T1, 0xffff), &[(T0, 0xffff_fffe)]);
+  assert_eq!(cpu.regs[T1], 1);
+
+  let cpu = exec(i_type(0x0B, T0, T1, 0xffff), &[(T0, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0);
+
+  let cpu = exec(i_type(0x0B, T0, T1, 0x8000), &[(T0, 0x0001_0000)]);
+  assert_eq!(cpu.regs[T1], 1);
+}
+
+/* ----- Shifts ----- */
+
+#[test]
+fn sll_srl_sra_by_immediate() {
+  let cpu = exec(r_type(0x00, 0, T0, T1, 31), &[(T0, 1)]);
+  assert_eq!(cpu.regs[T1], 0x8000_0000);
+
+  let cpu = exec(r_type(0x02, 0, T0, T1, 31), &[(T0, 0x8000_0000)]);
+  assert_eq!(cpu.regs[T1], 1);
+
+  let cpu = exec(r_type(0x03, 0, T0, T1, 31), &[(T0, 0x8000_0000)]);
+  assert_eq!(cpu.regs[T1], u32::MAX);
+}
+
+#[test]
+fn variable_shifts_mask_amount() {
+  let cpu = exec(r_type(0x04, T1, T0, T2, 0), &[(T0, 1), (T1, 33)]);
+  assert_eq!(cpu.regs[T2], 2);
+
+  let cpu = exec(
+    r_type(0x06, T1, T0, T2, 0),
+    &[(T0, 0x8000_0000), (T1, 0xffff_ffe0 | 31)],
+  );
+  assert_eq!(cpu.regs[T2], 1);
+
+  let cpu = exec(
+    r_type(0x07, T1, T0, T2, 0),
+    &[(T0, 0x8000_0000), (T1, 32 + 4)],
+  );
+  assert_eq!(cpu.regs[T2], 0xf800_0000);
+}
+
+/* ----- Logical ----- */
+
+#[test]
+fn logical_immediates_zero_extend() {
+  let cpu = exec(i_type(0x0C, T0, T1, 0xffff), &[(T0, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0xffff);
+
+  let cpu = exec(i_type(0x0D, T0, T1, 0x8000), &[(T0, 0)]);
+  assert_eq!(cpu.regs[T1], 0x8000);
+
+  let cpu = exec(i_type(0x0E, T0, T1, 0xffff), &[(T0, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0xffff_0000);
+
+  let cpu = exec(i_type(0x0F, 0, T1, 0x8001), &[(T1, 0xffff)]);
+  assert_eq!(cpu.regs[T1], 0x8001_0000);
+}
+
+#[test]
+fn nor_inverts() {
+  let cpu = exec(
+    r_type(0x27, T0, T1, T2, 0),
+    &[(T0, 0xf0f0_0000), (T1, 0x0000_0f0f)],
+  );
+  assert_eq!(cpu.regs[T2], 0x0f0f_f0f0);
+}
+
+/* ----- MULT / MULTU / DIV / DIVU ----- */
+
+#[test]
+fn mult_is_signed() {
+  let cpu = exec(r_type(0x18, T0, T1, 0, 0), &[(T0, -2i32 as u32), (T1, 3)]);
+  assert_eq!((cpu.hi, cpu.lo), (u32::MAX, -6i32 as u32));
+
+  let cpu = exec(
+    r_type(0x18, T0, T1, 0, 0),
+    &[(T0, i32::MIN as u32), (T1, i32::MIN as u32)],
+  );
+  assert_eq!((cpu.hi, cpu.lo), (0x4000_0000, 0));
+}
+
+#[test]
+fn multu_is_unsigned() {
+  let cpu = exec(
+    r_type(0x19, T0, T1, 0, 0),
+    &[(T0, u32::MAX), (T1, u32::MAX)],
+  );
+  assert_eq!((cpu.hi, cpu.lo), (0xffff_fffe, 1));
+}
+
+#[test]
+fn div_truncates_toward_zero() {
+  let cpu = exec(r_type(0x1A, T0, T1, 0, 0), &[(T0, -7i32 as u32), (T1, 2)]);
+  assert_eq!((cpu.hi, cpu.lo), (-1i32 as u32, -3i32 as u32));
+
+  let cpu = exec(r_type(0x1A, T0, T1, 0, 0), &[(T0, 7), (T1, -2i32 as u32)]);
+  assert_eq!((cpu.hi, cpu.lo), (1, -3i32 as u32));
+}
+
+#[test]
+fn div_min_by_minus_one_does_not_trap() {
+  let cpu = exec(
+    r_type(0x1A, T0, T1, 0, 0),
+    &[(T0, i32::MIN as u32), (T1, -1i32 as u32)],
+  );
+  assert_eq!((cpu.hi, cpu.lo), (0, i32::MIN as u32));
+}
+
+#[test]
+fn div_by_zero_keeps_hi_lo() {
+  for funct in [0x1A, 0x1B] {
+    let mut cpu = setup(&[r_type(funct, T0, T1, 0, 0)], &[], &[]);
+    cpu.regs[T0] = 42;
+    cpu.hi = 1;
+    cpu.lo = 2;
+
+    retired(cpu.step());
+    assert_eq!((cpu.hi, cpu.lo), (1, 2));
+  }
+}
+
+#[test]
+fn divu_is_unsigned() {
+  let cpu = exec(r_type(0x1B, T0, T1, 0, 0), &[(T0, u32::MAX), (T1, 2)]);
+  assert_eq!((cpu.hi, cpu.lo), (1, 0x7fff_ffff));
+}
+
+/* ----- Loads ----- */
+
+#[test]
+fn byte_loads_extend_correctly() {
+  let data = [0x80, 0x7f];
+
+  let (cpu, res) = exec_with(i_type(0x20, T0, T1, 0), &[(T0, DATA)], &data);
+  retired(res);
+  assert_eq!(cpu.regs[T1], 0xffff_ff80);
+
+  let (cpu, res) = exec_with(i_type(0x24, T0, T1, 0), &[(T0, DATA)], &data);
+  retired(res);
+  assert_eq!(cpu.regs[T1], 0x80);
+}
+
+#[test]
+fn half_loads_extend_correctly() {
+  let data = [0x00, 0x80];
+
+  let (cpu, res) = exec_with(i_type(0x21, T0, T1, 0), &[(T0, DATA)], &data);
+  retired(res);
+  assert_eq!(cpu.regs[T1], 0xffff_8000);
+
+  let (cpu, res) = exec_with(i_type(0x25, T0, T1, 0), &[(T0, DATA)], &data);
+  retired(res);
+  assert_eq!(cpu.regs[T1], 0x8000);
+}
+
+#[test]
+fn load_offset_is_signed() {
+  let (cpu, res) = exec_with(
+    i_type(0x23, T0, T1, 0xfffc),
+    &[(T0, DATA + 4)],
+    &[1, 0, 0, 0],
+  );
+  retired(res);
+  assert_eq!(cpu.regs[T1], 1);
+}
+
+/* ----- Branches ----- */
+
+#[test]
+fn branch_offset_is_sign_extended() {
+  let cpu = exec(i_type(0x04, 0, 0, 0x8000), &[]);
+  assert_eq!(cpu.pc, MemMap::HIGHMEM.base + 4 - 0x2_0000);
+
+  let cpu = exec(i_type(0x04, 0, 0, 0x7fff), &[]);
+  assert_eq!(cpu.pc, MemMap::HIGHMEM.base + 4 + 0x1_fffc);
+}
+
+/* ----- Address errors ----- */
+
+#[test]
+fn misaligned_load_raises_address_error() {
+  let (cpu, res) = exec_with(i_type(0x23, T0, T1, 2), &[(T0, DATA)], &[]);
+  assert!(matches!(
+    res,
+    StepOutcome::Exception { kind: ExceptionInterrupt::ADDRL(addr), .. } if addr == DATA + 2
+  ));
+  assert_eq!(cpu.badvaddr, DATA + 2);
+}
+
+#[test]
+fn misaligned_store_raises_address_error() {
+  let (cpu, res) = exec_with(i_type(0x29, T0, T1, 1), &[(T0, DATA)], &[]);
+  assert!(matches!(
+    res,
+    StepOutcome::Exception { kind: ExceptionInterrupt::ADDRS(addr), .. } if addr == DATA + 1
+  ));
+  assert_eq!(cpu.badvaddr, DATA + 1);
+}
+
+#[test]
+fn access_past_end_of_memory_raises_address_error() {
+  let end = MemMap::HIGHMEM.base + Cpu::new().bus.dram.size();
+
+  let (cpu, res) = exec_with(i_type(0x21, T0, T1, 0), &[(T0, end - 2)], &[]);
+  retired(res);
+  assert_eq!(cpu.regs[T1], 0);
+
+  let (cpu, res) = exec_with(i_type(0x23, T0, T1, 0), &[(T0, end)], &[]);
+  assert!(matches!(
+    res,
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::ADDRL(_),
+      ..
+    }
+  ));
+  assert_eq!(cpu.badvaddr, end);
+}
+
+#[test]
+fn misaligned_jump_faults_on_fetch() {
+  let mut cpu = setup(&[r_type(0x08, T0, 0, 0, 0)], &[], &[]);
+  cpu.regs[T0] = DATA + 2;
+
+  retired(cpu.step());
+  assert!(matches!(
+    cpu.step(),
+    StepOutcome::Exception { kind: ExceptionInterrupt::ADDRL(addr), .. } if addr == DATA + 2
+  ));
+  assert_eq!(cpu.badvaddr, DATA + 2);
+}
+
+#[test]
+fn unmapped_pc_raises_bus_error() {
+  let mut cpu = setup(&[r_type(0x08, T0, 0, 0, 0)], &[], &[]);
+  cpu.regs[T0] = 0x0040_0000;
+
+  retired(cpu.step());
+  assert!(matches!(
+    cpu.step(),
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::IBUS(0x0040_0000),
+      ..
+    }
+  ));
+  assert_eq!(cpu.badvaddr, 0x0040_0000);
+}
+
+#[test]
+fn protection_fault_records_bad_address() {
+  let mut cpu = setup(&[i_type(0x2B, T0, T1, 8)], &[], &[]);
+  let data = MemRegion {
+    base: DATA,
+    size: 0x100,
+  };
+  cpu.bus.map("data", data, Perm::R);
+  cpu.regs[T0] = DATA;
+
+  assert!(matches!(
+    cpu.step(),
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::PROTECTION(_),
+      badvaddr,
+      ..
+    } if badvaddr == DATA + 8
+  ));
+  assert_eq!(cpu.badvaddr, DATA + 8);
+}
+
+#[test]
+fn every_address_exception_reports_its_address() {
+  for kind in [
+    ExceptionInterrupt::ADDRL(4),
+    ExceptionInterrupt::ADDRS(4),
+    ExceptionInterrupt::IBUS(4),
+    ExceptionInterrupt::DBUS(4),
+  ] {
+    assert_eq!(kind.addr(), Some(4), "{kind}");
+  }
+  assert_eq!(ExceptionInterrupt::OVF.addr(), None);
+}
+
+/* ----- Termination and traps ----- */
+
+#[test]
+fn nop_executes_and_end_of_text_halts() {
+  let mut cpu = Cpu::new();
+  let code = [0u32, i_type(0x09, 0, T0, 5)];
+  cpu
+    .load(code.iter().flat_map(|w| w.to_le_bytes()).collect())
+    .unwrap();
+
+  retired(cpu.step());
+  retired(cpu.step());
+  assert_eq!(cpu.regs[T0], 5);
+  assert!(matches!(cpu.step(), StepOutcome::Halted));
+}
+
+#[test]
+fn halt_address_stops_before_executing() {
+  let mut cpu = setup(&[i_type(0x09, 0, T0, 5)], &[], &[]);
+  cpu.halt_addr = Some(MemMap::HIGHMEM.base);
+  assert!(matches!(cpu.step(), StepOutcome::Halted));
+  assert_eq!(cpu.regs[T0], 0);
+}
+
+#[test]
+fn break_reports_code() {
+  let (cpu, res) = exec_with(r_type(0x0D, 0, 0, 0, 7), &[], &[]);
+  assert!(matches!(res, StepOutcome::Break(7)));
+  assert_eq!(cpu.pc, MemMap::HIGHMEM.base + 4);
+}
+
+#[test]
+fn traps_fire_only_when_condition_holds() {
+  let teq = r_type(0x34, T0, T1, 0, 0);
+  let (_, res) = exec_with(teq, &[(T0, 3), (T1, 3)], &[]);
+  assert!(matches!(res, StepOutcome::Trap));
+  let (_, res) = exec_with(teq, &[(T0, 3), (T1, 4)], &[]);
+  retired(res);
+
+  /* TLTIU compares against the sign-extended immediate as unsigned */
+  let tltiu = i_type(0x01, T0, 0x0B, 0xffff);
+  let (_, res) = exec_with(tltiu, &[(T0, 5)], &[]);
+  assert!(matches!(res, StepOutcome::Trap));
+}
+
+#[test]
+fn exception_leaves_pc_at_faulting_instruction() {
+  let (cpu, res) = exec_with(i_type(0x23, T0, T1, 2), &[(T0, DATA)], &[]);
+  assert!(matches!(
+    res,
+    StepOutcome::Exception { pc, badvaddr, .. } if pc == MemMap::HIGHMEM.base && badvaddr == DATA + 2
+  ));
+  assert_eq!(cpu.pc, MemMap::HIGHMEM.base);
+}
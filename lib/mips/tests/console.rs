@@ -0,0 +1,181 @@
+use std::io::Read;
+
+use mips::{
+  emulator::interrupt::{Interrupt, SoftwareInterrupt},
+  Console, Event, Machine, Register, StopReason, Worker,
+};
+
+/// Greets two people by name, prompting for each.
+const GREET: &str = r#"
+        .data
+prompt: .asciiz "name? "
+hello:  .asciiz "hi "
+buf:    .space 32
+
+        .text
+main:   li    $s0, 2
+loop:   la    $a0, prompt
+        li    $v0, 4
+        syscall
+        li    $a0, 0
+        la    $a1, buf
+        li    $a2, 32
+        li    $v0, 14
+        syscall
+        move  $s1, $v0
+        la    $a0, hello
+        li    $v0, 4
+        syscall
+        li    $a0, 1
+        la    $a1, buf
+        move  $a2, $s1
+        li    $v0, 15
+        syscall
+        subi  $s0, $s0, 1
+        bgtz  $s0, loop
+"#;
+
+fn greet(console: &Console) {
+  Machine::builder()
+    .source(GREET)
+    .console(console)
+    .build()
+    .unwrap()
+    .run()
+    .unwrap();
+}
+
+#[test]
+fn captures_output_and_scripts_input_by_line() {
+  let console = Console::with_lines(["ann", "bob", "cat"]);
+  greet(&console);
+
+  assert_eq!(console.output(), "name? hi ann\nname? hi bob\n");
+  assert_eq!(
+    console.transcript(),
+    "name? ann\nhi ann\nname? bob\nhi bob\n"
+  );
+  assert_eq!(console.remaining_input(), b"cat\n");
+}
+
+#[test]
+fn reads_unterminated_input_to_the_end() {
+  let console = Console::with_input("ann");
+  greet(&console);
+  assert_eq!(console.output(), "name? hi annname? hi ");
+
+  let mut rest = Vec::new();
+  console.stdin().read_to_end(&mut rest).unwrap();
+  assert!(rest.is_empty());
+}
+
+#[test]
+fn works_across_threads() {
+  let console = Console::with_lines(["dee", "eve"]);
+  let attached = console.clone();
+  let worker = Worker::spawn(move || {
+    let machine = Machine::builder().source(GREET).console(&attached);
+    machine.build().unwrap().into_sys()
+  });
+
+  assert!(matches!(
+    worker.events().recv().unwrap(),
+    Event::Stopped(StopReason::Exited(0))
+  ));
+  assert_eq!(console.output(), "name? hi dee\nname? hi eve\n");
+}
+
+fn run_with(src: &str, console: &Console) -> Result<i32, Interrupt> {
+  Machine::builder()
+    .source(src)
+    .console(console)
+    .build()
+    .unwrap()
+    .run()
+}
+
+#[test]
+fn reads_integers_a_line_at_a_time() {
+  let console = Console::with_lines(["40", " 2 ", "7"]);
+  let src = "
+        li    $v0, 5
+        syscall
+        move  $t0, $v0
+        li    $v0, 5
+        syscall
+        addu  $a0, $t0, $v0
+        li    $v0, 1
+        syscall
+  ";
+  run_with(src, &console).unwrap();
+
+  assert_eq!(console.output(), "42");
+  assert_eq!(console.transcript(), "40\n 2 \n42");
+  assert_eq!(console.remaining_input(), b"7\n");
+
+  let console = Console::with_lines(["forty"]);
+  assert!(matches!(
+    run_with("li $v0, 5\nsyscall", &console),
+    Err(Interrupt::Software(SoftwareInterrupt::STDIN(_)))
+  ));
+}
+
+#[test]
+fn reads_strings_up_to_the_buffer_size() {
+  let src = r#"
+        .data
+buf:    .space 8
+
+        .text
+        la    $a0, buf
+        li    $a1, 8
+        li    $v0, 8
+        syscall
+        li    $v0, 4
+        syscall
+        la    $a0, buf
+        li    $a1, 4
+        li    $v0, 8
+        syscall
+        li    $v0, 4
+        syscall
+  "#;
+  let console = Console::with_lines(["ann", "robert"]);
+  run_with(src, &console).unwrap();
+
+  /* The short line keeps its newline; the long one is cut after 3 bytes */
+  assert_eq!(console.output(), "ann\nrob");
+  assert_eq!(console.remaining_input(), b"ert\n");
+}
+
+#[test]
+fn reads_single_characters() {
+  let src = "
+        li    $v0, 12
+        syscall
+        move  $t0, $v0
+        li    $v0, 12
+        syscall
+        move  $t1, $v0
+  ";
+  let console = Console::with_lines(["xy"]);
+  let mut machine = Machine::builder()
+    .source(src)
+    .console(&console)
+    .build()
+    .unwrap();
+  machine.run().unwrap();
+
+  let regs = &machine.sys().cpu().regs;
+  assert_eq!(
+    [regs[Register::T0], regs[Register::T1]],
+    [b'x' as u32, b'y' as u32]
+  );
+  assert_eq!(console.remaining_input(), b"\n");
+
+  let console = Console::new();
+  assert!(matches!(
+    run_with("li $v0, 12\nsyscall", &console),
+    Err(Interrupt::Software(SoftwareInterrupt::STDIN(_)))
+  ));
+}
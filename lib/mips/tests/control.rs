@@ -0,0 +1,121 @@
+use std::{
+  thread,
+  time::{Duration, Instant},
+};
+
+use mips::{
+  emulator::interrupt::{Interrupt, SoftwareInterrupt},
+  Event, LimitKind, Limits, Machine, Register, StopReason, Sys, Worker,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Counts up in `$t0` forever.
+const COUNT: &str = "loop: addiu $t0, $t0, 1\nb loop";
+
+fn sys(src: &str) -> Sys {
+  Machine::builder().source(src).build().unwrap().into_sys()
+}
+
+fn next(worker: &Worker) -> Event {
+  worker.events().recv_timeout(TIMEOUT).unwrap()
+}
+
+#[test]
+fn stop_flag_interrupts_run() {
+  let mut sys = sys("li $t0, 1\nli $t1, 2\nli $v0, 10\nsyscall");
+  sys.stop_flag().request();
+
+  assert!(matches!(
+    sys.run(),
+    Err(Interrupt::Software(SoftwareInterrupt::STOPPED(pc))) if pc == sys.cpu().pc
+  ));
+  assert_eq!(sys.cpu().regs[Register::T0], 1);
+  assert_eq!(sys.cpu().regs[Register::T1], 0);
+
+  /* The request is consumed, so running again finishes the program */
+  assert!(!sys.stop_flag().is_requested());
+  sys.run().unwrap();
+  assert_eq!(sys.cpu().regs[Register::T1], 2);
+}
+
+#[test]
+fn pauses_inspects_and_resumes_a_worker() {
+  let worker = Worker::spawn(|| sys(COUNT));
+  thread::sleep(Duration::from_millis(10));
+
+  /* Inspection leaves the machine running */
+  let first = worker.inspect(|sys| sys.cpu().regs[Register::T0]).unwrap();
+  thread::sleep(Duration::from_millis(10));
+  let second = worker.inspect(|sys| sys.cpu().regs[Register::T0]).unwrap();
+  assert!(second > first);
+
+  worker.pause();
+  assert!(matches!(
+    next(&worker),
+    Event::Stopped(StopReason::Interrupted)
+  ));
+  let paused = worker.inspect(|sys| sys.cpu().regs[Register::T0]).unwrap();
+  thread::sleep(Duration::from_millis(10));
+  assert_eq!(
+    worker.inspect(|sys| sys.cpu().regs[Register::T0]),
+    Some(paused)
+  );
+
+  worker.resume();
+  thread::sleep(Duration::from_millis(10));
+  assert!(worker.inspect(|sys| sys.cpu().regs[Register::T0]).unwrap() > paused);
+  worker.kill();
+}
+
+#[test]
+fn reports_how_the_program_ended() {
+  let worker = Worker::spawn(|| sys("li $a0, 3\nli $v0, 17\nsyscall"));
+  assert!(matches!(
+    next(&worker),
+    Event::Stopped(StopReason::Exited(3))
+  ));
+
+  let worker = Worker::spawn(|| sys("lw $t0, 1($zero)"));
+  assert!(matches!(next(&worker), Event::Faulted(_)));
+
+  /* Breakpoints stop the worker too */
+  let worker = Worker::spawn(|| {
+    let mut sys = sys(COUNT);
+    sys.set_breakpoint(sys.cpu().pc + 4);
+    sys
+  });
+  assert!(matches!(
+    next(&worker),
+    Event::Stopped(StopReason::Breakpoint(_))
+  ));
+}
+
+#[test]
+fn inspecting_does_not_extend_the_time_limit() {
+  let time = Duration::from_millis(50);
+  let worker = Worker::spawn(move || {
+    let mut sys = sys(COUNT);
+    sys.set_limits(Limits {
+      time: Some(time),
+      ..Limits::default()
+    });
+    sys
+  });
+
+  /* Keep interrupting the machine well inside each time slice */
+  let start = Instant::now();
+  let event = loop {
+    if let Ok(event) = worker.events().try_recv() {
+      break event;
+    }
+    assert!(start.elapsed() < TIMEOUT, "the time limit never fired");
+    worker.inspect(|sys| sys.retired());
+    thread::sleep(Duration::from_millis(1));
+  };
+
+  assert!(matches!(
+    event,
+    Event::Stopped(StopReason::LimitExceeded(limit)) if limit.kind == LimitKind::Time(time)
+  ));
+}
@@ -0,0 +1,101 @@
+use std::io::{empty, sink};
+
+use mips::emulator::{arch::Register, cpu::Cpu, debug::StopReason, sys::Sys, virt::MemMap};
+
+const BASE: u32 = MemMap::HIGHMEM.base;
+const FUNC: u32 = BASE + 16;
+
+fn sys() -> Sys {
+  let code: Vec<u8> = [
+    0x0c00_0004u32, // jal func
+    0x2508_0001,    // addiu $t0, $t0, 1
+    0x0c00_0004,    // jal func
+    0x0800_0007,    // j end
+    0x2529_0001,    // func: addiu $t1, $t1, 1
+    0x2529_0001,    // addiu $t1, $t1, 1
+    0x03e0_0008,    // jr $ra
+                    // end:
+  ]
+  .iter()
+  .flat_map(|w| w.to_le_bytes())
+  .collect();
+
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+  Sys::new(cpu, sink(), empty())
+}
+
+#[test]
+fn step_executes_one_instruction() {
+  let mut sys = sys();
+  assert_eq!(sys.step().unwrap(), StopReason::Step);
+  assert_eq!(sys.cpu().pc, FUNC);
+  assert_eq!(sys.step().unwrap(), StopReason::Step);
+  assert_eq!(sys.cpu().regs[Register::T1], 1);
+}
+
+#[test]
+fn continue_stops_at_breakpoints() {
+  let mut sys = sys();
+  assert!(sys.set_breakpoint(FUNC + 4));
+  assert!(!sys.set_breakpoint(FUNC + 4));
+
+  assert_eq!(sys.cont().unwrap(), StopReason::Breakpoint(FUNC + 4));
+  assert_eq!(sys.cpu().regs[Register::T1], 1);
+  assert_eq!(sys.cont().unwrap(), StopReason::Breakpoint(FUNC + 4));
+  assert_eq!(sys.cpu().regs[Register::T1], 3);
+
+  assert!(sys.clear_breakpoint(FUNC + 4));
+  assert_eq!(sys.cont().unwrap(), StopReason::Exited(0));
+  assert_eq!(sys.cpu().regs[Register::T1], 4);
+}
+
+#[test]
+fn step_over_runs_calls_to_completion() {
+  let mut sys = sys();
+  assert_eq!(sys.step_over().unwrap(), StopReason::Step);
+  assert_eq!(sys.cpu().pc, BASE + 4);
+  assert_eq!(sys.cpu().regs[Register::T1], 2);
+
+  /* Not a call: a single step */
+  assert_eq!(sys.step_over().unwrap(), StopReason::Step);
+  assert_eq!(sys.cpu().pc, BASE + 8);
+}
+
+#[test]
+fn step_over_honours_breakpoints_in_callee() {
+  let mut sys = sys();
+  sys.set_breakpoint(FUNC + 8);
+  assert_eq!(sys.step_over().unwrap(), StopReason::Breakpoint(FUNC + 8));
+}
+
+#[test]
+fn step_out_returns_to_caller() {
+  let mut sys = sys();
+  sys.step().unwrap();
+  assert_eq!(sys.step_out().unwrap(), StopReason::Step);
+  assert_eq!(sys.cpu().pc, BASE + 4);
+  assert_eq!(sys.cpu().regs[Register::T1], 2);
+}
+
+#[test]
+fn run_until_reaches_address() {
+  let mut sys = sys();
+  assert_eq!(
+    sys.run_until(BASE + 8).unwrap(),
+    StopReason::Reached(BASE + 8)
+  );
+  assert_eq!(sys.cpu().regs[Register::T0], 1);
+}
+
+#[test]
+fn polling_reports_a_breakpoint_at_the_chunk_boundary() {
+  let mut sys = sys();
+  sys.set_breakpoint(FUNC);
+  assert_eq!(sys.cont_for(1).unwrap(), StopReason::Breakpoint(FUNC));
+  assert_eq!(
+    sys.cont_until(10, |sys| sys.cpu().pc == FUNC + 8).unwrap(),
+    StopReason::Step
+  );
+  assert_eq!(sys.cpu().pc, FUNC + 8);
+}
@@ -0,0 +1,57 @@
+use mips::{
+  asm::{assemble, TEXT_BASE},
+  emulator::{arch::Isa, disasm::disassemble},
+};
+
+#[test]
+fn disassembles_assembled_code() {
+  let program = assemble(
+    "
+main:   addiu $sp, $sp, -8
+        sw    $ra, 4($sp)
+        jal   main
+        beq   $t0, $t1, main
+        sll   $t0, $t1, 3
+        lui   $at, 0x1234
+        jalr  $t9
+        syscall
+        nop
+    ",
+  )
+  .unwrap();
+
+  let lines: Vec<String> = program
+    .text
+    .iter()
+    .enumerate()
+    .map(|(i, &inst)| disassemble(inst, TEXT_BASE + i as u32 * 4, Isa::Mips32))
+    .collect();
+  assert_eq!(
+    lines,
+    [
+      "addiu $sp, $sp, -8",
+      "sw $ra, 4($sp)",
+      "jal 0x80000000",
+      "beq $t0, $t1, 0x80000000",
+      "sll $t0, $t1, 3",
+      "lui $at, 0x1234",
+      "jalr $t9",
+      "syscall",
+      "nop",
+    ]
+  );
+}
+
+#[test]
+fn unknown_words_are_shown_as_data() {
+  assert_eq!(disassemble(0xffff_ffff, 0, Isa::Mips32), ".word 0xffffffff");
+  /* lwl no longer exists in Release 6 */
+  assert_eq!(
+    disassemble(0x8920_0000, 0, Isa::Mips32),
+    "lwl $zero, 0($t1)"
+  );
+  assert_eq!(
+    disassemble(0x8920_0000, 0, Isa::Mips32R6),
+    ".word 0x89200000"
+  );
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use common::{i_type, BASE, DATA};
+use mips::{
+  emulator::{
+    arch::{Endian, Register},
+    cpu::Cpu,
+    elf::ElfError,
+  },
+  Machine,
+};
+
+const BYTES: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+const ORDERS: [Endian; 2] = [Endian::Little, Endian::Big];
+
+const T0: usize = Register::T0;
+const T1: usize = Register::T1;
+
+fn word_bytes(word: u32, endian: Endian) -> [u8; 4] {
+  match endian {
+    Endian::Little => word.to_le_bytes(),
+    Endian::Big => word.to_be_bytes(),
+  }
+}
+
+fn u32_from(bytes: &[u8], endian: Endian) -> u32 {
+  let bytes = bytes.try_into().unwrap();
+  match endian {
+    Endian::Little => u32::from_le_bytes(bytes),
+    Endian::Big => u32::from_be_bytes(bytes),
+  }
+}
+
+/// Runs `code` in the given byte order with `$t0` pointing at `data`,
+/// which is stored at `DATA`.
+fn run(endian: Endian, code: &[u32], data: &[u8], regs: &[(usize, u32)]) -> Cpu {
+  let mut cpu = Cpu::new();
+  cpu.bus.dram.endian = endian;
+  cpu
+    .load(code.iter().flat_map(|&w| word_bytes(w, endian)).collect())
+    .unwrap();
+  cpu.bus.write_bytes(DATA, data).unwrap();
+  cpu.regs[T0] = DATA;
+  for &(reg, value) in regs {
+    cpu.regs[reg] = value;
+  }
+
+  common::run(&mut cpu);
+  cpu
+}
+
+fn bytes_at(cpu: &Cpu, addr: u32, len: usize) -> Vec<u8> {
+  let mut buf = vec![0; len];
+  cpu.bus.dram.read_bytes(addr, &mut buf);
+  buf
+}
+
+/* ----- Aligned loads and stores ----- */
+
+#[test]
+fn loads_follow_the_byte_order() {
+  for endian in ORDERS {
+    let load = |opcode, offset| {
+      let cpu = run(endian, &[i_type(opcode, T0, T1, offset)], &BYTES, &[]);
+      cpu.regs[T1]
+    };
+    let big = endian == Endian::Big;
+
+    assert_eq!(load(0x23, 0), if big { 0x1122_3344 } else { 0x4433_2211 });
+    assert_eq!(load(0x25, 2), if big { 0x3344 } else { 0x4433 });
+    assert_eq!(load(0x21, 6), if big { 0x7788 } else { 0xffff_8877 });
+    assert_eq!(load(0x20, 7), 0xffff_ff88);
+    assert_eq!(load(0x24, 1), 0x22);
+  }
+}
+
+#[test]
+fn stores_follow_the_byte_order() {
+  for endian in ORDERS {
+    let store = |opcode, offset| {
+      let code = [i_type(opcode, T0, T1, offset)];
+      let cpu = run(endian, &code, &[0; 8], &[(T1, 0xaabb_ccdd)]);
+      bytes_at(&cpu, DATA, 8)
+    };
+    let big = endian == Endian::Big;
+
+    let word = store(0x2B, 4);
+    assert_eq!(word[4..], word_bytes(0xaabb_ccdd, endian));
+    let half = store(0x29, 2);
+    assert_eq!(half[2..4], if big { [0xcc, 0xdd] } else { [0xdd, 0xcc] });
+    let byte = store(0x28, 3);
+    assert_eq!(byte, [0, 0, 0, 0xdd, 0, 0, 0, 0]);
+  }
+}
+
+/* ----- Unaligned accesses ----- */
+
+#[test]
+fn lwl_and_lwr_load_partial_words() {
+  /* lwl at offset 1 fills the most significant three bytes on big-endian
+   * and two bytes on little-endian */
+  let lwl = [i_type(0x22, T0, T1, 1)];
+  let cpu = run(Endian::Big, &lwl, &BYTES, &[(T1, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0x2233_44ff);
+  let cpu = run(Endian::Little, &lwl, &BYTES, &[(T1, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0x2211_ffff);
+
+  let lwr = [i_type(0x26, T0, T1, 1)];
+  let cpu = run(Endian::Big, &lwr, &BYTES, &[(T1, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0xffff_1122);
+  let cpu = run(Endian::Little, &lwr, &BYTES, &[(T1, u32::MAX)]);
+  assert_eq!(cpu.regs[T1], 0xff44_3322);
+}
+
+#[test]
+fn lwl_lwr_pair_loads_an_unaligned_word() {
+  for endian in ORDERS {
+    /* Big-endian starts from the left with the lower address */
+    let (left, right) = match endian {
+      Endian::Big => (0, 3),
+      Endian::Little => (3, 0),
+    };
+
+    for offset in 0..4 {
+      let code = [
+        i_type(0x22, T0, T1, offset + left),
+        i_type(0x26, T0, T1, offset + right),
+      ];
+      let cpu = run(endian, &code, &BYTES, &[]);
+      let at = offset as usize;
+      assert_eq!(
+        cpu.regs[T1],
+        u32_from(&BYTES[at..at + 4], endian),
+        "{endian:?} at offset {offset}"
+      );
+    }
+  }
+}
+
+#[test]
+fn swl_swr_pair_stores_an_unaligned_word() {
+  for endian in ORDERS {
+    let (left, right) = match endian {
+      Endian::Big => (0, 3),
+      Endian::Little => (3, 0),
+    };
+
+    for offset in 0..4 {
+      let code = [
+        i_type(0x2A, T0, T1, offset + left),
+        i_type(0x2E, T0, T1, offset + right),
+      ];
+      let cpu = run(endian, &code, &[0xee; 8], &[(T1, 0xaabb_ccdd)]);
+
+      let at = offset as usize;
+      let mut expected = [0xee; 8];
+      expected[at..at + 4].copy_from_slice(&word_bytes(0xaabb_ccdd, endian));
+      assert_eq!(
+        bytes_at(&cpu, DATA, 8),
+        expected,
+        "{endian:?} at offset {offset}"
+      );
+    }
+  }
+}
+
+/* ----- ELF executables ----- */
+
+/// Builds an ELF executable in the given byte order with a text segment
+/// at `text_base` and a data segment holding `data` at `data_base`.
+fn elf(endian: Endian, text_base: u32, text: &[u32], data_base: u32, data: &[u8]) -> Vec<u8> {
+  let half = |out: &mut Vec<u8>, v: u16| match endian {
+    Endian::Little => out.extend_from_slice(&v.to_le_bytes()),
+    Endian::Big => out.extend_from_slice(&v.to_be_bytes()),
+  };
+  let word = |out: &mut Vec<u8>, v: u32| out.extend_from_slice(&word_bytes(v, endian));
+
+  let text: Vec<u8> = text.iter().flat_map(|&w| word_bytes(w, endian)).collect();
+  let text_off = 52 + 2 * 32;
+  let data_off = text_off + text.len();
+
+  let mut out = b"\x7fELF\x01".to_vec();
+  out.push(if endian == Endian::Big { 2 } else { 1 });
+  out.push(1);
+  out.resize(16, 0);
+  half(&mut out, 2);
+  half(&mut out, 8);
+  for v in [1, text_base, 52, 0, 0] {
+    word(&mut out, v);
+  }
+  for v in [52, 32, 2, 40, 0, 0] {
+    half(&mut out, v);
+  }
+
+  let segments = [
+    (text_off, text_base, text.len(), 0x5),
+    (data_off, data_base, data.len(), 0x6),
+  ];
+  for (offset, vaddr, size, flags) in segments {
+    let size = size as u32;
+    for v in [1, offset as u32, vaddr, vaddr, size, size, flags, 4] {
+      word(&mut out, v);
+    }
+  }
+
+  out.extend_from_slice(&text);
+  out.extend_from_slice(data);
+  out
+}
+
+/// lui $t0, hi(data); lw $t1, 0($t0); lbu $t2, 0($t0); sw $t1, 4($t0)
+fn copy_word(data: u32) -> [u32; 4] {
+  [
+    i_type(0x0F, 0, T0, (data >> 16) as u16),
+    i_type(0x23, T0, T1, 0),
+    i_type(0x24, T0, Register::T2, 0),
+    i_type(0x2B, T0, T1, 4),
+  ]
+}
+
+#[test]
+fn elf_sets_the_byte_order() {
+  for endian in ORDERS {
+    let other = if endian == Endian::Big {
+      Endian::Little
+    } else {
+      Endian::Big
+    };
+    let data = BASE + 0x1_0000;
+    let bytes = elf(endian, BASE, &copy_word(data), data, &BYTES[..4]);
+
+    let mut cpu = Cpu::new();
+    cpu.bus.dram.endian = other;
+    cpu.load_elf(&bytes).unwrap();
+    assert_eq!(cpu.bus.dram.endian, endian);
+
+    common::run(&mut cpu);
+    assert_eq!(cpu.regs[T1], u32_from(&BYTES[..4], endian));
+    assert_eq!(cpu.regs[Register::T2], 0x11);
+  }
+}
+
+#[test]
+fn loads_a_big_endian_elf_linked_below_dram() {
+  let text = 0x0040_0000;
+  let data = 0x0041_0000;
+  let bytes = elf(Endian::Big, text, &copy_word(data), data, &BYTES[..4]);
+
+  let mut cpu = Cpu::new();
+  cpu.load_elf(&bytes).unwrap();
+  assert_eq!(cpu.pc, text);
+  assert!(cpu.bus.memory(text, 16).is_some());
+
+  common::run(&mut cpu);
+  assert_eq!(cpu.regs[T1], 0x1122_3344);
+  assert_eq!(cpu.regs[Register::T2], 0x11);
+  assert_eq!(cpu.bus.load(data + 4, 32).unwrap(), 0x1122_3344);
+
+  /* The segments keep the permissions from their program headers */
+  assert!(cpu.bus.store(text, 32, 0).is_err());
+
+  /* Memory that no segment covers is still unmapped */
+  assert!(cpu.bus.load(0x0050_0000, 32).is_err());
+}
+
+#[test]
+fn elf_segments_must_fit_in_memory() {
+  /* Larger than DRAM, and wrapping past the top of the address space */
+  for (data, mem_size) in [(BASE + 0x1_0000, 0x4000_0000u32), (0xffff_f000, 0x2000)] {
+    let mut bytes = elf(Endian::Little, BASE, &copy_word(data), data, &BYTES[..4]);
+    /* p_memsz of the data segment */
+    bytes[104..108].copy_from_slice(&mem_size.to_le_bytes());
+
+    let mut cpu = Cpu::new();
+    let err = cpu.load_elf(&bytes).unwrap_err();
+    assert!(matches!(err, ElfError::SIZE(addr, size) if addr == data && size == mem_size));
+  }
+}
+
+#[test]
+fn machine_runs_an_elf_linked_below_dram() {
+  let text = 0x0040_0000;
+  let data = 0x0041_0000;
+  let mut machine = Machine::builder()
+    .elf(elf(Endian::Big, text, &copy_word(data), data, &BYTES[..4]))
+    .build()
+    .unwrap();
+
+  assert_eq!(machine.run().unwrap(), 0);
+  let sys = machine.sys();
+  assert_eq!(sys.cpu().regs[T1], 0x1122_3344);
+  assert_eq!(sys.cpu().bus.load(data + 4, 32).unwrap(), 0x1122_3344);
+}
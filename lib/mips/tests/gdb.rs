@@ -0,0 +1,218 @@
+use std::{
+  io::{empty, sink, Read, Write},
+  net::{TcpListener, TcpStream},
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+use mips::{
+  emulator::{arch::Register, cpu::Cpu, limits::Limits, sys::Sys},
+  gdb::GdbStub,
+};
+
+/// A minimal gdb: sends one packet at a time and reads the reply.
+struct Client {
+  stream: TcpStream,
+}
+
+impl Client {
+  fn read_byte(&mut self) -> u8 {
+    let mut byte = [0];
+    self.stream.read_exact(&mut byte).unwrap();
+    byte[0]
+  }
+
+  fn send_raw(&mut self, data: &str) {
+    let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    write!(self.stream, "${}#{:02x}", data, sum).unwrap();
+    assert_eq!(self.read_byte(), b'+');
+  }
+
+  fn recv(&mut self) -> String {
+    while self.read_byte() != b'$' {}
+    let mut data = Vec::new();
+    loop {
+      match self.read_byte() {
+        b'#' => break,
+        byte => data.push(byte),
+      }
+    }
+    self.read_byte();
+    self.read_byte();
+    self.stream.write_all(b"+").unwrap();
+    String::from_utf8(data).unwrap()
+  }
+
+  fn request(&mut self, data: &str) -> String {
+    self.send_raw(data);
+    self.recv()
+  }
+}
+
+/// Serves a program on a local port. The server thread hands back the CPU
+/// and the number of breakpoints left once gdb detaches.
+fn connect(code: &'static [u32]) -> (Client, JoinHandle<(Cpu, usize)>) {
+  connect_with(code, Limits::default())
+}
+
+fn connect_with(code: &'static [u32], limits: Limits) -> (Client, JoinHandle<(Cpu, usize)>) {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+
+  let server = thread::spawn(move || {
+    let mut cpu = Cpu::new();
+    cpu
+      .load(code.iter().flat_map(|w| w.to_le_bytes()).collect())
+      .unwrap();
+    let mut sys = Sys::new(cpu, sink(), empty());
+    sys.set_limits(limits);
+
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_nodelay(true).unwrap();
+    let sys = GdbStub::new(stream, sys).serve().unwrap();
+    (sys.cpu().clone(), sys.breakpoints().count())
+  });
+
+  let stream = TcpStream::connect(addr).unwrap();
+  stream.set_nodelay(true).unwrap();
+  (Client { stream }, server)
+}
+
+const CALLS: &[u32] = &[
+  0x0c00_0004, // jal func
+  0x2508_0001, // addiu $t0, $t0, 1
+  0x0c00_0004, // jal func
+  0x0800_0007, // j end
+  0x2529_0001, // func: addiu $t1, $t1, 1
+  0x2529_0001, // addiu $t1, $t1, 1
+  0x03e0_0008, // jr $ra
+];
+
+#[test]
+fn describes_target() {
+  let (mut gdb, server) = connect(CALLS);
+
+  let features = gdb.request("qSupported:swbreak+;hwbreak+");
+  assert!(features.contains("qXfer:features:read+"));
+
+  let xml = gdb.request("qXfer:features:read:target.xml:0,ffff");
+  assert!(xml.starts_with('l'));
+  for feature in ["mips.cpu", "mips.cp0", "mips.fpu"] {
+    assert!(xml.contains(&format!("org.gnu.gdb.{feature}")));
+  }
+
+  assert_eq!(gdb.request("?"), "S05");
+  assert_eq!(gdb.request("D"), "OK");
+  server.join().unwrap();
+}
+
+#[test]
+fn reads_and_writes_registers_and_memory() {
+  let (mut gdb, server) = connect(CALLS);
+
+  assert_eq!(gdb.request("g").len(), 72 * 8);
+  assert_eq!(gdb.request("p25"), "00000080");
+  assert_eq!(gdb.request("P8=07000000"), "OK");
+  assert_eq!(gdb.request("p8"), "07000000");
+
+  assert_eq!(gdb.request("m80000000,4"), "0400000c");
+  assert_eq!(gdb.request("M80000100,4:2a000000"), "OK");
+  assert_eq!(gdb.request("m80000100,4"), "2a000000");
+  assert_eq!(gdb.request("m00000000,4"), "E14");
+
+  assert_eq!(gdb.request("D"), "OK");
+  let (cpu, _) = server.join().unwrap();
+  assert_eq!(cpu.regs[Register::T0], 7);
+  assert_eq!(cpu.bus.load(0x8000_0100, 32).unwrap(), 42);
+}
+
+#[test]
+fn breakpoints_step_and_continue() {
+  let (mut gdb, server) = connect(CALLS);
+  gdb.request("qSupported:swbreak+");
+
+  assert_eq!(gdb.request("Z0,80000014,4"), "OK");
+  assert_eq!(gdb.request("c"), "T05swbreak:;");
+  assert_eq!(gdb.request("p25"), "14000080");
+
+  assert_eq!(gdb.request("s"), "S05");
+  assert_eq!(gdb.request("p25"), "18000080");
+
+  assert_eq!(gdb.request("z0,80000014,4"), "OK");
+  assert_eq!(gdb.request("Z1,80000008,4"), "OK");
+  assert_eq!(gdb.request("c"), "T05hwbreak:;");
+  assert_eq!(gdb.request("c"), "W00");
+
+  gdb.request("D");
+  let (cpu, breakpoints) = server.join().unwrap();
+  assert_eq!(cpu.regs[Register::T1], 4);
+  assert_eq!(breakpoints, 0);
+}
+
+#[test]
+fn watchpoints_report_address() {
+  let (mut gdb, server) = connect(&[
+    0x3c09_8000, // lui $t1, 0x8000
+    0x2408_0007, // addiu $t0, $0, 7
+    0xad28_0100, // sw $t0, 0x100($t1)
+  ]);
+
+  assert_eq!(gdb.request("Z2,80000100,4"), "OK");
+  assert_eq!(gdb.request("c"), "T05watch:80000100;");
+  assert_eq!(gdb.request("c"), "W00");
+
+  gdb.request("D");
+  server.join().unwrap();
+}
+
+#[test]
+fn exceptions_report_signal_and_message() {
+  let (mut gdb, server) = connect(&[
+    0x3c09_8000, // lui $t1, 0x8000
+    0x8d28_0102, // lw $t0, 0x102($t1)
+  ]);
+
+  let output = gdb.request("c");
+  assert!(output.starts_with('O'));
+  assert_eq!(gdb.recv(), "S0b");
+
+  gdb.request("D");
+  server.join().unwrap();
+}
+
+#[test]
+fn interrupt_stops_running_target() {
+  let (mut gdb, server) = connect(&[
+    0x1000_ffff, // loop: beq $0, $0, loop
+  ]);
+
+  gdb.send_raw("c");
+  gdb.stream.write_all(&[0x03]).unwrap();
+  assert_eq!(gdb.recv(), "S02");
+
+  gdb.request("D");
+  server.join().unwrap();
+}
+
+#[test]
+fn continue_stops_at_the_time_limit() {
+  /* Polling for an interrupt must not restart the clock */
+  let limits = Limits {
+    time: Some(Duration::from_millis(20)),
+    ..Limits::default()
+  };
+  let (mut gdb, server) = connect_with(
+    &[
+      0x1000_ffff, // loop: beq $0, $0, loop
+    ],
+    limits,
+  );
+  let timeout = Some(Duration::from_secs(10));
+  gdb.stream.set_read_timeout(timeout).unwrap();
+
+  assert_eq!(gdb.request("c"), "S05");
+  assert_eq!(gdb.request("p25"), "00000080");
+
+  gdb.request("D");
+  server.join().unwrap();
+}
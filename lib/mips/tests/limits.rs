@@ -0,0 +1,155 @@
+use std::{cell::RefCell, io::Write, rc::Rc, time::Duration};
+
+use mips::{
+  asm::TEXT_BASE,
+  emulator::interrupt::{Interrupt, SoftwareInterrupt},
+  LimitExceeded, LimitKind, Limits, Machine, StopReason,
+};
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+const SPIN: &str = "loop: b loop";
+
+fn limit(res: Result<i32, Interrupt>) -> LimitExceeded {
+  match res {
+    Err(Interrupt::Software(SoftwareInterrupt::LIMIT(limit))) => limit,
+    other => panic!("expected a limit, got {other:?}"),
+  }
+}
+
+fn machine(src: &str, limits: Limits) -> Machine {
+  Machine::builder()
+    .source(src)
+    .limits(limits)
+    .build()
+    .unwrap()
+}
+
+#[test]
+fn stops_after_the_instruction_budget() {
+  let limits = Limits {
+    instructions: Some(1000),
+    ..Limits::default()
+  };
+  let mut spin = machine(SPIN, limits);
+  assert_eq!(
+    limit(spin.run()),
+    LimitExceeded {
+      kind: LimitKind::Instructions(1000),
+      pc: TEXT_BASE,
+    }
+  );
+  assert_eq!(spin.sys().retired(), 1000);
+
+  /* A program finishing within the budget is unaffected */
+  let mut exits = machine("li $a0, 4\nli $v0, 17\nsyscall", limits);
+  assert_eq!(exits.run().unwrap(), 4);
+}
+
+#[test]
+fn a_program_may_use_its_whole_budget() {
+  let three = "li $t0, 1\nli $t1, 2\nli $t2, 3";
+  let limits = |n| Limits {
+    instructions: Some(n),
+    ..Limits::default()
+  };
+  let mut exact = machine(three, limits(3));
+  exact.run().unwrap();
+  assert_eq!(exact.sys().retired(), 3);
+
+  let mut short = machine(three, limits(2));
+  assert_eq!(limit(short.run()).kind, LimitKind::Instructions(2));
+}
+
+#[test]
+fn restore_rewinds_the_counts() {
+  let out = Output::default();
+  let mut machine = Machine::builder()
+    .source("li $a0, 7\nli $v0, 1\nsyscall")
+    .limits(Limits {
+      instructions: Some(3),
+      output: Some(1),
+      ..Limits::default()
+    })
+    .stdout(out.clone())
+    .build()
+    .unwrap();
+  let snapshot = machine.sys().snapshot();
+
+  for _ in 0..2 {
+    machine.run().unwrap();
+    assert_eq!(machine.sys().retired(), 3);
+    machine.sys_mut().restore(&snapshot);
+    assert_eq!(machine.sys().retired(), 0);
+  }
+  assert_eq!(&*out.0.borrow(), b"77");
+}
+
+#[test]
+fn stops_after_the_time_budget() {
+  let time = Duration::from_millis(20);
+  let mut spin = machine(
+    SPIN,
+    Limits {
+      time: Some(time),
+      ..Limits::default()
+    },
+  );
+  assert_eq!(limit(spin.run()).kind, LimitKind::Time(time));
+}
+
+#[test]
+fn truncates_output_at_the_limit() {
+  let out = Output::default();
+  let mut machine = Machine::builder()
+    .source("li $a0, 7\nloop: li $v0, 1\nsyscall\nb loop")
+    .limits(Limits {
+      output: Some(10),
+      ..Limits::default()
+    })
+    .stdout(out.clone())
+    .build()
+    .unwrap();
+
+  assert_eq!(limit(machine.run()).kind, LimitKind::Output(10));
+  assert_eq!(&*out.0.borrow(), b"7777777777");
+}
+
+#[test]
+fn reports_limits_to_the_debugger() {
+  let mut spin = machine(
+    SPIN,
+    Limits {
+      instructions: Some(10),
+      ..Limits::default()
+    },
+  );
+  let sys = spin.sys_mut();
+  assert_eq!(
+    sys.cont().unwrap(),
+    StopReason::LimitExceeded(LimitExceeded {
+      kind: LimitKind::Instructions(10),
+      pc: TEXT_BASE,
+    })
+  );
+
+  /* Raising the limit lets execution continue */
+  sys.set_limits(Limits {
+    instructions: Some(20),
+    ..Limits::default()
+  });
+  assert!(matches!(sys.cont(), Ok(StopReason::LimitExceeded(_))));
+  assert_eq!(sys.retired(), 20);
+}
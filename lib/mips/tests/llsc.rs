@@ -0,0 +1,124 @@
+mod common;
+
+use common::{i_type, run, BASE, DATA};
+use mips::emulator::{
+  arch::Register,
+  cpu::{Cpu, StepOutcome},
+  disasm::disassemble,
+  interrupt::ExceptionInterrupt,
+};
+
+const ERET: u32 = 0x4200_0018;
+
+const T0: usize = Register::T0;
+const T1: usize = Register::T1;
+const T2: usize = Register::T2;
+
+fn ll(rt: usize, imm: u16) -> u32 {
+  i_type(0x30, T0, rt, imm)
+}
+
+fn sc(rt: usize, imm: u16) -> u32 {
+  i_type(0x38, T0, rt, imm)
+}
+
+fn sw(rt: usize, imm: u16) -> u32 {
+  i_type(0x2B, T0, rt, imm)
+}
+
+/// Loads `code` at the reset PC with `$t0` pointing at a zeroed word and
+/// `$t2` holding the value to store conditionally.
+fn setup(code: &[u32]) -> Cpu {
+  common::setup(code, &[], &[(T0, DATA), (T2, 0x1234)])
+}
+
+#[test]
+fn sc_succeeds_while_the_reservation_holds() {
+  let mut cpu = setup(&[ll(T1, 0), sc(T2, 0)]);
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 1);
+  assert_eq!(cpu.bus.load(DATA, 32).unwrap(), 0x1234);
+
+  /* The reservation is consumed */
+  assert!(!cpu.bus.monitor.is_linked(cpu.id, DATA));
+}
+
+#[test]
+fn sc_fails_without_a_reservation() {
+  let mut cpu = setup(&[sc(T2, 0)]);
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 0);
+  assert_eq!(cpu.bus.load(DATA, 32).unwrap(), 0);
+}
+
+#[test]
+fn store_to_the_linked_line_breaks_the_reservation() {
+  let mut cpu = setup(&[ll(T1, 0), sw(T1, 4), sc(T2, 0)]);
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 0);
+  assert_eq!(cpu.bus.load(DATA, 32).unwrap(), 0);
+
+  /* A store to another line leaves it alone */
+  let mut cpu = setup(&[ll(T1, 0), sw(T1, 0x40), sc(T2, 0)]);
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 1);
+}
+
+#[test]
+fn host_writes_break_the_reservation() {
+  let mut cpu = setup(&[ll(T1, 0), sc(T2, 0)]);
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  cpu.bus.write_bytes(DATA + 8, b"x").unwrap();
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 0);
+}
+
+#[test]
+fn wide_writes_break_reservations_on_every_line_they_cover() {
+  let mut cpu = setup(&[ll(T1, 0x20), sc(T2, 0x20)]);
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  cpu.bus.write_bytes(DATA, &[0; 64]).unwrap();
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 0);
+}
+
+#[test]
+fn eret_breaks_the_reservation() {
+  let mut cpu = setup(&[ll(T1, 0), ERET, sc(T2, 0)]);
+  cpu.epc = BASE + 8;
+
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  assert_eq!(cpu.pc, BASE + 8);
+  run(&mut cpu);
+  assert_eq!(cpu.regs[T2], 0);
+  assert_eq!(disassemble(ERET, BASE, Default::default()), "eret");
+}
+
+#[test]
+fn exceptions_leave_the_reservation_for_eret() {
+  /* ll; lw from a misaligned address; sc */
+  let mut cpu = setup(&[ll(T1, 0), i_type(0x23, T0, T1, 2), sc(T2, 0)]);
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  assert!(matches!(cpu.step(), StepOutcome::Exception { .. }));
+  assert_eq!(cpu.epc, BASE + 4);
+  assert!(cpu.bus.monitor.is_linked(cpu.id, DATA));
+}
+
+#[test]
+fn misaligned_sc_raises_an_address_error() {
+  for code in [vec![sc(T2, 2)], vec![ll(T1, 0), sc(T2, 2)]] {
+    let mut cpu = setup(&code);
+    let res = loop {
+      match cpu.step() {
+        StepOutcome::Retired => {}
+        other => break other,
+      }
+    };
+    assert!(matches!(
+      res,
+      StepOutcome::Exception { kind: ExceptionInterrupt::ADDRS(addr), .. } if addr == DATA + 2
+    ));
+    assert_eq!(cpu.regs[T2], 0x1234);
+  }
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::{i_type, r_type, retired, BASE, DATA};
+use mips::emulator::{
+  arch::Register,
+  cpu::{Cpu, LoadHazard},
+};
+
+const T0: usize = Register::T0;
+const T1: usize = Register::T1;
+const T2: usize = Register::T2;
+const T3: usize = Register::T3;
+
+fn lw(rt: usize, imm: u16, base: usize) -> u32 {
+  i_type(0x23, base, rt, imm)
+}
+
+fn addu(rd: usize, rs: usize, rt: usize) -> u32 {
+  r_type(0x21, rs, rt, rd, 0)
+}
+
+/// Loads `code` at the reset PC and `data` at `DATA`, with the MIPS I
+/// load-delay model enabled and `$t0` pointing at the data.
+fn setup(code: &[u32], data: &[u8]) -> Cpu {
+  let mut cpu = common::setup(code, data, &[(T0, DATA)]);
+  cpu.text_end = Some(BASE + 4 * code.len() as u32);
+  cpu.load_delay = true;
+  cpu
+}
+
+fn run(cpu: &mut Cpu, steps: usize) {
+  for _ in 0..steps {
+    retired(cpu.step());
+  }
+}
+
+#[test]
+fn loaded_value_lands_after_the_next_instruction() {
+  let code = [lw(T1, 0, T0), addu(T2, T1, 0), addu(T3, T1, 0)];
+  let mut cpu = setup(&code, &[7, 0, 0, 0]);
+  cpu.regs[T1] = 1;
+
+  run(&mut cpu, 3);
+  assert_eq!(cpu.regs[T2], 1);
+  assert_eq!(cpu.regs[T3], 7);
+}
+
+#[test]
+fn loads_land_immediately_without_the_delay_model() {
+  let code = [lw(T1, 0, T0), addu(T2, T1, 0)];
+  let mut cpu = setup(&code, &[7, 0, 0, 0]);
+  cpu.load_delay = false;
+
+  run(&mut cpu, 2);
+  assert_eq!(cpu.regs[T2], 7);
+}
+
+#[test]
+fn write_in_the_delay_slot_cancels_the_load() {
+  let code = [lw(T1, 0, T0), i_type(0x09, 0, T1, 3), 0];
+  let mut cpu = setup(&code, &[7, 0, 0, 0]);
+
+  run(&mut cpu, 3);
+  assert_eq!(cpu.regs[T1], 3);
+}
+
+#[test]
+fn lwl_and_lwr_merge_with_a_load_in_flight() {
+  /* The little-endian unaligned load idiom, back to back */
+  let code = [i_type(0x26, T0, T1, 1), i_type(0x22, T0, T1, 4), 0];
+  let mut cpu = setup(&code, &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+  cpu.regs[T1] = 0xffff_ffff;
+  cpu.load_hazards.enabled = true;
+
+  run(&mut cpu, 3);
+  assert_eq!(cpu.regs[T1], 0x5544_3322);
+  assert!(cpu.load_hazards.is_empty());
+}
+
+#[test]
+fn hazards_are_recorded_only_when_enabled() {
+  let code = [lw(T1, 0, T0), addu(T2, T1, 0)];
+  let mut cpu = setup(&code, &[7, 0, 0, 0]);
+  run(&mut cpu, 2);
+  assert!(cpu.load_hazards.is_empty());
+
+  let mut cpu = setup(&code, &[7, 0, 0, 0]);
+  cpu.load_hazards.enabled = true;
+  run(&mut cpu, 2);
+  assert_eq!(
+    cpu.load_hazards.take(),
+    [LoadHazard {
+      pc: BASE + 4,
+      reg: T1
+    }]
+  );
+  assert!(cpu.load_hazards.is_empty());
+}
+
+#[test]
+fn hazard_log_keeps_only_the_most_recent_entries() {
+  /* loop: lw $t1, 0($t0); addu $t2, $t1, $zero; b loop */
+  let code = [lw(T1, 0, T0), addu(T2, T1, 0), i_type(0x04, 0, 0, 0xfffd)];
+  let mut cpu = setup(&code, &[7, 0, 0, 0]);
+  cpu.load_hazards.enabled = true;
+  cpu.load_hazards.capacity = 4;
+
+  run(&mut cpu, 30);
+  assert_eq!(cpu.load_hazards.len(), 4);
+  assert!(cpu.load_hazards.iter().all(|h| h.pc == BASE + 4));
+}
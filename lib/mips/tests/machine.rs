@@ -0,0 +1,173 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use mips::{
+  emulator::{
+    interrupt::{Interrupt, SoftwareInterrupt},
+    sys::{ARGS_GAP, HEAP_BASE},
+    virt::MemMap,
+  },
+  Endian, Machine, MachineError, Register, Syscalls,
+};
+
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+const CAT: &str = r#"
+        .data
+path:   .asciiz "in.txt"
+buf:    .space 16
+
+        .text
+main:   la    $a0, path
+        li    $a1, 0
+        li    $v0, 13
+        syscall
+        move  $a0, $v0
+        la    $a1, buf
+        li    $a2, 16
+        li    $v0, 14
+        syscall
+        move  $a2, $v0
+        li    $a0, 1
+        li    $v0, 15
+        syscall
+        li    $a0, 2
+        li    $v0, 17
+        syscall
+"#;
+
+#[test]
+fn builds_and_runs_a_program() {
+  let out = Output::default();
+  let mut machine = Machine::builder()
+    .source(CAT)
+    .file("in.txt", "contents")
+    .stdout(out.clone())
+    .build()
+    .unwrap();
+
+  assert!(machine.program().unwrap().symbols.contains_key("buf"));
+  assert_eq!(machine.run().unwrap(), 2);
+  assert_eq!(&*out.0.borrow(), b"contents");
+}
+
+#[test]
+fn applies_the_memory_layout() {
+  let machine = Machine::builder()
+    .memory(256 * 1024 * 1024)
+    .stack_size(1024 * 1024)
+    .build()
+    .unwrap();
+  let stack = machine.sys().stack();
+  assert_eq!(stack.top, MemMap::HIGHMEM.base + 256 * 1024 * 1024 - 4);
+  assert_eq!(stack.top - stack.limit, 1024 * 1024 - 4);
+  assert_eq!(machine.sys().cpu().regs[Register::SP], stack.top);
+
+  let err = Machine::builder()
+    .memory(HEAP_BASE - MemMap::HIGHMEM.base)
+    .build()
+    .err();
+  assert!(matches!(err, Some(MachineError::LAYOUT(..))));
+}
+
+#[test]
+fn assembles_in_the_requested_byte_order() {
+  let src = "
+        .data
+word:   .word 0x11223344
+        .text
+        la    $t0, word
+        lw    $t1, 0($t0)
+        lbu   $t2, 0($t0)
+  ";
+  let mut machine = Machine::builder()
+    .endian(Endian::Big)
+    .source(src)
+    .build()
+    .unwrap();
+  machine.run().unwrap();
+
+  let cpu = machine.sys().cpu();
+  assert_eq!(cpu.regs[Register::T1], 0x1122_3344);
+  assert_eq!(cpu.regs[Register::T2], 0x11);
+}
+
+#[test]
+fn restricts_the_syscall_profile() {
+  /* MARS service 30 (system time) is not part of SPIM */
+  let src = "li $v0, 30\nsyscall";
+  let run = |syscalls| {
+    Machine::builder()
+      .syscalls(syscalls)
+      .source(src)
+      .build()
+      .unwrap()
+      .run()
+  };
+
+  assert!(run(Syscalls::Mars).is_ok());
+  assert!(matches!(
+    run(Syscalls::Spim),
+    Err(Interrupt::Software(SoftwareInterrupt::UNSUPPORTED(30)))
+  ));
+}
+
+#[test]
+fn lays_out_program_arguments_like_mars() {
+  let machine = Machine::builder().args(["ab", "c"]).build().unwrap();
+  let sys = machine.sys();
+  let (top, bus, r) = (sys.stack().top, &sys.cpu().bus, &sys.cpu().regs);
+  let word = |addr| bus.load(addr, 32).unwrap();
+
+  /* Strings packed down from the top, first argument highest */
+  let mut strings = [0u8; 6];
+  bus.dram.read_bytes(top - 5, &mut strings);
+  assert_eq!(&strings, b"\0c\0ab\0");
+
+  /* argc and argv start 4 KiB lower */
+  let sp = top - ARGS_GAP - 12;
+  assert_eq!(r[Register::SP], sp);
+  assert_eq!(r[Register::A0], 2);
+  assert_eq!(r[Register::A1], sp + 4);
+  assert_eq!(
+    [word(sp), word(sp + 4), word(sp + 8), word(sp + 12)],
+    [2, top - 2, top - 4, 0]
+  );
+
+  /* Strings longer than the gap push the array below them */
+  let long = "x".repeat(5000);
+  let machine = Machine::builder().args([long]).build().unwrap();
+  let sys = machine.sys();
+  let top = sys.stack().top;
+  assert_eq!(sys.cpu().regs[Register::SP], top - 5016);
+  assert_eq!(sys.cpu().bus.load(top - 5012, 32).unwrap(), top - 5000);
+}
+
+#[test]
+fn passes_arguments_to_the_program() {
+  let src = "
+        lw    $t0, 4($a1)
+        lbu   $t1, 0($t0)
+        lw    $t2, 0($sp)
+  ";
+  let mut machine = Machine::builder()
+    .args(["prog", "xyz"])
+    .source(src)
+    .build()
+    .unwrap();
+  machine.run().unwrap();
+  let r = &machine.sys().cpu().regs;
+  assert_eq!(r[Register::T1], b'x' as u32);
+  assert_eq!(r[Register::T2], 2);
+}
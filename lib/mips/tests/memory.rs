@@ -0,0 +1,78 @@
+use mips::emulator::memory::{FlatMemory, Memory, SparseMemory, PAGE_SIZE};
+
+#[test]
+fn sparse_reads_zero_without_allocating() {
+  let mem = SparseMemory::new(1 << 30);
+  let mut buf = [0xff; 8];
+  mem.read(123_456, &mut buf);
+
+  assert_eq!(buf, [0; 8]);
+  assert_eq!(mem.resident_pages(), 0);
+}
+
+#[test]
+fn sparse_write_across_page_boundary() {
+  let mut mem = SparseMemory::new(1 << 30);
+  let offset = PAGE_SIZE as u32 - 2;
+  mem.write(offset, &[1, 2, 3, 4]);
+
+  let mut buf = [0; 4];
+  mem.read(offset, &mut buf);
+  assert_eq!(buf, [1, 2, 3, 4]);
+  assert_eq!(mem.resident_pages(), 2);
+}
+
+#[test]
+fn sparse_and_flat_agree() {
+  let mut sparse = SparseMemory::new(4 * PAGE_SIZE as u32);
+  let mut flat = FlatMemory::new(4 * PAGE_SIZE as u32);
+  let data: Vec<u8> = (0..=255).cycle().take(PAGE_SIZE + 100).collect();
+
+  sparse.write(PAGE_SIZE as u32 / 2, &data);
+  flat.write(PAGE_SIZE as u32 / 2, &data);
+
+  let mut a = vec![0; 4 * PAGE_SIZE];
+  let mut b = vec![0; 4 * PAGE_SIZE];
+  sparse.read(0, &mut a);
+  flat.read(0, &mut b);
+  assert_eq!(a, b);
+}
+
+#[test]
+fn cloned_sparse_memory_is_independent() {
+  let mut mem = SparseMemory::new(PAGE_SIZE as u32);
+  mem.write(0, &[1]);
+
+  let mut copy = mem.box_clone();
+  copy.write(0, &[2]);
+
+  let mut buf = [0; 1];
+  mem.read(0, &mut buf);
+  assert_eq!(buf, [1]);
+}
+
+#[test]
+fn sparse_pages_stop_at_the_end_of_memory() {
+  let size = PAGE_SIZE as u32 + 10;
+  let mut sparse = SparseMemory::new(size);
+  let mut flat = FlatMemory::new(size);
+  sparse.write(size - 4, &[1, 2, 3, 4]);
+  flat.write(size - 4, &[1, 2, 3, 4]);
+
+  let pages = |mem: &dyn Memory| {
+    let mut pages = Vec::new();
+    mem.for_each_page(&mut |offset, bytes| pages.push((offset, bytes.to_vec())));
+    pages
+  };
+  let sparse_pages = pages(&sparse);
+  assert_eq!(sparse_pages.len(), 1);
+  assert_eq!(sparse_pages[0].1.len(), 10);
+  assert_eq!(sparse_pages, pages(&flat));
+}
+
+#[test]
+#[should_panic]
+fn sparse_write_past_the_end_panics() {
+  let mut mem = SparseMemory::new(PAGE_SIZE as u32);
+  mem.write(PAGE_SIZE as u32 - 2, &[1, 2, 3, 4]);
+}
@@ -0,0 +1,153 @@
+use mips::emulator::{
+  cpu::{Cpu, StepOutcome},
+  interrupt::{ExceptionInterrupt, Interrupt},
+  virt::{Access, MemMap, MemRegion, Perm},
+};
+
+const BASE: u32 = MemMap::HIGHMEM.base;
+
+/// Builds a little-endian ELF with a text segment at `BASE` and a data
+/// segment one page above it.
+fn elf(text: &[u32]) -> Vec<u8> {
+  let text: Vec<u8> = text.iter().flat_map(|w| w.to_le_bytes()).collect();
+  let data = [0u8; 16];
+  let text_off = 52 + 2 * 32;
+  let data_off = text_off + text.len();
+
+  let mut out = Vec::new();
+  out.extend_from_slice(b"\x7fELF\x01\x01\x01");
+  out.resize(16, 0);
+  for half in [2u16, 8] {
+    out.extend_from_slice(&half.to_le_bytes());
+  }
+  for word in [1u32, BASE, 52, 0, 0] {
+    out.extend_from_slice(&word.to_le_bytes());
+  }
+  for half in [52u16, 32, 2, 40, 0, 0] {
+    out.extend_from_slice(&half.to_le_bytes());
+  }
+
+  let segments = [
+    (text_off, BASE, text.len(), 0x5),
+    (data_off, BASE + 0x1000, data.len(), 0x6),
+  ];
+  for (offset, vaddr, size, flags) in segments {
+    for word in [
+      1,
+      offset as u32,
+      vaddr,
+      vaddr,
+      size as u32,
+      size as u32,
+      flags,
+      4,
+    ] {
+      out.extend_from_slice(&word.to_le_bytes());
+    }
+  }
+
+  out.extend_from_slice(&text);
+  out.extend_from_slice(&data);
+  out
+}
+
+fn violation(res: StepOutcome) -> (u32, Access, String) {
+  match res {
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::PROTECTION(v),
+      ..
+    } => (v.addr, v.access, v.region),
+    other => panic!("expected a protection violation, got {other:?}"),
+  }
+}
+
+#[test]
+fn elf_text_is_read_only() {
+  let mut cpu = Cpu::new();
+  cpu
+    .load_elf(&elf(&[
+      0x3c09_8000, // lui $t1, 0x8000
+      0xad20_0000, // sw $zero, 0($t1)
+    ]))
+    .unwrap();
+
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  let (addr, access, region) = violation(cpu.step());
+  assert_eq!(
+    (addr, access, region.as_str()),
+    (BASE, Access::Write, ".text")
+  );
+}
+
+#[test]
+fn raw_image_is_read_only() {
+  let code: Vec<u8> = [
+    0x3c09_8000u32, // lui $t1, 0x8000
+    0xad20_0000,    // sw $zero, 0($t1)
+  ]
+  .iter()
+  .flat_map(|w| w.to_le_bytes())
+  .collect();
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  let (addr, access, region) = violation(cpu.step());
+  assert_eq!(
+    (addr, access, region.as_str()),
+    (BASE, Access::Write, ".text")
+  );
+
+  /* Memory past the image keeps the default rights */
+  assert!(cpu.bus.store(BASE + 8, 32, 0).is_ok());
+}
+
+#[test]
+fn elf_data_is_not_executable() {
+  let mut cpu = Cpu::new();
+  cpu
+    .load_elf(&elf(&[
+      0x3c09_8000, // lui $t1, 0x8000
+      0x3529_1000, // ori $t1, $t1, 0x1000
+      0x0120_0008, // jr $t1
+    ]))
+    .unwrap();
+
+  for _ in 0..3 {
+    assert!(matches!(cpu.step(), StepOutcome::Retired));
+  }
+  let (addr, access, region) = violation(cpu.step());
+  assert_eq!(
+    (addr, access, region.as_str()),
+    (BASE + 0x1000, Access::Execute, ".data")
+  );
+}
+
+#[test]
+fn guard_region_blocks_all_access() {
+  let mut cpu = Cpu::new();
+  let guard = MemRegion {
+    base: BASE + 0x2000,
+    size: 0x1000,
+  };
+  cpu.bus.map("guard", guard, Perm::NONE);
+
+  assert!(cpu.bus.load(BASE + 0x1ffc, 32).is_ok());
+  assert!(matches!(
+    cpu.bus.load(BASE + 0x2000, 32),
+    Err(Interrupt::Exception(ExceptionInterrupt::PROTECTION(_)))
+  ));
+  assert!(cpu.bus.load(BASE + 0x3000, 32).is_ok());
+}
+
+#[test]
+fn violation_message_names_region() {
+  let mut cpu = Cpu::new();
+  cpu.load_elf(&elf(&[0x3c09_8000])).unwrap();
+
+  let err = cpu.bus.store(BASE, 32, 0).unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "Memory protection violation: Write to 0x80000000 denied by .text (r-x)"
+  );
+}
@@ -0,0 +1,262 @@
+mod common;
+
+use common::{i_type, r_type, retired, BASE};
+use mips::emulator::{
+  arch::{Isa, Register},
+  cpu::{Cpu, StepOutcome},
+  decode,
+  interrupt::ExceptionInterrupt,
+};
+
+const T0: usize = Register::T0;
+const T1: usize = Register::T1;
+const T2: usize = Register::T2;
+const RA: usize = Register::RA;
+
+/// BC and BALC take a 26-bit word offset.
+fn j_type(opcode: u32, offset: i32) -> u32 {
+  (opcode << 26) | (offset as u32 & 0x3ff_ffff)
+}
+
+/// BEQZC and BNEZC take a 21-bit word offset.
+fn b21(opcode: u32, rs: usize, offset: i32) -> u32 {
+  (opcode << 26) | ((rs as u32) << 21) | (offset as u32 & 0x1f_ffff)
+}
+
+/// Loads `code` at the reset PC of a Release 6 CPU.
+fn setup(code: &[u32], regs: &[(usize, u32)]) -> Cpu {
+  let mut cpu = common::setup(code, &[], regs);
+  cpu.isa = Isa::Mips32R6;
+  cpu
+}
+
+fn exec(inst: u32, regs: &[(usize, u32)]) -> Cpu {
+  let mut cpu = setup(&[inst], regs);
+  retired(cpu.step());
+  cpu
+}
+
+fn unsupported(res: StepOutcome) -> bool {
+  matches!(
+    res,
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::UNSUPPORTED(_),
+      ..
+    }
+  )
+}
+
+/* ----- BC / BALC ----- */
+
+#[test]
+fn bc_is_relative_to_the_next_instruction() {
+  let cpu = exec(j_type(0x32, 3), &[]);
+  assert_eq!(cpu.pc, BASE + 4 + 12);
+
+  let cpu = exec(j_type(0x32, -1), &[]);
+  assert_eq!(cpu.pc, BASE);
+}
+
+#[test]
+fn bc_offset_spans_26_bits() {
+  let cpu = exec(j_type(0x32, -(1 << 25)), &[]);
+  assert_eq!(cpu.pc, (BASE + 4).wrapping_sub(1 << 27));
+
+  let cpu = exec(j_type(0x32, (1 << 25) - 1), &[]);
+  assert_eq!(cpu.pc, BASE + 4 + (1 << 27) - 4);
+}
+
+#[test]
+fn balc_links_the_next_instruction() {
+  let cpu = exec(j_type(0x3A, -2), &[]);
+  assert_eq!(cpu.pc, BASE - 4);
+  assert_eq!(cpu.regs[RA], BASE + 4);
+}
+
+#[test]
+fn compact_jumps_have_no_delay_slot() {
+  /* BC over an ADDIU that would run as a delay slot in earlier releases */
+  let mut cpu = setup(&[j_type(0x32, 1), i_type(0x09, 0, T0, 1), 0], &[]);
+  cpu.delay_slots = true;
+  retired(cpu.step());
+  assert_eq!(cpu.pc, BASE + 8);
+  retired(cpu.step());
+  assert_eq!(cpu.regs[T0], 0);
+}
+
+/* ----- Compact branches ----- */
+
+#[test]
+fn beqzc_and_bnezc_use_21_bit_offsets() {
+  let cpu = exec(b21(0x36, T0, -(1 << 20)), &[(T0, 0)]);
+  assert_eq!(cpu.pc, (BASE + 4).wrapping_sub(1 << 22));
+
+  let cpu = exec(b21(0x3E, T0, 4), &[(T0, 1)]);
+  assert_eq!(cpu.pc, BASE + 4 + 16);
+
+  let cpu = exec(b21(0x36, T0, 4), &[(T0, 1)]);
+  assert_eq!(cpu.pc, BASE + 4);
+}
+
+#[test]
+fn register_compact_branches_compare_as_encoded() {
+  let taken = BASE + 4 + 8;
+  let cases = [
+    /* BEQC, BNEC */
+    (i_type(0x08, T0, T1, 2), 5, 5, taken),
+    (i_type(0x18, T0, T1, 2), 5, 5, BASE + 4),
+    /* BLTC, BGEC are signed */
+    (i_type(0x17, T0, T1, 2), -1i32 as u32, 1, taken),
+    (i_type(0x16, T0, T1, 2), -1i32 as u32, 1, BASE + 4),
+    /* BLTUC, BGEUC are unsigned */
+    (i_type(0x07, T0, T1, 2), -1i32 as u32, 1, BASE + 4),
+    (i_type(0x06, T0, T1, 2), -1i32 as u32, 1, taken),
+  ];
+
+  for (inst, a, b, pc) in cases {
+    let cpu = exec(inst, &[(T0, a), (T1, b)]);
+    assert_eq!(cpu.pc, pc, "{inst:#010x}");
+    assert_eq!(cpu.regs[RA], 0, "{inst:#010x}");
+  }
+}
+
+#[test]
+fn bovc_branches_on_signed_overflow() {
+  let bovc = i_type(0x08, T1, T0, 2);
+  let cpu = exec(bovc, &[(T0, i32::MAX as u32), (T1, 1)]);
+  assert_eq!(cpu.pc, BASE + 12);
+  let cpu = exec(bovc, &[(T0, u32::MAX), (T1, 1)]);
+  assert_eq!(cpu.pc, BASE + 4);
+
+  let bnvc = i_type(0x18, T1, T0, 2);
+  let cpu = exec(bnvc, &[(T0, u32::MAX), (T1, 1)]);
+  assert_eq!(cpu.pc, BASE + 12);
+}
+
+#[test]
+fn linking_compact_branches_link_whether_taken_or_not() {
+  /* BEQZALC */
+  let cpu = exec(i_type(0x08, 0, T0, 0xfffe), &[(T0, 0)]);
+  assert_eq!(cpu.pc, BASE - 4);
+  assert_eq!(cpu.regs[RA], BASE + 4);
+
+  /* BLTZALC */
+  let cpu = exec(i_type(0x07, T0, T0, 2), &[(T0, 1)]);
+  assert_eq!(cpu.pc, BASE + 4);
+  assert_eq!(cpu.regs[RA], BASE + 4);
+}
+
+#[test]
+fn jic_and_jialc_add_the_offset_to_a_register() {
+  let cpu = exec(i_type(0x36, 0, T0, 0xfffc), &[(T0, BASE + 0x100)]);
+  assert_eq!(cpu.pc, BASE + 0xfc);
+  assert_eq!(cpu.regs[RA], 0);
+
+  let cpu = exec(i_type(0x3E, 0, T0, 8), &[(T0, BASE + 0x100)]);
+  assert_eq!(cpu.pc, BASE + 0x108);
+  assert_eq!(cpu.regs[RA], BASE + 4);
+}
+
+#[test]
+fn forbidden_slot_rejects_control_transfers() {
+  /* A not-taken BEQZC followed by BC */
+  let mut cpu = setup(&[b21(0x36, T0, 4), j_type(0x32, 0)], &[(T0, 1)]);
+  retired(cpu.step());
+  assert!(matches!(
+    cpu.step(),
+    StepOutcome::Exception {
+      kind: ExceptionInterrupt::FORBIDDEN(_),
+      pc,
+      ..
+    } if pc == BASE + 4
+  ));
+
+  /* Anything else may follow */
+  let mut cpu = setup(&[b21(0x36, T0, 4), i_type(0x09, 0, T1, 1)], &[(T0, 1)]);
+  retired(cpu.step());
+  retired(cpu.step());
+  assert_eq!(cpu.regs[T1], 1);
+}
+
+/* ----- Multiply and divide ----- */
+
+#[test]
+fn mul_and_muh_split_the_product() {
+  let a = -3i32 as u32;
+  let b = 0x4000_0000;
+
+  let cpu = exec(r_type(0x18, T0, T1, T2, 2), &[(T0, a), (T1, b)]);
+  assert_eq!(cpu.regs[T2], 0x4000_0000);
+  let cpu = exec(r_type(0x18, T0, T1, T2, 3), &[(T0, a), (T1, b)]);
+  assert_eq!(cpu.regs[T2], 0xffff_ffff);
+
+  let cpu = exec(r_type(0x19, T0, T1, T2, 2), &[(T0, a), (T1, b)]);
+  assert_eq!(cpu.regs[T2], 0x4000_0000);
+  let cpu = exec(r_type(0x19, T0, T1, T2, 3), &[(T0, a), (T1, b)]);
+  assert_eq!(cpu.regs[T2], 0x3fff_ffff);
+}
+
+#[test]
+fn div_and_mod_write_a_register() {
+  let regs = [(T0, -7i32 as u32), (T1, 2)];
+  let cpu = exec(r_type(0x1A, T0, T1, T2, 2), &regs);
+  assert_eq!(cpu.regs[T2] as i32, -3);
+  let cpu = exec(r_type(0x1A, T0, T1, T2, 3), &regs);
+  assert_eq!(cpu.regs[T2] as i32, -1);
+
+  let cpu = exec(r_type(0x1B, T0, T1, T2, 2), &regs);
+  assert_eq!(cpu.regs[T2], 0x7fff_fffc);
+  let cpu = exec(r_type(0x1B, T0, T1, T2, 3), &regs);
+  assert_eq!(cpu.regs[T2], 1);
+
+  /* hi and lo are untouched */
+  assert_eq!((cpu.hi, cpu.lo), (0, 0));
+}
+
+#[test]
+fn division_by_zero_and_overflow_do_not_trap() {
+  let cpu = exec(r_type(0x1A, T0, T1, T2, 2), &[(T0, 5), (T1, 0), (T2, 9)]);
+  assert_eq!(cpu.regs[T2], 0);
+
+  let min = [(T0, i32::MIN as u32), (T1, -1i32 as u32)];
+  let cpu = exec(r_type(0x1A, T0, T1, T2, 2), &min);
+  assert_eq!(cpu.regs[T2], i32::MIN as u32);
+  let cpu = exec(r_type(0x1A, T0, T1, T2, 3), &min);
+  assert_eq!(cpu.regs[T2], 0);
+}
+
+#[test]
+fn pre_r6_multiply_and_divide_are_removed() {
+  for inst in [
+    r_type(0x18, T0, T1, 0, 0),
+    r_type(0x1B, T0, T1, 0, 0),
+    r_type(0x10, 0, 0, T2, 0),
+  ] {
+    let mut cpu = setup(&[inst], &[]);
+    assert!(unsupported(cpu.step()), "{inst:#010x}");
+  }
+
+  /* The same encodings still work before Release 6 */
+  let mut cpu = setup(&[r_type(0x18, T0, T1, 0, 0)], &[(T0, 3), (T1, 4)]);
+  cpu.isa = Isa::default();
+  retired(cpu.step());
+  assert_eq!(cpu.lo, 12);
+}
+
+#[test]
+fn jr_and_register_conditional_links_are_removed() {
+  let bltzal = i_type(0x01, T0, 0x10, 2);
+  let bgezal = i_type(0x01, T0, 0x11, 2);
+  for inst in [r_type(0x08, RA, 0, 0, 0), bltzal, bgezal] {
+    let mut cpu = setup(&[inst], &[]);
+    assert!(unsupported(cpu.step()), "{inst:#010x}");
+    assert_eq!(decode::link_reg(inst, Isa::Mips32R6), None);
+  }
+
+  /* BAL is BGEZAL $zero and still links */
+  let bal = i_type(0x01, 0, 0x11, 2);
+  let cpu = exec(bal, &[]);
+  assert_eq!(cpu.regs[RA], BASE + 4);
+  assert_eq!(decode::link_reg(bal, Isa::Mips32R6), Some(RA));
+  assert_eq!(decode::link_reg(bgezal, Isa::Mips32), Some(RA));
+}
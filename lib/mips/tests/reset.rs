@@ -0,0 +1,67 @@
+use mips::{
+  emulator::{
+    arch::Register,
+    bus::Bus,
+    cpu::{Cpu, StepOutcome},
+    interrupt::{ExceptionInterrupt, Interrupt, Result, SoftwareInterrupt},
+    virt::MemMap,
+  },
+  interrupt_exception, interrupt_software,
+};
+
+const BASE: u32 = MemMap::HIGHMEM.base;
+
+#[test]
+fn pc_starts_at_the_absolute_base_of_dram() {
+  let mut cpu = Cpu::new();
+  assert_eq!(cpu.pc, BASE);
+
+  cpu.load(0x2408_0005u32.to_le_bytes().to_vec()).unwrap();
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  assert_eq!(cpu.pc, BASE + 4);
+  assert_eq!(cpu.regs[Register::T0], 5);
+}
+
+#[test]
+fn sw_and_lw_round_trip() {
+  /* sw $t1, 0x100($t0); lw $t2, 0x100($t0) */
+  let code: Vec<u8> = [0xAD09_0100u32, 0x8D0A_0100]
+    .iter()
+    .flat_map(|w| w.to_le_bytes())
+    .collect();
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+  cpu.regs[Register::T0] = BASE;
+  cpu.regs[Register::T1] = 0xdead_beef;
+
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  assert!(matches!(cpu.step(), StepOutcome::Retired));
+  assert_eq!(cpu.regs[Register::T2], 0xdead_beef);
+}
+
+#[test]
+fn bus_store_writes_through_to_dram() {
+  let mut bus = Bus::default();
+  bus.store(BASE + 8, 16, 0xabcd).unwrap();
+  assert_eq!(bus.dram.load(BASE + 8, 16).unwrap(), 0xabcd);
+}
+
+fn overflow() -> Result<()> {
+  interrupt_exception!(OVF)
+}
+
+fn unsupported(code: u32) -> Result<()> {
+  interrupt_software!(UNSUPPORTED(code))
+}
+
+#[test]
+fn interrupt_macros_expand_outside_the_crate() {
+  assert!(matches!(
+    overflow(),
+    Err(Interrupt::Exception(ExceptionInterrupt::OVF))
+  ));
+  assert!(matches!(
+    unsupported(9),
+    Err(Interrupt::Software(SoftwareInterrupt::UNSUPPORTED(9)))
+  ));
+}
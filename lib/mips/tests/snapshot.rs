@@ -0,0 +1,94 @@
+use std::io::{empty, sink};
+
+use mips::emulator::{
+  arch::Register,
+  cpu::Cpu,
+  sys::{Stack, Sys, HEAP_BASE},
+  virt::{MemMap, MemRegion},
+  watch::WatchKind,
+};
+
+const DATA: u32 = MemMap::HIGHMEM.base + 0x100;
+
+fn program() -> Cpu {
+  let code: Vec<u8> = [
+    0x3c09_8000u32, // lui $t1, 0x8000
+    0x2508_0007,    // addiu $t0, $t0, 7
+    0xad28_0100,    // sw $t0, 0x100($t1)
+  ]
+  .iter()
+  .flat_map(|w| w.to_le_bytes())
+  .collect();
+
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+  cpu
+}
+
+#[test]
+fn restore_rewinds_registers_and_memory() {
+  let mut sys = Sys::new(program(), sink(), empty());
+  let snapshot = sys.snapshot();
+
+  sys.run().unwrap();
+  assert_eq!(sys.cpu().bus.load(DATA, 32).unwrap(), 7);
+
+  sys.restore(&snapshot);
+  assert_eq!(sys.cpu().pc, MemMap::HIGHMEM.base);
+  assert_eq!(sys.cpu().regs[Register::T0], 0);
+  assert_eq!(sys.cpu().bus.load(DATA, 32).unwrap(), 0);
+
+  sys.run().unwrap();
+  assert_eq!(sys.cpu().bus.load(DATA, 32).unwrap(), 7);
+}
+
+#[test]
+fn restore_rewinds_the_stack_check() {
+  let mut cpu = Cpu::new();
+  cpu
+    .load(0x3c1d_8010u32.to_le_bytes().to_vec()) // lui $sp, 0x8010
+    .unwrap();
+  let mut sys = Sys::new(cpu, sink(), empty());
+  let snapshot = sys.snapshot();
+  let stack = sys.stack();
+
+  sys.set_stack(Stack::below(HEAP_BASE + 0x2000, 0x1000));
+  sys.run().unwrap();
+  assert!(!sys.stack_checked());
+
+  sys.restore(&snapshot);
+  assert!(sys.stack_checked());
+  assert_eq!(sys.stack(), stack);
+}
+
+#[test]
+fn watch_ids_stay_unique_across_restore() {
+  let region = MemRegion {
+    base: DATA,
+    size: 4,
+  };
+  let mut sys = Sys::new(program(), sink(), empty());
+  let snapshot = sys.snapshot();
+
+  let first = sys.cpu_mut().bus.watch(region, WatchKind::Write);
+  sys.restore(&snapshot);
+  let second = sys.cpu_mut().bus.watch(region, WatchKind::Read);
+
+  assert_ne!(first, second);
+  let ids: Vec<_> = sys.cpu().bus.watchpoints().iter().map(|w| w.id).collect();
+  assert_eq!(ids, [first, second]);
+}
+
+#[test]
+fn fork_does_not_affect_parent() {
+  let mut parent = Sys::new(program(), sink(), empty());
+  let mut child = parent.fork(sink(), empty());
+
+  child.cpu_mut().regs[Register::T0] = 35;
+  child.run().unwrap();
+  assert_eq!(child.cpu().bus.load(DATA, 32).unwrap(), 42);
+  assert_eq!(parent.cpu().bus.load(DATA, 32).unwrap(), 0);
+
+  parent.run().unwrap();
+  assert_eq!(parent.cpu().bus.load(DATA, 32).unwrap(), 7);
+}
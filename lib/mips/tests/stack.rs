@@ -0,0 +1,123 @@
+use std::io::{empty, sink};
+
+use mips::emulator::{
+  arch::Register,
+  cpu::Cpu,
+  interrupt::{Interrupt, SoftwareInterrupt, StackFault, StackFaultKind},
+  sys::{Stack, Sys, HEAP_BASE, STACK_SIZE},
+  virt::{MemMap, MemRegion},
+  watch::WatchKind,
+};
+
+const BASE: u32 = MemMap::HIGHMEM.base;
+
+fn sys(code: &[u32]) -> Sys {
+  let code: Vec<u8> = code.iter().flat_map(|w| w.to_le_bytes()).collect();
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+  Sys::new(cpu, sink(), empty())
+}
+
+fn stack_fault(res: Result<(), Interrupt>) -> StackFault {
+  match res {
+    Err(Interrupt::Software(SoftwareInterrupt::STACK(fault))) => fault,
+    other => panic!("expected a stack fault, got {other:?}"),
+  }
+}
+
+#[test]
+fn sp_starts_at_stack_top() {
+  let sys = sys(&[]);
+  let stack = sys.stack();
+  assert_eq!(sys.cpu().regs[Register::SP], stack.top);
+  assert_eq!(stack.top - stack.limit, STACK_SIZE - 4);
+}
+
+#[test]
+fn runaway_recursion_overflows() {
+  let mut sys = sys(&[
+    0x27bd_f000, // addiu $sp, $sp, -4096
+    0x1000_fffe, // beq $0, $0, -2
+  ]);
+  let top = sys.stack().top;
+
+  let fault = stack_fault(sys.run());
+  assert_eq!(fault.kind, StackFaultKind::Overflow);
+  assert_eq!(fault.pc, BASE);
+  assert_eq!(fault.inst, 0x27bd_f000);
+  assert!(fault.depth > STACK_SIZE - 4);
+  assert_eq!(fault.sp, top - fault.depth);
+}
+
+#[test]
+fn stack_into_heap_collides() {
+  let mut sys = sys(&[
+    0x2404_1000, // addiu $a0, $0, 0x1000
+    0x2402_0009, // addiu $v0, $0, 9
+    0x0000_000c, // syscall
+    0x27bd_f000, // addiu $sp, $sp, -4096
+    0x1000_fffe, // beq $0, $0, -2
+  ]);
+  sys.set_stack(Stack {
+    top: HEAP_BASE + 0x1ffc,
+    limit: HEAP_BASE,
+  });
+
+  let fault = stack_fault(sys.run());
+  assert_eq!(fault.kind, StackFaultKind::HeapCollision);
+  assert_eq!(fault.sp, HEAP_BASE + 0xffc);
+  assert_eq!(fault.pc, BASE + 12);
+  assert_eq!(sys.brk(), HEAP_BASE + 0x1000);
+}
+
+#[test]
+fn program_can_set_up_its_own_stack() {
+  let mut sys = sys(&[
+    0x3c1d_8010, // lui $sp, 0x8010
+    0x27bd_fff0, // addiu $sp, $sp, -16
+    0xafa0_0000, // sw $zero, 0($sp)
+  ]);
+  assert!(sys.stack_checked());
+
+  sys.run().unwrap();
+  assert!(!sys.stack_checked());
+  assert_eq!(sys.cpu().regs[Register::SP], 0x800f_fff0);
+
+  /* Placing the stack again brings the check back */
+  sys.set_stack(Stack::below(HEAP_BASE + 0x2000, 0x1000));
+  assert!(sys.stack_checked());
+}
+
+#[test]
+fn preset_sp_outside_the_stack_is_not_checked() {
+  let mut cpu = Cpu::new();
+  cpu
+    .load(0x27bd_fff0u32.to_le_bytes().to_vec()) // addiu $sp, $sp, -16
+    .unwrap();
+  cpu.regs[Register::SP] = BASE + 0x10_0000;
+
+  let mut sys = Sys::new(cpu, sink(), empty());
+  assert!(!sys.stack_checked());
+  sys.run().unwrap();
+}
+
+#[test]
+fn stack_fault_is_reported_before_a_watchpoint() {
+  let mut sys = sys(&[
+    0x8fbd_0000, // lw $sp, 0($sp)
+  ]);
+  let stack = sys.stack();
+  let bus = &mut sys.cpu_mut().bus;
+  bus.store(stack.top, 32, stack.limit - 0x100).unwrap();
+  bus.watch(
+    MemRegion {
+      base: stack.top,
+      size: 4,
+    },
+    WatchKind::Read,
+  );
+
+  let fault = stack_fault(sys.run());
+  assert_eq!(fault.kind, StackFaultKind::Overflow);
+  assert_eq!(fault.pc, BASE);
+}
@@ -0,0 +1,183 @@
+use mips::{
+  emulator::{
+    interrupt::{Interrupt, SoftwareInterrupt},
+    sys::HEAP_BASE,
+    vfs::{OpenMode, Vfs, FIRST_FD},
+  },
+  Console, Machine, Register,
+};
+
+fn run(src: &str) -> Machine {
+  let mut machine = Machine::builder().source(src).build().unwrap();
+  machine.run().unwrap();
+  machine
+}
+
+/* ----- SBRK ----- */
+
+#[test]
+fn sbrk_hands_out_word_aligned_blocks() {
+  let machine = run(
+    "
+        li    $a0, 5
+        li    $v0, 9
+        syscall
+        move  $t0, $v0
+        li    $a0, 8
+        li    $v0, 9
+        syscall
+        move  $t1, $v0
+        sw    $t1, 0($t0)
+  ",
+  );
+
+  let sys = machine.sys();
+  let r = &sys.cpu().regs;
+  assert_eq!(r[Register::T0], HEAP_BASE);
+  assert_eq!(r[Register::T1], HEAP_BASE + 8);
+  assert_eq!(sys.brk(), HEAP_BASE + 16);
+  assert_eq!(sys.cpu().bus.load(HEAP_BASE, 32).unwrap(), HEAP_BASE + 8);
+}
+
+#[test]
+fn sbrk_refuses_to_shrink_or_reach_the_stack() {
+  let fails = |src| {
+    let mut machine = Machine::builder().source(src).build().unwrap();
+    matches!(
+      machine.run(),
+      Err(Interrupt::Software(SoftwareInterrupt::HEAP(..)))
+    )
+  };
+
+  assert!(fails("li $a0, -4\nli $v0, 9\nsyscall"));
+  assert!(fails("move $a0, $sp\nli $v0, 9\nsyscall"));
+}
+
+/* ----- Files ----- */
+
+#[test]
+fn writes_and_appends_to_files() {
+  let src = r#"
+        .data
+path:   .asciiz "out.txt"
+one:    .ascii "abc"
+two:    .ascii "de"
+
+        .text
+        la    $a0, path
+        li    $a1, 1
+        li    $v0, 13
+        syscall
+        move  $s0, $v0
+        move  $a0, $s0
+        la    $a1, one
+        li    $a2, 3
+        li    $v0, 15
+        syscall
+        move  $a0, $s0
+        li    $v0, 16
+        syscall
+
+        la    $a0, path
+        li    $a1, 9
+        li    $v0, 13
+        syscall
+        move  $a0, $v0
+        la    $a1, two
+        li    $a2, 2
+        li    $v0, 15
+        syscall
+        move  $t0, $v0
+  "#;
+
+  let machine = run(src);
+  let sys = machine.sys();
+  assert_eq!(sys.cpu().regs[Register::S0], FIRST_FD);
+  assert_eq!(sys.cpu().regs[Register::T0], 2);
+  assert_eq!(sys.vfs().get("out.txt"), Some(&b"abcde"[..]));
+  assert_eq!(sys.vfs().open_handles(), 1);
+}
+
+#[test]
+fn file_errors_return_minus_one() {
+  let src = r#"
+        .data
+path:   .asciiz "missing.txt"
+buf:    .space 4
+
+        .text
+        la    $a0, path
+        li    $a1, 0
+        li    $v0, 13
+        syscall
+        move  $t0, $v0
+        la    $a0, path
+        li    $a1, 7
+        li    $v0, 13
+        syscall
+        move  $t1, $v0
+        li    $a0, 42
+        la    $a1, buf
+        li    $a2, 4
+        li    $v0, 14
+        syscall
+        move  $t2, $v0
+  "#;
+
+  let machine = run(src);
+  let r = &machine.sys().cpu().regs;
+  assert_eq!(r[Register::T0], u32::MAX);
+  assert_eq!(r[Register::T1], u32::MAX);
+  assert_eq!(r[Register::T2], u32::MAX);
+}
+
+#[test]
+fn stdout_and_stderr_share_the_console() {
+  let src = r#"
+        .data
+msg:    .ascii "hi"
+
+        .text
+        li    $a0, 1
+        la    $a1, msg
+        li    $a2, 2
+        li    $v0, 15
+        syscall
+        li    $a0, 2
+        li    $v0, 15
+        syscall
+  "#;
+
+  let console = Console::new();
+  let mut machine = Machine::builder()
+    .source(src)
+    .console(&console)
+    .build()
+    .unwrap();
+  machine.run().unwrap();
+  assert_eq!(console.output(), "hihi");
+}
+
+#[test]
+fn vfs_enforces_open_modes() {
+  let mut vfs = Vfs::new();
+  vfs.insert("a", "xyz");
+
+  let read = vfs.open("a", OpenMode::Read).unwrap();
+  assert_eq!(vfs.write(read, b"!"), None);
+  assert_eq!(vfs.read(read, 2).unwrap(), b"xy");
+  assert_eq!(vfs.read(read, 2).unwrap(), b"z");
+  assert_eq!(vfs.read(read, 2).unwrap(), b"");
+
+  let write = vfs.open("a", OpenMode::Write).unwrap();
+  assert_ne!(read, write);
+  assert_eq!(vfs.get("a"), Some(&b""[..]));
+  assert_eq!(vfs.read(write, 1), None);
+  assert_eq!(vfs.write(write, b"new"), Some(3));
+  assert_eq!(vfs.get("a"), Some(&b"new"[..]));
+
+  assert!(vfs.close(read));
+  assert!(!vfs.close(read));
+  assert_eq!(vfs.open_handles(), 1);
+  assert_eq!(vfs.open("b", OpenMode::Read), None);
+}
@@ -0,0 +1,99 @@
+use std::{
+  io::{empty, sink},
+  sync::{Arc, Mutex},
+};
+
+use mips::emulator::{
+  arch::Register,
+  cpu::Cpu,
+  interrupt::{Interrupt, SoftwareInterrupt},
+  sys::Sys,
+  virt::{Access, MemMap, MemRegion},
+  watch::{WatchHit, WatchKind},
+};
+
+const BASE: u32 = MemMap::HIGHMEM.base;
+const DATA: MemRegion = MemRegion {
+  base: BASE + 0x100,
+  size: 4,
+};
+
+fn sys() -> Sys {
+  let code: Vec<u8> = [
+    0x3c09_8000u32, // lui $t1, 0x8000
+    0x2408_0007,    // addiu $t0, $0, 7
+    0xad28_0100,    // sw $t0, 0x100($t1)
+    0x8d2a_0100,    // lw $t2, 0x100($t1)
+  ]
+  .iter()
+  .flat_map(|w| w.to_le_bytes())
+  .collect();
+
+  let mut cpu = Cpu::new();
+  cpu.load(code).unwrap();
+  Sys::new(cpu, sink(), empty())
+}
+
+#[test]
+fn write_watchpoint_pauses_after_store() {
+  let mut sys = sys();
+  let id = sys.cpu_mut().bus.watch(DATA, WatchKind::Write);
+
+  match sys.run() {
+    Err(Interrupt::Software(SoftwareInterrupt::WATCH(hit))) => assert_eq!(
+      hit,
+      WatchHit {
+        id,
+        pc: BASE + 8,
+        addr: DATA.base,
+        size: 32,
+        access: Access::Write,
+        old: 0,
+        new: 7,
+      }
+    ),
+    other => panic!("expected a watchpoint hit, got {other:?}"),
+  }
+  assert_eq!(sys.cpu().pc, BASE + 12);
+
+  /* The load does not trigger a write watchpoint */
+  sys.run().unwrap();
+  assert_eq!(sys.cpu().regs[Register::T2], 7);
+}
+
+#[test]
+fn callback_watchpoint_reports_without_pausing() {
+  let mut sys = sys();
+  let hits = Arc::new(Mutex::new(Vec::new()));
+  let log = hits.clone();
+  sys
+    .cpu_mut()
+    .bus
+    .watch_with(DATA, WatchKind::ReadWrite, move |hit| {
+      log.lock().unwrap().push((hit.pc, hit.access))
+    });
+
+  sys.run().unwrap();
+  assert_eq!(
+    *hits.lock().unwrap(),
+    [(BASE + 8, Access::Write), (BASE + 12, Access::Read)]
+  );
+}
+
+#[test]
+fn unwatched_accesses_run_through() {
+  let mut sys = sys();
+  let bus = &mut sys.cpu_mut().bus;
+  let id = bus.watch(DATA, WatchKind::ReadWrite);
+  bus.watch(
+    MemRegion {
+      base: DATA.end(),
+      size: 4,
+    },
+    WatchKind::ReadWrite,
+  );
+  assert!(bus.unwatch(id));
+  assert!(!bus.unwatch(id));
+
+  sys.run().unwrap();
+}